@@ -0,0 +1,49 @@
+use _bima::simulation::soa::Particles;
+use bima_rs::body::Body;
+use bima_rs::close_encounter::CloseEncounter;
+use bima_rs::force;
+use bima_rs::vec3::Vec3;
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+
+const EPS: f64 = 0.01;
+
+fn bodies(n: usize) -> Vec<Body> {
+    (0..n)
+        .map(|i| {
+            let f = i as f64;
+            let r = Vec3::new(f.sin(), (1.7 * f).cos(), (0.3 * f).sin() * f.cos());
+            Body::new(i, 1.0 / n as f64, r, Vec3::zero(), None)
+        })
+        .collect()
+}
+
+// The accelerations of every body, once with the scalar sum over `Vec<Body>`
+// that is used for the other close encounter methods, once with the SIMD
+// kernel over `Particles`.
+fn direct_soften(c: &mut Criterion) {
+    let mut group = c.benchmark_group("direct_soften");
+    for n in [16, 256, 2048] {
+        let bodies = bodies(n);
+        group.bench_with_input(BenchmarkId::new("scalar", n), &bodies, |b, bodies| {
+            b.iter(|| {
+                bodies
+                    .iter()
+                    .map(|body| force::direct(body, bodies, &CloseEncounter::Soften(EPS), None))
+                    .fold(Vec3::zero(), |total, a| total + a)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("simd", n), &bodies, |b, bodies| {
+            b.iter(|| {
+                let particles = Particles::from_bodies(black_box(bodies));
+                bodies
+                    .iter()
+                    .map(|body| particles.soften(body.r, EPS, Some(body.id)))
+                    .fold(Vec3::zero(), |total, a| total + a)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, direct_soften);
+criterion_main!(benches);
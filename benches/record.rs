@@ -0,0 +1,92 @@
+use _bima::simulation::cm::Barycentre;
+use _bima::simulation::store::Store;
+use _bima::units::UnitSystem;
+use bima_rs::record::Record;
+use bima_rs::record::line::Line;
+use bima_rs::vec3::Vec3;
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+fn cm() -> Barycentre {
+    Barycentre {
+        r: Vec3::new(0.1, -0.2, 0.3),
+        v: Vec3::new(0.01, 0.0, -0.02),
+        t: 0.0,
+    }
+}
+
+fn lines(n: usize, save_acc: bool) -> Vec<Line> {
+    (0..n)
+        .map(|i| {
+            let t = i as f64 * 1e-3;
+            let r = Vec3::new(t.cos(), t.sin(), 0.0);
+            let v = Vec3::new(-t.sin(), t.cos(), 0.0);
+            Line::new(t, r, v, save_acc.then_some(-1.0 * r))
+        })
+        .collect()
+}
+
+// Lines written per second by `Store::append`, one chunk of every size to a
+// new dataset of each column. The file is made again for every chunk, so that
+// none of them is appended to the ones before.
+fn append(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("bima-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut group = c.benchmark_group("store_append");
+    for save_acc in [false, true] {
+        let path = dir.join(format!("append-{}.h5", save_acc));
+        let store = || {
+            Store::new(
+                path.clone(),
+                1,
+                vec![1.0],
+                &UnitSystem::nbody(),
+                true,
+                save_acc,
+            )
+            .unwrap()
+        };
+        for n in [1_024, 65_536] {
+            let name = if save_acc { "acc" } else { "no_acc" };
+            group.throughput(Throughput::Elements(n as u64));
+            group.bench_function(BenchmarkId::new(name, n), |b| {
+                // one at a time, the file of the last one is closed when it
+                // is dropped, out of the timing
+                b.iter_batched(
+                    || (store(), lines(n, save_acc)),
+                    |(mut store, lines)| {
+                        store.append(0, lines, &cm()).unwrap();
+                        store
+                    },
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+    }
+    group.finish();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// Lines turned into the rows handed to Python by `Barycentre::to_vec`, which
+// takes the place of `Record::to_vec` to add back the centre of mass.
+fn to_vec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("record_to_vec");
+    for (objects, n) in [(2, 65_536), (1_000, 128)] {
+        let mut record = Record::empty(&vec![1.0; objects], true);
+        for i in 0..objects {
+            record.add_many(i, lines(n, true));
+        }
+        group.throughput(Throughput::Elements((objects * n) as u64));
+        let (cm, id) = (cm(), format!("{}x{}", objects, n));
+        group.bench_with_input(BenchmarkId::from_parameter(id), &record, |b, record| {
+            b.iter_batched(
+                || record.clone(),
+                |record| cm.to_vec(record),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, append, to_vec);
+criterion_main!(benches);
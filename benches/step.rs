@@ -0,0 +1,85 @@
+use _bima::initial::Initial;
+use _bima::simulation::Simulation;
+use _bima::simulation::driver::{Options, run_memory};
+use _bima::units::UnitSystem;
+use bima_rs::vec3::Vec3;
+use criterion::{
+    BenchmarkId, Criterion, SamplingMode, Throughput, criterion_group, criterion_main,
+};
+
+const DELTA_T: f64 = 1e-3;
+// steps of every run, reported as elements so criterion gives steps/s
+const STEPS: u64 = 4;
+
+fn simulation(n: usize) -> Simulation {
+    let initial: Vec<Initial> = (0..n)
+        .map(|i| {
+            let f = i as f64;
+            Initial {
+                m: 1.0 / n as f64,
+                r: Vec3::new(f.sin(), (1.7 * f).cos(), (0.3 * f).sin() * f.cos()),
+                v: 0.1 * Vec3::new((0.7 * f).cos(), (1.3 * f).sin(), (2.9 * f).cos()),
+                beta: 0.0,
+                tau: None,
+            }
+        })
+        .collect();
+    Simulation::from_initial(&initial, UnitSystem::nbody()).unwrap()
+}
+
+fn options(force_method: u8, integrator: u8) -> Options {
+    Options {
+        force_method,
+        integrator,
+        // softened, so close pairs do not slow down the steps
+        close_encounter: 1,
+        ce_par: Some(0.01),
+        delta_t: Some(DELTA_T),
+        t_stop: STEPS as f64 * DELTA_T,
+        threads: 1,
+        ..Default::default()
+    }
+}
+
+// Steps of the leapfrog with the direct sum and with the fast multipole
+// method. The octree of `bima_rs` is not implemented yet, the fast multipole
+// method is the tree code here. The direct sum stops at 10⁴ bodies, a step
+// of 10⁵ takes tens of seconds.
+fn force_methods(c: &mut Criterion) {
+    let mut group = c.benchmark_group("force_method");
+    group.sample_size(10);
+    group.sampling_mode(SamplingMode::Flat);
+    group.throughput(Throughput::Elements(STEPS));
+    for n in [10, 100, 1_000, 10_000, 100_000] {
+        let simulation = simulation(n);
+        for (name, force_method) in [("direct", 0), ("fmm", 2)] {
+            if force_method == 0 && n > 10_000 {
+                continue;
+            }
+            group.bench_with_input(BenchmarkId::new(name, n), &simulation, |b, simulation| {
+                b.iter(|| run_memory(simulation, options(force_method, 3), &mut ()).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+// Steps of every integrator of `get_integrator` with the direct sum.
+fn integrators(c: &mut Criterion) {
+    let mut group = c.benchmark_group("integrator");
+    group.throughput(Throughput::Elements(STEPS));
+    let simulation = simulation(100);
+    for (name, integrator) in [("euler", 0), ("rk4", 1), ("bs", 2), ("leap_frog", 3)] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &simulation,
+            |b, simulation| {
+                b.iter(|| run_memory(simulation, options(0, integrator), &mut ()).unwrap())
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, force_methods, integrators);
+criterion_main!(benches);
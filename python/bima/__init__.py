@@ -11,12 +11,20 @@ from bima.method.close_encounter import CloseEncounterMethod
 from bima.method.force import ForceMethod
 from bima.method.integrator import Integrator
 from bima.method.timestep import TimestepMethod
+from bima.method.boundary import Boundary
+from bima.method.event import Event
+from bima.method.potential import Potential
+from bima.method.nongravity import NonGravity
+from bima.method.cluster import Cluster, MassFunction
 from bima.simulation import Simulation
 from bima.simulation import Config
+from bima.ensemble import Ensemble
 from bima.energy import Energy
+from bima.units import UnitSystem
+from bima.elements import Elements
 from bima.body import Body
 
 # (Optional) Clean up namespace
 __all__ = ["Initial", "CloseEncounterMethod", "ForceMethod",
            "Integrator", "TimestepMethod", "Simulation", 
-           "Config", "Energy", "Body" "__version__"]
+           "Config", "Ensemble", "Energy", "Elements", "Body", "Boundary", "Event", "Potential", "NonGravity", "Cluster", "MassFunction", "UnitSystem", "__version__"]
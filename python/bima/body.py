@@ -1,10 +1,25 @@
+from typing import Optional
 import numpy as np
 from numpy.typing import NDArray
 
+class Escape:
+    def __init__(self, row: list[float]):
+        self.id = int(row[0])
+        self.t = row[1]
+        self.x, self.y, self.z = row[2], row[3], row[4]
+        self.vx, self.vy, self.vz = row[5], row[6], row[7]
+
+    def __repr__(self) -> str:
+        return f"Escape(id={self.id}, t={self.t})"
+
+    def __str__(self) -> str:
+        return self.__repr__()
+
 class Body:
-    def __init__(self, v: list[list[float]], id: int, m: float):
+    def __init__(self, v: list[list[float]], id: int, m: float, escape: Optional[Escape] = None):
         self.id = id
         self.m = m
+        self.escape = escape
         arr = np.array(v)
         shape = arr.shape
         self.t = arr[:,0]
@@ -71,13 +71,16 @@ class BodyLazy:
 class DiskFile:
     file: Optional[h5py.File] = None
 
-    def __init__(self, path: str, n: int) -> None:
+    def __init__(self, path: str, n: int, n_test: int = 0, group: str = "/") -> None:
         self.path = path
         self.n = n
+        self.n_test = n_test
+        self.group = group
 
     def __enter__(self):
         file = h5py.File(self.path)
         self.file = file
+        self.root = file[self.group]
         return self
 
     def __exit__(self, exc_type, exc_value, traceback):
@@ -98,23 +101,60 @@ class DiskFile:
         if i >= self.n:
             raise ValueError(
                 f"index cannot be larger than the total member: {self.n}")
-        bodies = self.file['objects']
+        bodies = self.root['objects']
         return  BodyLazy(bodies[f"{i}"])
+
+    def get_test(self, i: int) -> BodyLazy:
+        if self.file is None:
+            raise ValueError("No file")
+        if i < 0:
+            raise ValueError("index cannot be negative")
+        if i >= self.n_test:
+            raise ValueError(
+                f"index cannot be larger than the total test particles: {self.n_test}")
+        bodies = self.root['test_particles']
+        return BodyLazy(bodies[f"{i}"])
+
+    def _table(self, name: str) -> dict[str, NDArray[np.float64]]:
+        if self.file is None:
+            raise ValueError("No file")
+        if name not in self.root:
+            return dict()
+        g = self.root[name]
+        return {column: g[column][:] for column in g.keys()}
+
+    def escapes(self) -> dict[str, NDArray[np.float64]]:
+        return self._table("escapes")
+
+    def events(self) -> dict[str, NDArray[np.float64]]:
+        return self._table("events")
+
+    def regularised(self) -> dict[str, NDArray[np.float64]]:
+        return self._table("regularised")
         
 
 
 class Disk:
     _t: Optional[NDArray[np.float64]]
 
-    def __init__(self, path: str):
+    def __init__(self, path: str, group: str = "/"):
         self.path = path
+        # the run of an ensemble sharing the file, "/" for a file of its own
+        self.group = group
         with h5py.File(self.path) as f:
-            self.n = len(f['objects'])
+            root = f[group]
+            self.n = len(root['objects'])
+            # SI value of the units the data is in, with G
+            self.units: dict[str, float | str] = {
+                k: (v.decode() if isinstance(v, bytes) else v) for k, v in root.attrs.items()}
+            self.n_test = len(root['test_particles']) if 'test_particles' in root else 0
 
     def open(self):
-        return DiskFile(self.path, self.n)
+        return DiskFile(self.path, self.n, self.n_test, self.group)
 
     def __repr__(self) -> str:
+        if self.group != "/":
+            return f"Disk(path={self.path}, group={self.group})"
         return f"Disk(path={self.path})"
 
     def __str__(self) -> str:
@@ -0,0 +1,52 @@
+from typing import Union
+from bima.body import Body
+from bima.disk import BodyLazy
+from bima import _bima
+from numpy.typing import NDArray
+import numpy as np
+
+BodyLike = Union[Body, BodyLazy]
+
+
+def _columns(body: BodyLike) -> NDArray[np.float64]:
+    if isinstance(body, BodyLazy):
+        return np.column_stack([body.t(), body.x(), body.y(), body.z(), body.vx(), body.vy(), body.vz()])
+    return np.column_stack([body.t, body.x, body.y, body.z, body.vx, body.vy, body.vz])
+
+
+class Elements:
+    """
+    osculating orbital elements of a body as time series, angles in radians
+    """
+
+    def __init__(self, t: NDArray[np.float64], a: NDArray[np.float64], e: NDArray[np.float64],
+                 i: NDArray[np.float64], node: NDArray[np.float64], peri: NDArray[np.float64],
+                 f: NDArray[np.float64], mean_anomaly: NDArray[np.float64], period: NDArray[np.float64]):
+        self.t = t
+        self.a = a
+        self.e = e
+        self.i = i
+        self.node = node
+        self.peri = peri
+        self.f = f
+        self.mean_anomaly = mean_anomaly
+        self.period = period
+
+    @classmethod
+    def from_bodies(cls, body: BodyLike, primaries: list[BodyLike], g: float = 1.0):
+        """
+        Args:
+            body: body in memory or read from a file
+            primaries: the elements are relative to their centre of mass, a single
+                primary or the inner bodies for jacobi elements
+            g: gravitational constant
+
+        Raises:
+            ValueError: no primaries or their total mass is zero
+        """
+        elements = _bima.calc_elements(_columns(body), body.m, [_columns(p) for p in primaries],
+                                       [p.m for p in primaries], g)
+        return cls(*elements)
+
+    def __len__(self):
+        return len(self.t)
@@ -1,5 +1,7 @@
+from typing import Optional
 from bima.body import Body
 from bima import _bima
+from bima.units import UnitSystem
 from numpy.typing import NDArray
 import numpy as np
 
@@ -10,7 +12,7 @@ class Energy:
         self.e = e
 
     @classmethod
-    def from_bodies(cls, bodies: list[Body]):
+    def from_bodies(cls, bodies: list[Body], units: Optional[UnitSystem] = None):
         objects = []
         masses = []
         for body in bodies:
@@ -26,6 +28,7 @@ class Energy:
                 object.append([t, x, y, z, vx, vy, vz])
             objects.append(object)
             masses.append(body.m)
-        energy = _bima.calc_energy(objects, masses)
+        g = units.g if units is not None else 1.0
+        energy = _bima.calc_energy(objects, masses, g)
         ins = cls(energy[0], energy[1])
         return ins
@@ -0,0 +1,59 @@
+from bima.disk import Disk
+from bima import _bima
+from bima.initial import Initial
+from bima.simulation import Config
+from bima.units import UnitSystem
+from typing import Optional, Self
+
+
+class Ensemble:
+    def __init__(self, initial: list[Initial], units: Optional[UnitSystem] = None) -> None:
+        """
+        many sets of initial conditions run together with the same config
+
+        Args:
+            initial: one set per run
+            units: units of every set
+        """
+        self.initial = initial
+        self.units = units if units is not None else UnitSystem.nbody()
+        self._ensemble = _bima.Ensemble([i._initial for i in initial], self.units)
+
+    @classmethod
+    def perturbed(cls, initial: Initial, n: int, sigma_r: float, sigma_v: float, seed: int = 0,
+                  units: Optional[UnitSystem] = None) -> Self:
+        """
+        n copies of one set with gaussian noise on the positions and velocities,
+        copy k uses the seed `seed + k`
+
+        Args:
+            initial: the set to copy
+            n: number of runs
+            sigma_r: standard deviation of every component of the positions
+            sigma_v: standard deviation of every component of the velocities
+            seed: seed of the first copy
+            units: units of the set
+        """
+        copies = [initial.perturb(sigma_r, sigma_v, seed + k) for k in range(n)]
+        return cls(copies, units)
+
+    def __len__(self) -> int:
+        return len(self.initial)
+
+    def run(self, config: Config, t_stop: float, dir_path: str, replace: bool = False,
+            single_file: bool = False, jobs: int = 0) -> list[Disk]:
+        """
+        run every set on its own thread, `jobs` at a time, 0 for one per core.
+        Run k is written to `dir_path/k/res.h5`, or to the group `runs/k` of
+        `dir_path/res.h5` with `single_file`
+
+        Returns:
+            the result of every run, in order
+        """
+        if t_stop <= 0:
+            raise ValueError("t_stop must be positive")
+        runs = self._ensemble.run_disk(dir_path, config.force.value, config.integrator, config.timestep.value, config.close_encounter.value,
+                                       t_stop, config.timestep.delta_t, config.close_encounter.par, config.save_acceleration, replace,
+                                       *config.boundary_args(), config.event_args(), config.external_args(),
+                                       config.nongravity_args(), config.threads, config.force.par, config.box_size, config.unwrap, config.timestep.par, single_file, jobs)
+        return [Disk(path, group) for path, group in runs]
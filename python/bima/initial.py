@@ -1,7 +1,8 @@
-from typing import Self
+from typing import Literal, Optional, Self
 from numpy.typing import NDArray
 import numpy as np
 from bima import _bima
+from bima.method.cluster import ClusterType, MassFunction, MassFunctionType
 
 
 class Initial:
@@ -11,12 +12,15 @@ class Initial:
         self._initial = _initial
         self.m = m
     @classmethod
-    def from_arr(cls, data: NDArray[np.float64]) -> Self:
+    def from_arr(cls, data: NDArray[np.float64], beta: Optional[list[float]] = None,
+                 tau: Optional[list[Optional[float]]] = None) -> Self:
         """
         set the initial position of the celestial bodies
 
         Args:
             data: List of floating point numbers
+            beta: radiation pressure to gravity ratio of every body
+            tau: drag timescale of every body, None for no drag
 
         Returns:
             Initial instance
@@ -35,9 +39,215 @@ class Initial:
         vy = data[:, 5].tolist()
         vz = data[:, 6].tolist()
 
-        initial = cls(_bima.set_initial(m, x, y, z, vx, vy, vz), m)
+        initial = cls(_bima.set_initial(m, x, y, z, vx, vy, vz, beta, tau), m)
         return initial
 
+    @classmethod
+    def from_elements(cls, central: float, data: NDArray[np.float64], g: float = 1.0,
+                      frame: Literal["heliocentric", "jacobi"] = "heliocentric",
+                      beta: Optional[list[float]] = None,
+                      tau: Optional[list[Optional[float]]] = None) -> Self:
+        """
+        set the initial position of the celestial bodies from their orbital elements
+        around a central body, which becomes the first body
+
+        Args:
+            central: mass of the central body
+            data: columns m, a, e, i, node, peri, mean anomaly. Angles are in radians,
+                a is negative for hyperbolic orbits and is the pericentre distance
+                for parabolic ones
+            g: gravitational constant
+            frame: elements relative to the central body or jacobi elements
+            beta: radiation pressure to gravity ratio of every body, central body included
+            tau: drag timescale of every body, central body included
+
+        Returns:
+            Initial instance
+        Raises:
+            ValueError: Incorrect dimension or invalid elements
+        """
+        shape = data.shape
+        if len(shape) != 2 or shape[1] != 7:
+            raise ValueError(
+                f"Incorrect dimension, should be (n, 7). shape = {shape}")
+        frames = dict(heliocentric=0, jacobi=1)
+        if frame not in frames:
+            raise ValueError(f"Unknown frame {frame}")
+        m = data[:, 0].tolist()
+        a = data[:, 1].tolist()
+        e = data[:, 2].tolist()
+        i = data[:, 3].tolist()
+        node = data[:, 4].tolist()
+        peri = data[:, 5].tolist()
+        mean_anomaly = data[:, 6].tolist()
+
+        initial = cls(_bima.set_initial_elements(
+            central, m, a, e, i, node, peri, mean_anomaly, g, frames[frame], beta, tau), [central] + m)
+        return initial
+
+    @classmethod
+    def from_cluster(cls, n: int, model: ClusterType, mass_function: MassFunctionType = MassFunction.Equal,
+                     virial_ratio: float = 0.5, seed: int = 0) -> Self:
+        """
+        sample a star cluster in N-body units, G = M = 1 with a potential energy of -1/2
+
+        Args:
+            n: number of bodies
+            model: density profile and velocity distribution
+            mass_function: distribution of the masses
+            virial_ratio: kinetic over potential energy, 0.5 for virial equilibrium
+            seed: seed of the random number generator
+
+        Returns:
+            Initial instance
+        Raises:
+            ValueError: Invalid model or mass function
+        """
+        _initial = _bima.generate_cluster(n, model.value, model.par, mass_function.value,
+                                          mass_function.range, virial_ratio, seed)
+        return cls(_initial, [i.m for i in _initial])
+
+    @classmethod
+    def from_ring(cls, central: float, n: int, m_disk: float, r_in: float, r_out: float,
+                  p: float = 1.0, sigma_e: float = 0.0, sigma_i: float = 0.0, g: float = 1.0,
+                  seed: int = 0) -> Self:
+        """
+        sample a thin disk of bodies on Keplerian orbits around a central body,
+        which becomes the first body
+
+        Args:
+            central: mass of the central body
+            n: number of bodies in the disk
+            m_disk: total mass of the disk, 0 for test particles
+            r_in: inner radius
+            r_out: outer radius
+            p: power of the surface density, Σ ∝ r^-p
+            sigma_e: dispersion of the eccentricities
+            sigma_i: dispersion of the inclinations in radians
+            g: gravitational constant
+            seed: seed of the random number generator
+
+        Returns:
+            Initial instance
+        Raises:
+            ValueError: Wrong parameters
+        """
+        _initial = _bima.generate_ring(
+            central, n, m_disk, r_in, r_out, p, sigma_e, sigma_i, g, seed)
+        return cls(_initial, [i.m for i in _initial])
+
+    @classmethod
+    def from_galaxy(cls, n_disk: int, m_disk: float, r_d: float, z_d: float, n_bulge: int = 0,
+                    m_bulge: float = 0.0, a_bulge: float = 1.0, q: float = 1.5, seed: int = 0) -> Self:
+        """
+        sample an exponential disk with an optional Hernquist bulge, G = 1. The bulge
+        bodies come first
+
+        Args:
+            n_disk: number of bodies in the disk
+            m_disk: total mass of the disk
+            r_d: scale length of the disk
+            z_d: scale height of the disk
+            n_bulge: number of bodies in the bulge
+            m_bulge: total mass of the bulge
+            a_bulge: scale radius of the bulge
+            q: Toomre parameter of the disk
+            seed: seed of the random number generator
+
+        Returns:
+            Initial instance
+        Raises:
+            ValueError: Wrong parameters
+        """
+        _initial = _bima.generate_galaxy(
+            n_disk, m_disk, r_d, z_d, n_bulge, m_bulge, a_bulge, q, seed)
+        return cls(_initial, [i.m for i in _initial])
+
+    @classmethod
+    def from_table(cls, path: str, columns: Optional[list[str]] = None) -> Self:
+        """
+        read the initial position of the celestial bodies from a comma or
+        whitespace separated table
+
+        Args:
+            path: path of the table. Lines starting with # are skipped
+            columns: names of the columns, read from the first line if not given.
+                m, x, y, z, vx, vy and vz are required, beta and tau are optional
+
+        Returns:
+            Initial instance
+        Raises:
+            ValueError: Missing column or malformed line
+        """
+        _initial = _bima.load_table(path, columns)
+        return cls(_initial, [i.m for i in _initial])
+
+    @classmethod
+    def from_snapshot(cls, path: str) -> Self:
+        """
+        read every particle type of a Gadget style HDF5 snapshot
+
+        Args:
+            path: path of the snapshot
+
+        Returns:
+            Initial instance
+        Raises:
+            ValueError: Malformed snapshot
+        """
+        _initial = _bima.load_snapshot(path)
+        return cls(_initial, [i.m for i in _initial])
+
+    @classmethod
+    def from_result(cls, path: str, index: int = -1) -> tuple[Self, float]:
+        """
+        read the bodies of an earlier run to continue or branch it. Escaped bodies
+        are left out and test particles come last
+
+        Args:
+            path: path of res.h5
+            index: time index, negative from the end
+
+        Returns:
+            Initial instance and the time it was taken at
+        Raises:
+            ValueError: Time index out of range
+        """
+        _initial, t = _bima.load_result(path, index)
+        return cls(_initial, [i.m for i in _initial]), t
+
+    def _with(self, _initial) -> Self:
+        return type(self)(_initial, [i.m for i in _initial])
+
+    def to_barycentric(self, velocity: bool = True) -> Self:
+        """move to the centre of mass frame, position and optionally velocity"""
+        return self._with(_bima.to_barycentric(self._initial, velocity))
+
+    def rotate(self, alpha: float, beta: float, gamma: float) -> Self:
+        """rotate by z-x-z Euler angles in radians"""
+        return self._with(_bima.rotate_euler(self._initial, alpha, beta, gamma))
+
+    def to_invariable_plane(self) -> Self:
+        """rotate so that the total angular momentum points along z"""
+        return self._with(_bima.to_invariable_plane(self._initial))
+
+    def boost(self, v: tuple[float, float, float],
+              r: Optional[tuple[float, float, float]] = None) -> Self:
+        """add the velocity v, and shift by r, to every body"""
+        return self._with(_bima.boost(self._initial, v, r))
+
+    def scale_virial(self, virial_ratio: float = 0.5, g: float = 1.0) -> Self:
+        """scale the velocities to the ratio of kinetic to potential energy"""
+        return self._with(_bima.scale_virial(self._initial, virial_ratio, g))
+
+    def scale_energy(self, energy: float, g: float = 1.0) -> Self:
+        """scale the positions and velocities to the total energy, keeping the virial ratio"""
+        return self._with(_bima.scale_energy(self._initial, energy, g))
+
+    def perturb(self, sigma_r: float, sigma_v: float, seed: int = 0) -> Self:
+        """add gaussian noise of standard deviation sigma_r to the positions and sigma_v to the velocities"""
+        return self._with(_bima.perturb(self._initial, sigma_r, sigma_v, seed))
+
     def __repr__(self) -> str:
         return self._initial.__repr__()
 
@@ -0,0 +1,30 @@
+from typing import Union
+
+class _Radius:
+    value = 0
+
+    def __init__(self, par: float):
+        self.par = par
+
+    def __repr__(self):
+        return f"Boundary.Radius({self.par})"
+
+class _Unbound:
+    value = 1
+
+    def __init__(self, par: float):
+        self.par = par
+
+    def __repr__(self):
+        return f"Boundary.Unbound({self.par})"
+
+class Boundary:
+    @staticmethod
+    def Radius(value: float) -> _Radius:
+        return _Radius(value)
+
+    @staticmethod
+    def Unbound(value: float) -> _Unbound:
+        return _Unbound(value)
+
+type BoundaryType = Union[_Radius, _Unbound]
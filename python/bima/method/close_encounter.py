@@ -20,14 +20,24 @@ class _Soften:
 
 class _Regularized:
     value = 2
-    par = None
+
+    def __init__(self, radius: float | None = None):
+        # bodies passing within `radius` are regularised, none if not given,
+        # with the direct force method only
+        self.par = radius
 
     def __repr__(self):
-        return "CloseEncounter.Regularized"
+        if self.par is None:
+            return "CloseEncounter.Regularized"
+        return f"CloseEncounter.Regularize({self.par})"
 
 class CloseEncounterMethod:
     Regularized = _Regularized()
 
+    @staticmethod
+    def Regularize(radius: float) -> _Regularized:
+        return _Regularized(radius)
+
     @staticmethod
     def Truncated(value: float) -> _Truncated:
         return _Truncated(value)
@@ -36,4 +46,19 @@ class CloseEncounterMethod:
     def Soften(value: float) -> _Soften:
         return _Soften(value)
 
+class Encounter:
+    # a pair taken into a regularised group, or let go of
+    def __init__(self, row: list[float]):
+        self.t = row[0]
+        self.body, self.other = int(row[1]), int(row[2])
+        self.separation = row[3]
+        self.start = bool(row[4])
+        self.members = int(row[5])
+
+    def __repr__(self) -> str:
+        return f"Encounter(body={self.body}, other={self.other}, t={self.t}, start={self.start})"
+
+    def __str__(self) -> str:
+        return self.__repr__()
+
 type CloseEncounterMethodType = Union[_Regularized, _Truncated, _Soften]
\ No newline at end of file
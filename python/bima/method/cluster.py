@@ -0,0 +1,80 @@
+from typing import Optional, Union
+
+class _Plummer:
+    value = 0
+    par = None
+
+    def __repr__(self):
+        return "Cluster.Plummer"
+
+class _King:
+    value = 1
+
+    def __init__(self, par: float):
+        self.par = par
+
+    def __repr__(self):
+        return f"Cluster.King({self.par})"
+
+class _Hernquist:
+    value = 2
+    par = None
+
+    def __repr__(self):
+        return "Cluster.Hernquist"
+
+class _Uniform:
+    value = 3
+    par = None
+
+    def __repr__(self):
+        return "Cluster.Uniform"
+
+class Cluster:
+    Plummer = _Plummer()
+    Hernquist = _Hernquist()
+    Uniform = _Uniform()
+
+    @staticmethod
+    def King(w0: float) -> _King:
+        return _King(w0)
+
+type ClusterType = Union[_Plummer, _King, _Hernquist, _Uniform]
+
+class _Equal:
+    value = 0
+    range = None
+
+    def __repr__(self):
+        return "MassFunction.Equal"
+
+class _Salpeter:
+    value = 1
+
+    def __init__(self, range: Optional[tuple[float, float]]):
+        self.range = range
+
+    def __repr__(self):
+        return f"MassFunction.Salpeter({self.range})"
+
+class _Kroupa:
+    value = 2
+
+    def __init__(self, range: Optional[tuple[float, float]]):
+        self.range = range
+
+    def __repr__(self):
+        return f"MassFunction.Kroupa({self.range})"
+
+class MassFunction:
+    Equal = _Equal()
+
+    @staticmethod
+    def Salpeter(m_min: float = 0.1, m_max: float = 100.) -> _Salpeter:
+        return _Salpeter((m_min, m_max))
+
+    @staticmethod
+    def Kroupa(m_min: float = 0.08, m_max: float = 100.) -> _Kroupa:
+        return _Kroupa((m_min, m_max))
+
+type MassFunctionType = Union[_Equal, _Salpeter, _Kroupa]
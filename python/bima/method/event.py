@@ -0,0 +1,76 @@
+from typing import Union
+
+class _Approach:
+    value = 0
+
+    def __init__(self, body: int, other: int, stop: bool):
+        self.body = body
+        self.other = other
+        self.stop = stop
+
+    def args(self) -> tuple[int, int, int, float, bool]:
+        return (self.value, self.body, self.other, 0.0, self.stop)
+
+    def __repr__(self):
+        return f"Event.Approach({self.body}, {self.other}, stop={self.stop})"
+
+class _Plane:
+    value = 1
+
+    def __init__(self, body: int, axis: int, par: float, stop: bool):
+        self.body = body
+        self.axis = axis
+        self.par = par
+        self.stop = stop
+
+    def args(self) -> tuple[int, int, int, float, bool]:
+        return (self.value, self.body, self.axis, self.par, self.stop)
+
+    def __repr__(self):
+        return f"Event.Plane({self.body}, axis={self.axis}, {self.par}, stop={self.stop})"
+
+class _Distance:
+    value = 2
+
+    def __init__(self, body: int, other: int, par: float, stop: bool):
+        self.body = body
+        self.other = other
+        self.par = par
+        self.stop = stop
+
+    def args(self) -> tuple[int, int, int, float, bool]:
+        return (self.value, self.body, self.other, self.par, self.stop)
+
+    def __repr__(self):
+        return f"Event.Distance({self.body}, {self.other}, {self.par}, stop={self.stop})"
+
+class Event:
+    @staticmethod
+    def Approach(body: int, other: int, stop: bool = False) -> _Approach:
+        """closest approach of `body` to `other`, e.g. every periapsis"""
+        return _Approach(body, other, stop)
+
+    @staticmethod
+    def Plane(body: int, axis: int, value: float = 0.0, stop: bool = False) -> _Plane:
+        """`body` crosses the plane where the coordinate along `axis` (0, 1, 2) equals `value`"""
+        return _Plane(body, axis, value, stop)
+
+    @staticmethod
+    def Distance(body: int, other: int, value: float, stop: bool = False) -> _Distance:
+        """separation between `body` and `other` crosses `value`"""
+        return _Distance(body, other, value, stop)
+
+type EventType = Union[_Approach, _Plane, _Distance]
+
+class Detection:
+    def __init__(self, row: list[float]):
+        self.event = int(row[0])
+        self.t = row[1]
+        self.x, self.y, self.z = row[2], row[3], row[4]
+        self.vx, self.vy, self.vz = row[5], row[6], row[7]
+
+    def __repr__(self) -> str:
+        return f"Detection(event={self.event}, t={self.t})"
+
+    def __str__(self) -> str:
+        return self.__repr__()
@@ -1,5 +1,72 @@
-from enum import IntEnum
+from typing import Union
 
-class ForceMethod(IntEnum):
-  Direct = 0
-  Octree = 1
\ No newline at end of file
+class _Direct:
+    value = 0
+    par = None
+
+    def __repr__(self):
+        return "ForceMethod.Direct"
+
+class _Octree:
+    value = 1
+    par = None
+
+    def __repr__(self):
+        return "ForceMethod.Octree"
+
+class _FMM:
+    value = 2
+
+    def __init__(self, order: int):
+        if order < 1 or order > 12:
+            raise ValueError("order must be from 1 to 12")
+        self.par = order
+
+    def __repr__(self):
+        return f"ForceMethod.FMM({self.par})"
+
+class _Ewald:
+    value = 3
+    par = None
+
+    def __repr__(self):
+        return "ForceMethod.Ewald"
+
+class _PM:
+    def __init__(self, cells: int, short_range: bool):
+        if cells < 16 or cells > 256 or cells & (cells - 1) != 0:
+            raise ValueError("cells must be a power of two from 16 to 256")
+        self.value = 5 if short_range else 4
+        self.par = cells
+
+    def __repr__(self):
+        name = "P3M" if self.value == 5 else "PM"
+        return f"ForceMethod.{name}({self.par})"
+
+class ForceMethod:
+    Direct = _Direct()
+    Octree = _Octree()
+    # direct summation over the periodic images, needs `Config.box_size`
+    Ewald = _Ewald()
+
+    @staticmethod
+    def FMM(order: int = 4) -> _FMM:
+        """fast multipole method, the error goes down with the expansion order. The
+        close encounter method only applies to the bodies of the neighbouring cells"""
+        return _FMM(order)
+
+    @staticmethod
+    def PM(cells: int = 64) -> _PM:
+        """particle mesh with `cells` per side, gravity is smoothed over a few cells.
+        Periodic with `Config.box_size`, otherwise around the bodies with at most
+        128 cells per side. The mesh takes about 48 bytes a node on every step,
+        800 MB at 256 cells, and twice as many nodes per side without a box"""
+        return _PM(cells, False)
+
+    @staticmethod
+    def P3M(cells: int = 64) -> _PM:
+        """particle mesh with the pairs of neighbouring bodies summed directly, with
+        the close encounter method, on the same mesh as `PM`"""
+        return _PM(cells, True)
+
+type ForceMethodType = Union[_Direct, _Octree, _FMM, _Ewald, _PM]
@@ -7,3 +7,5 @@ class Integrator(IntEnum):
     RK4 = 1
     BS = 2
     LeapFrog = 3
+    # fourth order, with `TimestepMethod.Block` only
+    Hermite = 4
@@ -0,0 +1,24 @@
+from dataclasses import dataclass
+
+
+@dataclass
+class NonGravity:
+    """
+    Forces felt relative to the central body. `beta` and `tau` of every body
+    are set through `Initial.from_arr`.
+
+    Args:
+        central: index of the central body
+        c: speed of light in code units, needed by `gr` and `radiation`
+        gr: first post-Newtonian correction of the central body
+        radiation: radiation pressure and Poynting-Robertson drag
+        drag: velocity-proportional drag
+    """
+    central: int = 0
+    c: float = 0.0
+    gr: bool = False
+    radiation: bool = False
+    drag: bool = False
+
+    def args(self) -> tuple[int, float, bool, bool, bool]:
+        return (self.central, self.c, self.gr, self.radiation, self.drag)
@@ -0,0 +1,95 @@
+from typing import Union
+
+type Vec3 = tuple[float, float, float]
+
+class _External:
+    value: int
+    par: list[float]
+
+    def args(self) -> tuple[int, list[float]]:
+        return (self.value, self.par)
+
+class _PointMass(_External):
+    value = 0
+
+    def __init__(self, m: float, centre: Vec3):
+        self.par = [m, *centre]
+
+    def __repr__(self):
+        return f"Potential.PointMass({self.par})"
+
+class _Plummer(_External):
+    value = 1
+
+    def __init__(self, m: float, a: float, centre: Vec3):
+        self.par = [m, a, *centre]
+
+    def __repr__(self):
+        return f"Potential.Plummer({self.par})"
+
+class _Hernquist(_External):
+    value = 2
+
+    def __init__(self, m: float, a: float, centre: Vec3):
+        self.par = [m, a, *centre]
+
+    def __repr__(self):
+        return f"Potential.Hernquist({self.par})"
+
+class _NFW(_External):
+    value = 3
+
+    def __init__(self, m: float, rs: float, centre: Vec3):
+        self.par = [m, rs, *centre]
+
+    def __repr__(self):
+        return f"Potential.NFW({self.par})"
+
+class _MiyamotoNagai(_External):
+    value = 4
+
+    def __init__(self, m: float, a: float, b: float, centre: Vec3):
+        self.par = [m, a, b, *centre]
+
+    def __repr__(self):
+        return f"Potential.MiyamotoNagai({self.par})"
+
+class _Uniform(_External):
+    value = 5
+
+    def __init__(self, g: Vec3):
+        self.par = [*g]
+
+    def __repr__(self):
+        return f"Potential.Uniform({self.par})"
+
+class Potential:
+    """External fields added on top of the mutual gravity of the bodies. Python
+    callables are not supported, the fields are computed in Rust"""
+
+    @staticmethod
+    def PointMass(m: float, centre: Vec3 = (0, 0, 0)) -> _PointMass:
+        return _PointMass(m, centre)
+
+    @staticmethod
+    def Plummer(m: float, a: float, centre: Vec3 = (0, 0, 0)) -> _Plummer:
+        return _Plummer(m, a, centre)
+
+    @staticmethod
+    def Hernquist(m: float, a: float, centre: Vec3 = (0, 0, 0)) -> _Hernquist:
+        return _Hernquist(m, a, centre)
+
+    @staticmethod
+    def NFW(m: float, rs: float, centre: Vec3 = (0, 0, 0)) -> _NFW:
+        """`m` is the characteristic mass 4π ρ0 rs³"""
+        return _NFW(m, rs, centre)
+
+    @staticmethod
+    def MiyamotoNagai(m: float, a: float, b: float, centre: Vec3 = (0, 0, 0)) -> _MiyamotoNagai:
+        return _MiyamotoNagai(m, a, b, centre)
+
+    @staticmethod
+    def Uniform(g: Vec3) -> _Uniform:
+        return _Uniform(g)
+
+type PotentialType = Union[_PointMass, _Plummer, _Hernquist, _NFW, _MiyamotoNagai, _Uniform]
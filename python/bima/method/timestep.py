@@ -2,6 +2,7 @@ from typing import Union
 
 class _Constant:
     value = 0
+    par = None
 
     def __init__(self, delta_t: float):
         self.delta_t = delta_t
@@ -12,10 +13,23 @@ class _Constant:
 class _Adaptive:
     value = 1
     delta_t = None
+    par = None
 
     def __repr__(self):
         return "TimestepMethod.Adaptive"
 
+class _Block:
+    value = 2
+
+    def __init__(self, delta_t: float, eta: float):
+        if eta <= 0:
+            raise ValueError("eta must be positive")
+        self.delta_t = delta_t
+        self.par = eta
+
+    def __repr__(self):
+        return f"TimestepMethod.Block({self.delta_t}, eta={self.par})"
+
 class TimestepMethod:
     Adaptive = _Adaptive()
 
@@ -23,4 +37,11 @@ class TimestepMethod:
     def Constant(value: float) -> _Constant:
         return _Constant(value)
 
-type TimestepMethodType = Union[_Adaptive, _Constant]
+    @staticmethod
+    def Block(delta_t: float, eta: float = 0.02) -> _Block:
+        """a power of two fraction of `delta_t` for every body, from its acceleration
+        and its jerk with the accuracy `eta`, all of them meeting at the multiples of
+        `delta_t`. Needs `Integrator.Hermite` or `LeapFrog` and `ForceMethod.Direct`"""
+        return _Block(delta_t, eta)
+
+type TimestepMethodType = Union[_Adaptive, _Constant, _Block]
@@ -1,27 +1,60 @@
-from bima.body import Body
+from bima.body import Body, Escape
 from bima.disk import Disk
-from bima.method.close_encounter import CloseEncounterMethodType
-from bima.method.force import ForceMethod
+from bima.method.close_encounter import CloseEncounterMethodType, Encounter
+from bima.method.force import ForceMethodType
 from bima.method.integrator import Integrator
 from bima.method.timestep import TimestepMethodType
 from bima import _bima
 from bima.initial import Initial
-from dataclasses import dataclass
+from bima.units import UnitSystem
+from bima.method.boundary import BoundaryType
+from bima.method.event import EventType, Detection
+from bima.method.potential import PotentialType
+from bima.method.nongravity import NonGravity
+from dataclasses import dataclass, field
+from typing import Optional
 
 
 @dataclass
 class Config:
-    force: ForceMethod
+    force: ForceMethodType
     integrator: Integrator
     timestep: TimestepMethodType
     close_encounter: CloseEncounterMethodType
     save_acceleration: bool = False
+    boundary: Optional[BoundaryType] = None
+    events: list[EventType] = field(default_factory=list)
+    external: list[PotentialType] = field(default_factory=list)
+    nongravity: Optional[NonGravity] = None
+    # threads for the forces, 0 for one per core; the result is the same for any number
+    threads: int = 1
+    # side of the periodic box, for `ForceMethod.Ewald`, `PM` and `P3M`; the
+    # positions are wrapped into it from 0 to box_size unless `unwrap`
+    box_size: Optional[float] = None
+    unwrap: bool = False
+
+    def boundary_args(self) -> tuple[Optional[int], Optional[float]]:
+        if self.boundary is None:
+            return None, None
+        return self.boundary.value, self.boundary.par
+
+    def event_args(self) -> list[tuple[int, int, int, float, bool]]:
+        return [event.args() for event in self.events]
+
+    def external_args(self) -> list[tuple[int, list[float]]]:
+        return [potential.args() for potential in self.external]
+
+    def nongravity_args(self) -> Optional[tuple[int, float, bool, bool, bool]]:
+        if self.nongravity is None:
+            return None
+        return self.nongravity.args()
 
 
 class Simulation:
-    def __init__(self, initial: Initial) -> None:
+    def __init__(self, initial: Initial, units: Optional[UnitSystem] = None) -> None:
         self.initial = initial
-        self._sim = _bima.Simulation(initial._initial)
+        self.units = units if units is not None else UnitSystem.nbody()
+        self._sim = _bima.Simulation(initial._initial, self.units)
         self.in_memory = InMemory(self)
 
     def in_disk(self, dir_path: str, replace=False):
@@ -31,16 +64,25 @@ class Simulation:
 class InMemory:
     def __init__(self, simulation: Simulation):
         self.simulation = simulation
+        self.events: list[Detection] = []
+        self.regularised: list[Encounter] = []
 
     def run(self, config: Config, t_stop: float) -> list[Body]:
         if t_stop <= 0:
             raise ValueError("t_stop must be positive")
-        record: list[list[list[float]]] = self.simulation._sim.run_memory(config.force, config.integrator, config.timestep.value, config.close_encounter.value,
-                                                                          t_stop, config.timestep.delta_t, config.close_encounter.par, config.save_acceleration)
+        record, escapes, events, regularised = self.simulation._sim.run_memory(config.force.value, config.integrator, config.timestep.value, config.close_encounter.value,
+                                                                  t_stop, config.timestep.delta_t, config.close_encounter.par, config.save_acceleration,
+                                                                  *config.boundary_args(), config.event_args(), config.external_args(),
+                                                                  config.nongravity_args(), config.threads, config.force.par, config.box_size, config.unwrap, config.timestep.par)
+        # detections of `config.events` during this run, in time order
+        self.events = [Detection(e) for e in events]
+        # pairs taken into or let go of by the regularisation, in time order
+        self.regularised = [Encounter(e) for e in regularised]
         # print("raw\n", record[0])
+        escaped = {int(e[0]): Escape(e) for e in escapes}
         bodies: list[Body] = []
         for i, body in enumerate(record):
-            bodies.append(Body(body, i, self.simulation.initial.m[i]))
+            bodies.append(Body(body, i, self.simulation.initial.m[i], escaped.get(i)))
         return bodies
 
 
@@ -53,6 +95,8 @@ class InDisk:
     def run(self, config: Config, t_stop: float) -> Disk:
         if t_stop <= 0:
             raise ValueError("t_stop must be positive")
-        path = self.simulation._sim.run_disk(self.dir_path, config.force, config.integrator, config.timestep.value, config.close_encounter.value,
-                                             t_stop, config.timestep.delta_t, config.close_encounter.par, config.save_acceleration, self.replace)
+        path = self.simulation._sim.run_disk(self.dir_path, config.force.value, config.integrator, config.timestep.value, config.close_encounter.value,
+                                             t_stop, config.timestep.delta_t, config.close_encounter.par, config.save_acceleration, self.replace,
+                                             *config.boundary_args(), config.event_args(), config.external_args(),
+                                             config.nongravity_args(), config.threads, config.force.par, config.box_size, config.unwrap, config.timestep.par)
         return Disk(path)
@@ -0,0 +1,6 @@
+from bima import _bima
+
+# G and the SI value of the length, mass and time units. Use the constructors
+# UnitSystem.si(), UnitSystem.au_msun_yr(), UnitSystem.pc_msun_myr() and
+# UnitSystem.nbody(), or UnitSystem(length, mass, time, name) for others.
+UnitSystem = _bima.UnitSystem
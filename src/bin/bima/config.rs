@@ -0,0 +1,312 @@
+use _bima::initial::Initial;
+use _bima::load;
+use _bima::simulation::driver::Options;
+use _bima::simulation::utils::{EventArg, NonGravityArg, PotentialArg};
+use _bima::units::UnitSystem;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+// The whole run, see the `Command line` section of the README. Relative paths
+// are relative to the directory of the config file.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub initial: InitialConfig,
+    pub output: PathBuf,
+    #[serde(default)]
+    pub replace: bool,
+    pub t_stop: f64,
+    #[serde(default)]
+    pub units: Units,
+    pub method: Method,
+    #[serde(default)]
+    pub save_acc: bool,
+    // positions continuous across the periodic box instead of wrapped into it
+    #[serde(default)]
+    pub unwrap: bool,
+    pub boundary: Option<BoundaryConfig>,
+    #[serde(default)]
+    pub events: Vec<EventConfig>,
+    #[serde(default)]
+    pub external: Vec<PotentialConfig>,
+    pub nongravity: Option<NonGravityConfig>,
+    // seconds between two progress lines
+    #[serde(default = "default_log_interval")]
+    pub log_interval: f64,
+}
+
+fn default_log_interval() -> f64 {
+    10.0
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Units {
+    // nbody, si, au_msun_yr or pc_msun_myr
+    Name(String),
+    // in SI
+    Custom { length: f64, mass: f64, time: f64 },
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Units::Name("nbody".to_string())
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Table,
+    Snapshot,
+    Result,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct InitialConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: Format,
+    // names of the columns of a table without a header
+    pub columns: Option<Vec<String>>,
+    // time index of an earlier result, negative from the end
+    #[serde(default = "default_index")]
+    pub index: i64,
+}
+
+fn default_index() -> i64 {
+    -1
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Force {
+    #[default]
+    Direct = 0,
+    // 1 is the octree, which `bima_rs` does not implement yet
+    Fmm = 2,
+    Ewald = 3,
+    Pm = 4,
+    P3m = 5,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    Euler = 0,
+    Rk4 = 1,
+    Bs = 2,
+    #[default]
+    LeapFrog = 3,
+    Hermite = 4,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Timestep {
+    #[default]
+    Constant = 0,
+    Block = 2,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseEncounter {
+    Truncated = 0,
+    Soften = 1,
+    #[default]
+    Regularized = 2,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Method {
+    #[serde(default)]
+    pub force: Force,
+    // expansion order of fmm
+    pub order: Option<usize>,
+    // cells per side of pm and p3m
+    pub cells: Option<usize>,
+    // side of the periodic box, for ewald, pm and p3m
+    pub box_size: Option<f64>,
+    #[serde(default)]
+    pub integrator: Integrator,
+    #[serde(default)]
+    pub timestep: Timestep,
+    // constant timestep, or the interval block timesteps meet at
+    pub delta_t: f64,
+    // accuracy of block timesteps
+    pub eta: Option<f64>,
+    #[serde(default)]
+    pub close_encounter: CloseEncounter,
+    pub ce_par: Option<f64>,
+    // 0 for one per core
+    #[serde(default = "default_threads")]
+    pub threads: usize,
+}
+
+fn default_threads() -> usize {
+    1
+}
+
+impl Method {
+    // The parameter of the force method, the keys of the other methods are
+    // rejected rather than left unused.
+    fn force_par(&self) -> Result<Option<f64>, String> {
+        let periodic = matches!(self.force, Force::Ewald | Force::Pm | Force::P3m);
+        let mesh = matches!(self.force, Force::Pm | Force::P3m);
+        if self.order.is_some() && !matches!(self.force, Force::Fmm) {
+            return Err("`order` is only used by fmm".to_string());
+        }
+        if self.cells.is_some() && !mesh {
+            return Err("`cells` is only used by pm and p3m".to_string());
+        }
+        if self.box_size.is_some() && !periodic {
+            return Err("`box_size` is only used by ewald, pm and p3m".to_string());
+        }
+        Ok(self.order.or(self.cells).map(|par| par as f64))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryKind {
+    Radius = 0,
+    Unbound = 1,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BoundaryConfig {
+    pub kind: BoundaryKind,
+    pub radius: f64,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Approach = 0,
+    Plane = 1,
+    Distance = 2,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct EventConfig {
+    pub condition: Condition,
+    pub body: usize,
+    // the other body, or the axis of a plane
+    #[serde(default)]
+    pub other: usize,
+    #[serde(default)]
+    pub par: f64,
+    #[serde(default)]
+    pub stop: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Potential {
+    PointMass = 0,
+    Plummer = 1,
+    Hernquist = 2,
+    Nfw = 3,
+    MiyamotoNagai = 4,
+    Uniform = 5,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PotentialConfig {
+    pub potential: Potential,
+    // same order as the Python `Potential`, the centre last
+    pub par: Vec<f64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct NonGravityConfig {
+    pub central: usize,
+    pub c: f64,
+    pub gr: bool,
+    pub radiation: bool,
+    pub drag: bool,
+}
+
+impl Config {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut config: Config = toml::from_str(&content)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        config.initial.path = dir.join(&config.initial.path);
+        config.output = dir.join(&config.output);
+        Ok(config)
+    }
+    pub fn units(&self) -> Result<UnitSystem, String> {
+        let units = match &self.units {
+            Units::Name(name) => UnitSystem::from_name(name),
+            Units::Custom { length, mass, time } => UnitSystem::new(*length, *mass, *time, None),
+        };
+        units.map_err(|e| e.to_string())
+    }
+    // the bodies and the time they are at
+    pub fn initial(&self) -> Result<(Vec<Initial>, f64), String> {
+        let initial = &self.initial;
+        let path = initial.path.to_string_lossy();
+        let loaded = match initial.format {
+            Format::Table => load::read_table(&path, initial.columns.clone()).map(|b| (b, 0.0)),
+            Format::Snapshot => load::read_snapshot(&path).map(|b| (b, 0.0)),
+            Format::Result => load::read_result(&path, initial.index),
+        };
+        loaded.map_err(|e| format!("Failed to load {}: {}", path, e))
+    }
+    // the options of a run from the bodies at the time `t_start`
+    pub fn options(&self, t_start: f64) -> Result<Options, String> {
+        let method = &self.method;
+        let force_par = method
+            .force_par()
+            .map_err(|e| format!("Invalid method: {}", e))?;
+        let events = self
+            .events
+            .iter()
+            .map(|event| -> EventArg {
+                let condition = event.condition as u8;
+                (condition, event.body, event.other, event.par, event.stop)
+            })
+            .collect();
+        let external = self
+            .external
+            .iter()
+            .map(|potential| -> PotentialArg { (potential.potential as u8, potential.par.clone()) })
+            .collect();
+        let nongravity = self
+            .nongravity
+            .as_ref()
+            .map(|ng| -> NonGravityArg { (ng.central, ng.c, ng.gr, ng.radiation, ng.drag) });
+        Ok(Options {
+            force_method: method.force as u8,
+            force_par,
+            box_size: method.box_size,
+            unwrap: self.unwrap,
+            integrator: method.integrator as u8,
+            timestep_method: method.timestep as u8,
+            timestep_par: method.eta,
+            close_encounter: method.close_encounter as u8,
+            t_start,
+            t_stop: self.t_stop,
+            delta_t: Some(method.delta_t),
+            ce_par: method.ce_par,
+            save_acc: self.save_acc,
+            boundary: self.boundary.as_ref().map(|b| b.kind as u8),
+            boundary_par: self.boundary.as_ref().map(|b| b.radius),
+            events,
+            external,
+            nongravity,
+            threads: method.threads,
+        })
+    }
+}
@@ -0,0 +1,107 @@
+mod config;
+use _bima::simulation::Simulation;
+use _bima::simulation::driver::{self, DriverErr, Progress};
+use config::Config;
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "Usage: bima <config.toml>";
+
+// One line per `interval` on stderr, so the progress reads well in the log
+// of a batch job.
+struct Log {
+    start: Instant,
+    last: Instant,
+    interval: Duration,
+    t_stop: f64,
+}
+
+impl Log {
+    fn new(interval: f64, t_stop: f64) -> Self {
+        let now = Instant::now();
+        Log {
+            start: now,
+            last: now,
+            interval: Duration::from_secs_f64(interval.max(0.0)),
+            t_stop,
+        }
+    }
+}
+
+impl Progress for Log {
+    fn update(&mut self, iteration: usize, percentage: f64) -> Result<(), DriverErr> {
+        let now = Instant::now();
+        if percentage < 1.0 && now.duration_since(self.last) < self.interval {
+            return Ok(());
+        }
+        self.last = now;
+        let elapsed = now.duration_since(self.start).as_secs_f64();
+        let speed = iteration as f64 / elapsed.max(f64::EPSILON);
+        let eta = if percentage > 0.0 {
+            format!("{:.0}s", elapsed * (1. - percentage) / percentage)
+        } else {
+            "???".to_string()
+        };
+        writeln!(
+            io::stderr(),
+            "[{:>9.1}s] {:6.2}% t={:.6e}/{:.6e} {} it ({:.0} it/s) eta {}",
+            elapsed,
+            percentage.min(1.) * 100.0,
+            percentage.min(1.) * self.t_stop,
+            self.t_stop,
+            iteration,
+            speed,
+            eta,
+        )
+        .map_err(|e| DriverErr::Progress(e.to_string()))
+    }
+}
+
+fn run(path: PathBuf) -> Result<String, String> {
+    let config = Config::read(&path)?;
+    let units = config.units()?;
+    let (initial, t) = config.initial()?;
+    eprintln!(
+        "Loaded {} bodies from {} at t={:e}, in {} units",
+        initial.len(),
+        config.initial.path.display(),
+        t,
+        units.name
+    );
+    let simulation = Simulation::from_initial(&initial, units)
+        .ok_or_else(|| "Total mass is zero".to_string())?;
+    let mut log = Log::new(config.log_interval, config.t_stop);
+    driver::run_disk(
+        &simulation,
+        config.options(t)?,
+        &config.output.to_string_lossy(),
+        config.replace,
+        &mut log,
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+    if path == "-h" || path == "--help" {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    match run(PathBuf::from(path)) {
+        Ok(path) => {
+            println!("{}", path);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
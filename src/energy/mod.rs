@@ -11,11 +11,14 @@ mod progress_bar;
 mod py_stdout;
 mod updater;
 
+// `g` is G in the units of the masses, the energy comes out in the same units.
 #[pyfunction]
+#[pyo3(signature = (objects, masses, g=1.0))]
 pub fn calc_energy<'py>(
     py: Python<'py>,
     objects: Vec<Vec<[f64; 7]>>,
     masses: Vec<f64>,
+    g: f64,
 ) -> PyResult<[Py<PyArray1<f64>>; 2]> {
     if objects.is_empty() {
         return Err(PyValueError::new_err("objects cannot be empty"));
@@ -39,14 +42,15 @@ pub fn calc_energy<'py>(
                 )
             })
             .collect();
-        let traj = Trajectory::from_lines(lines, mass);
+        let traj = Trajectory::from_lines(lines, g * mass);
         trajectories.push(traj);
     }
     let record = Record::from_trajectories(trajectories, false);
     let mut effect = Updater::new(py)?;
     let energies = energy::calc_energy(&record, &mut effect)
         .map_err(|_| PyValueError::new_err("Empty objects"))?;
-    let (times, energy_values): (Vec<f64>, Vec<f64>) = energies.into_iter().unzip();
+    let (times, energy_values): (Vec<f64>, Vec<f64>) =
+        energies.into_iter().map(|(t, e)| (t, e / g)).unzip();
     Ok([
         times.into_pyarray(py).into(),
         energy_values.into_pyarray(py).into(),
@@ -0,0 +1,228 @@
+use crate::generate::rng::Rng;
+use bima_rs::vec3::Vec3;
+use std::f64::consts::PI;
+
+// fraction of the mass left out to keep the radii finite
+const MASS_CUT: f64 = 0.999;
+const GRID: usize = 64;
+
+#[derive(Clone, Debug)]
+pub enum Model {
+    Plummer,
+    // dimensionless central potential W0
+    King(f64),
+    Hernquist,
+    // homogeneous sphere with gaussian velocities
+    Uniform,
+}
+
+// Speed drawn from p(v) ∝ v² f(v) on [0, v_max] by rejection, with the bound
+// taken from the largest value on a grid.
+fn sample_speed(rng: &mut Rng, v_max: f64, f: impl Fn(f64) -> f64) -> f64 {
+    let p = |v: f64| v * v * f(v);
+    let bound = 1.1
+        * (1..=GRID)
+            .map(|k| p(v_max * k as f64 / GRID as f64))
+            .fold(0.0, f64::max);
+    if bound <= 0.0 {
+        return 0.0;
+    }
+    loop {
+        let v = rng.range(0., v_max);
+        if rng.uniform() * bound < p(v) {
+            return v;
+        }
+    }
+}
+
+// Isotropic distribution function of the Hernquist model with G = M = a = 1,
+// up to a constant, for the binding energy `e`.
+fn hernquist_df(e: f64) -> f64 {
+    if e <= 0.0 || e >= 1.0 {
+        return 0.0;
+    }
+    let q = e.sqrt();
+    let q2 = e;
+    q / (1. - q2).powi(2)
+        * ((1. - 2. * q2) * (8. * q2 * q2 - 8. * q2 - 3.) + 3. * q.asin() / (q2 * (1. - q2)).sqrt())
+}
+
+// Radius enclosing the fraction `x` of the mass of a Hernquist model with
+// G = M = a = 1, and a speed drawn at that radius.
+pub fn hernquist(x: f64, rng: &mut Rng) -> (f64, f64) {
+    let s = (x * MASS_CUT).sqrt();
+    let r = s / (1. - s);
+    let psi = 1. / (1. + r);
+    let v = sample_speed(rng, (2. * psi).sqrt(), |v| hernquist_df(psi - 0.5 * v * v));
+    (r, v)
+}
+
+// Radius and potential of the King model, integrated outwards from the centre
+// until the potential drops to zero at the tidal radius. Radii are in core
+// radii, potentials in σ², and the mass is cumulative.
+struct King {
+    r: Vec<f64>,
+    w: Vec<f64>,
+    m: Vec<f64>,
+}
+
+// density relative to the centre, up to a constant
+fn king_density(w: f64) -> f64 {
+    if w <= 0.0 {
+        return 0.0;
+    }
+    w.exp() * erf(w.sqrt()) - (4. * w / PI).sqrt() * (1. + 2. * w / 3.)
+}
+
+// Abramowitz and Stegun 7.1.26
+fn erf(x: f64) -> f64 {
+    let t = 1. / (1. + 0.3275911 * x);
+    let y = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    1. - y * (-x * x).exp()
+}
+
+impl King {
+    fn new(w0: f64) -> Self {
+        let rho0 = king_density(w0);
+        let rhs = |r: f64, w: f64, dw: f64| -9. * king_density(w) / rho0 - 2. * dw / r;
+        // series expansion away from the singular centre
+        let mut r = 1e-3;
+        let mut w = w0 - 1.5 * r * r;
+        let mut dw = -3. * r;
+        let (mut rs, mut ws, mut ms) = (vec![0.0], vec![w0], vec![0.0]);
+        while w > 0.0 {
+            let h = 1e-3 * r.max(1.0);
+            let k1 = (dw, rhs(r, w, dw));
+            let k2 = (
+                dw + 0.5 * h * k1.1,
+                rhs(r + 0.5 * h, w + 0.5 * h * k1.0, dw + 0.5 * h * k1.1),
+            );
+            let k3 = (
+                dw + 0.5 * h * k2.1,
+                rhs(r + 0.5 * h, w + 0.5 * h * k2.0, dw + 0.5 * h * k2.1),
+            );
+            let k4 = (dw + h * k3.1, rhs(r + h, w + h * k3.0, dw + h * k3.1));
+            w += h / 6. * (k1.0 + 2. * k2.0 + 2. * k3.0 + k4.0);
+            dw += h / 6. * (k1.1 + 2. * k2.1 + 2. * k3.1 + k4.1);
+            r += h;
+            rs.push(r);
+            ws.push(w.max(0.0));
+            ms.push(-r * r * dw);
+        }
+        King {
+            r: rs,
+            w: ws,
+            m: ms,
+        }
+    }
+    // radius enclosing the fraction `x` of the mass, with its potential
+    fn invert(&self, x: f64) -> (f64, f64) {
+        let target = x * self.m[self.m.len() - 1];
+        let k = self
+            .m
+            .partition_point(|m| *m < target)
+            .clamp(1, self.m.len() - 1);
+        let (m0, m1) = (self.m[k - 1], self.m[k]);
+        let s = if m1 > m0 {
+            (target - m0) / (m1 - m0)
+        } else {
+            0.0
+        };
+        (
+            self.r[k - 1] + s * (self.r[k] - self.r[k - 1]),
+            self.w[k - 1] + s * (self.w[k] - self.w[k - 1]),
+        )
+    }
+}
+
+impl Model {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Model::King(w0) => *w0 > 0.0 && *w0 <= 16.0,
+            _ => true,
+        }
+    }
+    // Positions and velocities in the natural units of the model, to be
+    // rescaled afterwards.
+    pub fn sample(&self, n: usize, rng: &mut Rng) -> Vec<(Vec3, Vec3)> {
+        let king = match self {
+            Model::King(w0) => Some(King::new(*w0)),
+            _ => None,
+        };
+        (0..n)
+            .map(|_| {
+                let x = rng.uniform();
+                let (r, v) = match self {
+                    Model::Plummer => {
+                        let x = x * MASS_CUT;
+                        let r = 1. / (x.powf(-2. / 3.) - 1.).sqrt();
+                        let psi = 1. / (1. + r * r).sqrt();
+                        let v_esc = (2. * psi).sqrt();
+                        let v =
+                            sample_speed(rng, v_esc, |v| (psi - 0.5 * v * v).max(0.0).powf(3.5));
+                        (r, v)
+                    }
+                    Model::King(_) => {
+                        let king = king.as_ref().unwrap();
+                        let (r, w) = king.invert(x);
+                        let v = sample_speed(rng, (2. * w).sqrt(), |v| {
+                            ((w - 0.5 * v * v).exp() - 1.).max(0.0)
+                        });
+                        (r, v)
+                    }
+                    Model::Hernquist => hernquist(x, rng),
+                    Model::Uniform => {
+                        let r = x.cbrt();
+                        let v = Vec3::new(rng.normal(), rng.normal(), rng.normal());
+                        return (r * rng.direction(), v);
+                    }
+                };
+                (r * rng.direction(), v * rng.direction())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hernquist_df_follows_the_closed_form() {
+        // 3 asin q + q (1 - q²)^½ (1 - 2q²) (8q⁴ - 8q² - 3) over (1 - q²)^5/2
+        for (e, f) in [
+            (0.01, 2.5970653309889994e-4),
+            (0.1, 0.09428488722121352),
+            (0.3, 2.149121172897769),
+            (0.9, 1467.2770122746608),
+        ] {
+            assert!(
+                (hernquist_df(e) - f).abs() < 1e-9 * f,
+                "f({})={}",
+                e,
+                hernquist_df(e)
+            );
+        }
+        for k in 1..1000 {
+            assert!(hernquist_df(k as f64 / 1000.0) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn hernquist_is_in_virial_equilibrium() {
+        // 2K = -W, with W = ½ Σ Φ(r) in the potential -1 / (1 + r)
+        let mut rng = Rng::new(3);
+        let n = 20000;
+        let (mut kinetic, mut potential) = (0.0, 0.0);
+        for _ in 0..n {
+            let x = rng.uniform();
+            let (r, v) = hernquist(x, &mut rng);
+            kinetic += 0.5 * v * v;
+            potential -= 0.5 / (1. + r);
+        }
+        let ratio = 2. * kinetic / -potential;
+        assert!((ratio - 1.).abs() < 0.02, "2K/|W|={}", ratio);
+    }
+}
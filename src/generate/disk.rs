@@ -0,0 +1,318 @@
+use crate::generate::cluster;
+use crate::generate::rng::Rng;
+use crate::orbit::Elements;
+use bima_rs::vec3::Vec3;
+use std::f64::consts::PI;
+
+// Thin disk of bodies on Keplerian orbits around a central mass, with
+// Σ ∝ r^-p between the two radii and Rayleigh distributed eccentricities and
+// inclinations.
+#[derive(Clone, Debug)]
+pub struct Ring {
+    pub r_in: f64,
+    pub r_out: f64,
+    pub p: f64,
+    pub sigma_e: f64,
+    pub sigma_i: f64,
+}
+
+impl Ring {
+    pub fn is_valid(&self) -> bool {
+        self.r_in > 0.0 && self.r_in < self.r_out && self.sigma_e >= 0.0 && self.sigma_i >= 0.0
+    }
+    // semi-major axis with dN/da ∝ a^(1-p)
+    fn semi_major_axis(&self, rng: &mut Rng) -> f64 {
+        let k = 2. - self.p;
+        let u = rng.uniform();
+        if k.abs() < 1e-12 {
+            self.r_in * (self.r_out / self.r_in).powf(u)
+        } else {
+            let (l, h) = (self.r_in.powf(k), self.r_out.powf(k));
+            (l + u * (h - l)).powf(1. / k)
+        }
+    }
+    fn rayleigh(rng: &mut Rng, sigma: f64) -> f64 {
+        sigma * (-2. * (1. - rng.uniform()).ln()).sqrt()
+    }
+    // positions and velocities relative to the central body, `mu` being G
+    // times the mass of the central body and one body of the disk
+    pub fn sample(&self, n: usize, mu: f64, rng: &mut Rng) -> Vec<(Vec3, Vec3)> {
+        (0..n)
+            .map(|_| {
+                let a = self.semi_major_axis(rng);
+                let mut e = Ring::rayleigh(rng, self.sigma_e);
+                while e >= 1.0 {
+                    e = Ring::rayleigh(rng, self.sigma_e);
+                }
+                let el = Elements {
+                    a,
+                    e,
+                    i: Ring::rayleigh(rng, self.sigma_i),
+                    node: rng.range(0., 2. * PI),
+                    peri: rng.range(0., 2. * PI),
+                    mean_anomaly: rng.range(0., 2. * PI),
+                };
+                el.to_cartesian(mu)
+            })
+            .collect()
+    }
+}
+
+// Exponential disk with a sech² vertical profile and a Hernquist bulge,
+// G = 1. The disk is stirred to the Toomre parameter `q`.
+#[derive(Clone, Debug)]
+pub struct Galaxy {
+    pub m_disk: f64,
+    // scale length and scale height
+    pub r_d: f64,
+    pub z_d: f64,
+    pub q: f64,
+    pub m_bulge: f64,
+    pub a_bulge: f64,
+}
+
+// Abramowitz and Stegun 9.8.1 to 9.8.8
+fn poly(x: f64, c: &[f64]) -> f64 {
+    c.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+fn bessel_i0(x: f64) -> f64 {
+    if x <= 3.75 {
+        let t = (x / 3.75).powi(2);
+        poly(
+            t,
+            &[
+                1.0, 3.5156229, 3.0899424, 1.2067492, 0.2659732, 0.0360768, 0.0045813,
+            ],
+        )
+    } else {
+        let t = 3.75 / x;
+        x.exp() / x.sqrt()
+            * poly(
+                t,
+                &[
+                    0.39894228,
+                    0.01328592,
+                    0.00225319,
+                    -0.00157565,
+                    0.00916281,
+                    -0.02057706,
+                    0.02635537,
+                    -0.01647633,
+                    0.00392377,
+                ],
+            )
+    }
+}
+
+fn bessel_i1(x: f64) -> f64 {
+    if x <= 3.75 {
+        let t = (x / 3.75).powi(2);
+        x * poly(
+            t,
+            &[
+                0.5, 0.87890594, 0.51498869, 0.15084934, 0.02658733, 0.00301532, 0.00032411,
+            ],
+        )
+    } else {
+        let t = 3.75 / x;
+        x.exp() / x.sqrt()
+            * poly(
+                t,
+                &[
+                    0.39894228,
+                    -0.03988024,
+                    -0.00362018,
+                    0.00163801,
+                    -0.01031555,
+                    0.02282967,
+                    -0.02895312,
+                    0.01787654,
+                    -0.00420059,
+                ],
+            )
+    }
+}
+
+fn bessel_k0(x: f64) -> f64 {
+    if x <= 2.0 {
+        let t = (x / 2.).powi(2);
+        -(x / 2.).ln() * bessel_i0(x)
+            + poly(
+                t,
+                &[
+                    -0.57721566,
+                    0.42278420,
+                    0.23069756,
+                    0.03488590,
+                    0.00262698,
+                    0.00010750,
+                    0.0000074,
+                ],
+            )
+    } else {
+        let t = 2. / x;
+        (-x).exp() / x.sqrt()
+            * poly(
+                t,
+                &[
+                    1.25331414,
+                    -0.07832358,
+                    0.02189568,
+                    -0.01062446,
+                    0.00587872,
+                    -0.00251540,
+                    0.00053208,
+                ],
+            )
+    }
+}
+
+fn bessel_k1(x: f64) -> f64 {
+    if x <= 2.0 {
+        let t = (x / 2.).powi(2);
+        (x / 2.).ln() * bessel_i1(x)
+            + poly(
+                t,
+                &[
+                    1.0,
+                    0.15443144,
+                    -0.67278579,
+                    -0.18156897,
+                    -0.01919402,
+                    -0.00110404,
+                    -0.00004686,
+                ],
+            ) / x
+    } else {
+        let t = 2. / x;
+        (-x).exp() / x.sqrt()
+            * poly(
+                t,
+                &[
+                    1.25331414,
+                    0.23498619,
+                    -0.03655620,
+                    0.01504268,
+                    -0.00780353,
+                    0.00325614,
+                    -0.00068245,
+                ],
+            )
+    }
+}
+
+impl Galaxy {
+    pub fn is_valid(&self) -> bool {
+        self.m_disk >= 0.0
+            && self.r_d > 0.0
+            && self.z_d > 0.0
+            && self.q >= 0.0
+            && self.m_bulge >= 0.0
+            && self.a_bulge > 0.0
+    }
+    fn surface_density(&self, r: f64) -> f64 {
+        self.m_disk / (2. * PI * self.r_d * self.r_d) * (-r / self.r_d).exp()
+    }
+    // squared circular velocity of the razor-thin disk and the bulge
+    fn v_circular_2(&self, r: f64) -> f64 {
+        let y = r / (2. * self.r_d);
+        let disk = 4.
+            * PI
+            * self.surface_density(0.0)
+            * self.r_d
+            * y
+            * y
+            * (bessel_i0(y) * bessel_k0(y) - bessel_i1(y) * bessel_k1(y));
+        let bulge = self.m_bulge * r / (r + self.a_bulge).powi(2);
+        disk + bulge
+    }
+    pub fn sample_disk(&self, n: usize, rng: &mut Rng) -> Vec<(Vec3, Vec3)> {
+        (0..n)
+            .map(|_| {
+                // Σ ∝ exp(-R/Rd) makes R/Rd gamma distributed with shape 2
+                let r = -self.r_d * ((1. - rng.uniform()) * (1. - rng.uniform())).ln();
+                let r = r.max(1e-3 * self.r_d);
+                let z = self.z_d * (2. * rng.uniform() - 1.).atanh();
+                let phi = rng.range(0., 2. * PI);
+                let sigma = self.surface_density(r);
+                let v_c2 = self.v_circular_2(r);
+                let omega2 = v_c2 / (r * r);
+                let h = 1e-4 * self.r_d;
+                let d_omega2 = (self.v_circular_2(r + h) / (r + h).powi(2)
+                    - self.v_circular_2(r - h) / (r - h).powi(2))
+                    / (2. * h);
+                let kappa2 = (r * d_omega2 + 4. * omega2).max(0.0);
+                let sigma_z = (PI * sigma * self.z_d).sqrt();
+                let sigma_r = if kappa2 > 0.0 {
+                    3.36 * self.q * sigma / kappa2.sqrt()
+                } else {
+                    0.0
+                };
+                let sigma_phi = sigma_r * (kappa2 / (4. * omega2)).sqrt();
+                // asymmetric drift, taking σ_R² ∝ Σ
+                let v_phi2 =
+                    v_c2 + sigma_r * sigma_r * (1. - kappa2 / (4. * omega2) - 2. * r / self.r_d);
+                let v_r = sigma_r * rng.normal();
+                let v_phi = v_phi2.max(0.0).sqrt() + sigma_phi * rng.normal();
+                let v_z = sigma_z * rng.normal();
+                let (s, c) = phi.sin_cos();
+                (
+                    Vec3::new(r * c, r * s, z),
+                    Vec3::new(v_r * c - v_phi * s, v_r * s + v_phi * c, v_z),
+                )
+            })
+            .collect()
+    }
+    // isotropic in its own potential
+    pub fn sample_bulge(&self, n: usize, rng: &mut Rng) -> Vec<(Vec3, Vec3)> {
+        let v_scale = (self.m_bulge / self.a_bulge).sqrt();
+        (0..n)
+            .map(|_| {
+                let x = rng.uniform();
+                let (r, v) = cluster::hernquist(x, rng);
+                (
+                    self.a_bulge * r * rng.direction(),
+                    v_scale * v * rng.direction(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // radial velocity dispersion of the isotropic Hernquist model with
+    // G = M = a = 1 (Hernquist 1990, eq. 10)
+    fn hernquist_sigma2(r: f64) -> f64 {
+        let cubic = 25. + 52. * r + 42. * r * r + 12. * r * r * r;
+        (12. * r * (1. + r).powi(3) * ((1. + r) / r).ln() - r / (1. + r) * cubic) / 12.
+    }
+
+    #[test]
+    fn bulge_has_the_hernquist_dispersion() {
+        let galaxy = Galaxy {
+            m_disk: 1.0,
+            r_d: 1.0,
+            z_d: 0.1,
+            q: 1.2,
+            m_bulge: 2.0,
+            a_bulge: 0.5,
+        };
+        let v2 = galaxy.m_bulge / galaxy.a_bulge;
+        let mut rng = Rng::new(5);
+        // inside 10 scale lengths, where the closed form keeps its digits
+        let (sampled, expected) = galaxy
+            .sample_bulge(20000, &mut rng)
+            .into_iter()
+            .map(|(r, v)| (r.norm() / galaxy.a_bulge, v.norm_2()))
+            .filter(|(r, _)| *r < 10.)
+            .fold((0.0, 0.0), |(sampled, expected), (r, v)| {
+                (sampled + v, expected + 3. * v2 * hernquist_sigma2(r))
+            });
+        let error = (sampled / expected - 1.).abs();
+        assert!(error < 0.02, "error={}", error);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::generate::rng::Rng;
+
+#[derive(Clone, Debug)]
+pub enum MassFunction {
+    Equal,
+    // dN/dm ∝ m^-2.35 between the two masses
+    Salpeter(f64, f64),
+    // dN/dm ∝ m^-0.3, m^-1.3 and m^-2.3 below 0.08, 0.5 and above
+    Kroupa(f64, f64),
+}
+
+// dN/dm ∝ m^-alpha on every segment, continuous at the breaks
+struct PowerLaw {
+    bounds: Vec<f64>,
+    alpha: Vec<f64>,
+    // cumulative weight of the segments
+    weights: Vec<f64>,
+}
+
+// ∫ m^-alpha dm from low to high
+fn integral(alpha: f64, low: f64, high: f64) -> f64 {
+    if (alpha - 1.).abs() < 1e-12 {
+        (high / low).ln()
+    } else {
+        (high.powf(1. - alpha) - low.powf(1. - alpha)) / (1. - alpha)
+    }
+}
+
+impl PowerLaw {
+    fn new(breaks: &[f64], alpha: &[f64], low: f64, high: f64) -> Self {
+        let mut bounds = vec![low];
+        let mut slopes = Vec::new();
+        for (k, a) in alpha.iter().enumerate() {
+            let upper = breaks.get(k).copied().unwrap_or(f64::INFINITY);
+            if upper > low && bounds.last().is_some_and(|b| *b < high) {
+                bounds.push(upper.min(high));
+                slopes.push(*a);
+            }
+        }
+        let mut weights = Vec::with_capacity(slopes.len());
+        let (mut total, mut k) = (0.0, 1.0);
+        for (s, a) in slopes.iter().enumerate() {
+            if s > 0 {
+                // continuity at the break
+                k *= bounds[s].powf(a - slopes[s - 1]);
+            }
+            total += k * integral(*a, bounds[s], bounds[s + 1]);
+            weights.push(total);
+        }
+        PowerLaw {
+            bounds,
+            alpha: slopes,
+            weights,
+        }
+    }
+    fn sample(&self, rng: &mut Rng) -> f64 {
+        let total = self.weights.last().copied().unwrap_or(0.0);
+        let x = rng.uniform() * total;
+        let s = self
+            .weights
+            .iter()
+            .position(|w| x < *w)
+            .unwrap_or(self.weights.len() - 1);
+        let (low, high, a) = (self.bounds[s], self.bounds[s + 1], self.alpha[s]);
+        let u = rng.uniform();
+        if (a - 1.).abs() < 1e-12 {
+            low * (high / low).powf(u)
+        } else {
+            let (l, h) = (low.powf(1. - a), high.powf(1. - a));
+            (l + u * (h - l)).powf(1. / (1. - a))
+        }
+    }
+}
+
+impl MassFunction {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            MassFunction::Equal => true,
+            MassFunction::Salpeter(low, high) | MassFunction::Kroupa(low, high) => {
+                *low > 0.0 && low < high && high.is_finite()
+            }
+        }
+    }
+    // masses of `n` bodies adding up to one
+    pub fn sample(&self, n: usize, rng: &mut Rng) -> Vec<f64> {
+        let law = match self {
+            MassFunction::Equal => return vec![1. / n as f64; n],
+            MassFunction::Salpeter(low, high) => PowerLaw::new(&[], &[2.35], *low, *high),
+            MassFunction::Kroupa(low, high) => {
+                PowerLaw::new(&[0.08, 0.5], &[0.3, 1.3, 2.3], *low, *high)
+            }
+        };
+        let m: Vec<f64> = (0..n).map(|_| law.sample(rng)).collect();
+        let total: f64 = m.iter().sum();
+        m.into_iter().map(|m| m / total).collect()
+    }
+}
@@ -0,0 +1,210 @@
+mod cluster;
+mod disk;
+mod mass;
+pub(crate) mod rng;
+use crate::initial::Initial;
+use crate::transform;
+use bima_rs::vec3::Vec3;
+use cluster::Model;
+use disk::{Galaxy, Ring};
+use mass::MassFunction;
+use pyo3::{PyErr, exceptions::PyValueError, prelude::*};
+use rng::Rng;
+
+pub enum GenerateErr {
+    InvalidModel,
+    InvalidMassFunction,
+    WrongPar,
+    Empty,
+}
+
+impl From<GenerateErr> for PyErr {
+    fn from(value: GenerateErr) -> Self {
+        match value {
+            GenerateErr::InvalidModel => PyValueError::new_err("Invalid model"),
+            GenerateErr::InvalidMassFunction => PyValueError::new_err("Invalid mass function"),
+            GenerateErr::WrongPar => PyValueError::new_err("Wrong parameters"),
+            GenerateErr::Empty => PyValueError::new_err("Number of bodies cannot be zero"),
+        }
+    }
+}
+
+fn get_model(model: u8, par: Option<f64>) -> Result<Model, GenerateErr> {
+    let model = match (model, par) {
+        (0, _) => Model::Plummer,
+        (1, Some(w0)) => Model::King(w0),
+        (1, None) => return Err(GenerateErr::WrongPar),
+        (2, _) => Model::Hernquist,
+        (3, _) => Model::Uniform,
+        _ => return Err(GenerateErr::InvalidModel),
+    };
+    model
+        .is_valid()
+        .then_some(model)
+        .ok_or(GenerateErr::WrongPar)
+}
+
+fn get_mass_function(
+    mass_function: u8,
+    range: Option<(f64, f64)>,
+) -> Result<MassFunction, GenerateErr> {
+    let mass_function = match mass_function {
+        0 => MassFunction::Equal,
+        1 => {
+            let (low, high) = range.unwrap_or((0.1, 100.));
+            MassFunction::Salpeter(low, high)
+        }
+        2 => {
+            let (low, high) = range.unwrap_or((0.08, 100.));
+            MassFunction::Kroupa(low, high)
+        }
+        _ => return Err(GenerateErr::InvalidMassFunction),
+    };
+    mass_function
+        .is_valid()
+        .then_some(mass_function)
+        .ok_or(GenerateErr::WrongPar)
+}
+
+// Moves the bodies to their centre of mass, then scales the positions to a
+// potential energy of -1/2 and the velocities to a kinetic energy of
+// `virial_ratio` / 2, with G = M = 1. The total energy is -1/4 in virial
+// equilibrium.
+fn to_nbody_units(bodies: &mut [Initial], virial_ratio: f64) {
+    transform::barycentre(bodies, true);
+    let (kinetic, potential) = transform::energies(bodies, 1.0);
+    let r_scale = if potential < 0.0 {
+        -2. * potential
+    } else {
+        1.0
+    };
+    let v_scale = if kinetic > 0.0 {
+        (0.5 * virial_ratio / kinetic).sqrt()
+    } else {
+        0.0
+    };
+    transform::scale(bodies, r_scale, v_scale);
+}
+
+// `model` is 0 for Plummer, 1 for King with `par` as W0, 2 for Hernquist and
+// 3 for a homogeneous sphere. `mass_function` is 0 for equal masses, 1 for
+// Salpeter and 2 for Kroupa, between the masses in `mass_range`.
+#[pyfunction]
+#[pyo3(signature = (n, model, par=None, mass_function=0, mass_range=None, virial_ratio=0.5, seed=0))]
+pub fn generate_cluster(
+    n: usize,
+    model: u8,
+    par: Option<f64>,
+    mass_function: u8,
+    mass_range: Option<(f64, f64)>,
+    virial_ratio: f64,
+    seed: u64,
+) -> PyResult<Vec<Initial>> {
+    if n == 0 {
+        return Err(GenerateErr::Empty.into());
+    }
+    if virial_ratio < 0.0 {
+        return Err(GenerateErr::WrongPar.into());
+    }
+    let model = get_model(model, par)?;
+    let mass_function = get_mass_function(mass_function, mass_range)?;
+    let mut rng = Rng::new(seed);
+    let m = mass_function.sample(n, &mut rng);
+    let mut bodies = to_initial(m, model.sample(n, &mut rng));
+    to_nbody_units(&mut bodies, virial_ratio);
+    Ok(bodies)
+}
+
+fn to_initial(m: Vec<f64>, states: Vec<(Vec3, Vec3)>) -> Vec<Initial> {
+    m.into_iter()
+        .zip(states)
+        .map(|(m, (r, v))| Initial {
+            m,
+            r,
+            v,
+            beta: 0.0,
+            tau: None,
+        })
+        .collect()
+}
+
+// The central body at rest at the origin, followed by `n` bodies sharing the
+// mass `m_disk`, which may be zero for test particles.
+#[pyfunction]
+#[pyo3(signature = (central, n, m_disk, r_in, r_out, p=1.0, sigma_e=0.0, sigma_i=0.0, g=1.0, seed=0))]
+pub fn generate_ring(
+    central: f64,
+    n: usize,
+    m_disk: f64,
+    r_in: f64,
+    r_out: f64,
+    p: f64,
+    sigma_e: f64,
+    sigma_i: f64,
+    g: f64,
+    seed: u64,
+) -> PyResult<Vec<Initial>> {
+    if n == 0 {
+        return Err(GenerateErr::Empty.into());
+    }
+    let ring = Ring {
+        r_in,
+        r_out,
+        p,
+        sigma_e,
+        sigma_i,
+    };
+    if central <= 0.0 || m_disk < 0.0 || !ring.is_valid() {
+        return Err(GenerateErr::WrongPar.into());
+    }
+    let mut rng = Rng::new(seed);
+    let m = m_disk / n as f64;
+    let states = ring.sample(n, g * (central + m), &mut rng);
+    let masses = std::iter::once(central)
+        .chain(std::iter::repeat_n(m, n))
+        .collect();
+    let states = std::iter::once((Vec3::zero(), Vec3::zero()))
+        .chain(states)
+        .collect();
+    Ok(to_initial(masses, states))
+}
+
+// The bulge bodies followed by the disk bodies, in their centre of mass
+// frame, with G = 1.
+#[pyfunction]
+#[pyo3(signature = (n_disk, m_disk, r_d, z_d, n_bulge=0, m_bulge=0.0, a_bulge=1.0, q=1.5, seed=0))]
+pub fn generate_galaxy(
+    n_disk: usize,
+    m_disk: f64,
+    r_d: f64,
+    z_d: f64,
+    n_bulge: usize,
+    m_bulge: f64,
+    a_bulge: f64,
+    q: f64,
+    seed: u64,
+) -> PyResult<Vec<Initial>> {
+    if n_disk + n_bulge == 0 {
+        return Err(GenerateErr::Empty.into());
+    }
+    let galaxy = Galaxy {
+        m_disk,
+        r_d,
+        z_d,
+        q,
+        m_bulge: if n_bulge > 0 { m_bulge } else { 0.0 },
+        a_bulge,
+    };
+    if !galaxy.is_valid() || m_disk + galaxy.m_bulge <= 0.0 {
+        return Err(GenerateErr::WrongPar.into());
+    }
+    let mut rng = Rng::new(seed);
+    let mut states = galaxy.sample_bulge(n_bulge, &mut rng);
+    states.extend(galaxy.sample_disk(n_disk, &mut rng));
+    let m: Vec<f64> = std::iter::repeat_n(m_bulge / n_bulge.max(1) as f64, n_bulge)
+        .chain(std::iter::repeat_n(m_disk / n_disk.max(1) as f64, n_disk))
+        .collect();
+    let mut bodies = to_initial(m, states);
+    transform::barycentre(&mut bodies, true);
+    Ok(bodies)
+}
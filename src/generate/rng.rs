@@ -0,0 +1,52 @@
+use bima_rs::vec3::Vec3;
+use std::f64::consts::PI;
+
+// xoshiro256** seeded through splitmix64, so that a seed gives the same
+// bodies on every platform.
+pub struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut s = [0; 4];
+        for item in s.iter_mut() {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *item = z ^ (z >> 31);
+        }
+        Rng { s }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+    // in [0, 1)
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+    pub fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.uniform()
+    }
+    pub fn normal(&mut self) -> f64 {
+        let u = 1. - self.uniform();
+        let v = self.uniform();
+        (-2. * u.ln()).sqrt() * (2. * PI * v).cos()
+    }
+    pub fn direction(&mut self) -> Vec3 {
+        let cos_theta = self.range(-1., 1.);
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let phi = self.range(0., 2. * PI);
+        Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+}
@@ -1,16 +1,27 @@
-use pyo3::{exceptions::PyValueError, prelude::*};
+#[cfg(feature = "python")]
+use crate::orbit::{self, Elements, Frame};
 use bima_rs::vec3::Vec3;
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, prelude::*};
 
-#[pyclass]
-#[derive(Debug)]
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
 pub struct Initial {
     pub m: f64,
     pub r: Vec3,
     pub v: Vec3,
+    // radiation pressure to gravity ratio and drag timescale
+    pub beta: f64,
+    pub tau: Option<f64>,
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl Initial {
+    #[getter(m)]
+    fn get_m(&self) -> f64 {
+        self.m
+    }
     fn __repr__(&self) -> String {
         format!(
             "Initial(m={:.9}, r={}, v={})",
@@ -23,7 +34,9 @@ impl Initial {
         self.__repr__()
     }
 }
+#[cfg(feature = "python")]
 #[pyfunction]
+#[pyo3(signature = (m, x, y, z, vx, vy, vz, beta=None, tau=None))]
 pub fn set_initial(
     m: Vec<f64>,
     x: Vec<f64>,
@@ -32,14 +45,20 @@ pub fn set_initial(
     vx: Vec<f64>,
     vy: Vec<f64>,
     vz: Vec<f64>,
+    beta: Option<Vec<f64>>,
+    tau: Option<Vec<Option<f64>>>,
 ) -> PyResult<Vec<Initial>> {
     let n = m.len();
+    let beta = beta.unwrap_or_else(|| vec![0.0; n]);
+    let tau = tau.unwrap_or_else(|| vec![None; n]);
     if n != x.len()
         || n != y.len()
         || n != z.len()
         || n != vx.len()
         || n != vy.len()
         || n != vz.len()
+        || n != beta.len()
+        || n != tau.len()
     {
         return Err(PyValueError::new_err("Dimension not same"));
     }
@@ -48,6 +67,67 @@ pub fn set_initial(
             m: m[i],
             r: Vec3::new(x[i], y[i], z[i]),
             v: Vec3::new(vx[i], vy[i], vz[i]),
+            beta: beta[i],
+            tau: tau[i],
+        })
+        .collect())
+}
+
+// The central body comes first, followed by the bodies in the given order.
+// Angles are in radians, `frame` is 0 for heliocentric and 1 for jacobi.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (central, m, a, e, i, node, peri, mean_anomaly, g=1.0, frame=0, beta=None, tau=None))]
+pub fn set_initial_elements(
+    central: f64,
+    m: Vec<f64>,
+    a: Vec<f64>,
+    e: Vec<f64>,
+    i: Vec<f64>,
+    node: Vec<f64>,
+    peri: Vec<f64>,
+    mean_anomaly: Vec<f64>,
+    g: f64,
+    frame: u8,
+    beta: Option<Vec<f64>>,
+    tau: Option<Vec<Option<f64>>>,
+) -> PyResult<Vec<Initial>> {
+    let n = m.len();
+    let beta = beta.unwrap_or_else(|| vec![0.0; n + 1]);
+    let tau = tau.unwrap_or_else(|| vec![None; n + 1]);
+    if n != a.len()
+        || n != e.len()
+        || n != i.len()
+        || n != node.len()
+        || n != peri.len()
+        || n != mean_anomaly.len()
+        || n + 1 != beta.len()
+        || n + 1 != tau.len()
+    {
+        return Err(PyValueError::new_err("Dimension not same"));
+    }
+    let frame = Frame::try_from(frame)?;
+    let elements: Vec<Elements> = (0..n)
+        .map(|k| Elements {
+            a: a[k],
+            e: e[k],
+            i: i[k],
+            node: node[k],
+            peri: peri[k],
+            mean_anomaly: mean_anomaly[k],
+        })
+        .collect();
+    let states = orbit::to_cartesian(central, &m, &elements, g, frame)?;
+    Ok(states
+        .into_iter()
+        .zip(std::iter::once(central).chain(m))
+        .enumerate()
+        .map(|(k, ((r, v), m))| Initial {
+            m,
+            r,
+            v,
+            beta: beta[k],
+            tau: tau[k],
         })
         .collect())
 }
@@ -1,16 +1,46 @@
-mod initial;
-mod simulation;
+pub mod initial;
+pub mod load;
+pub mod simulation;
+pub mod units;
+#[cfg(feature = "python")]
 mod energy;
+#[cfg(feature = "python")]
+mod generate;
+#[cfg(feature = "python")]
+mod orbit;
+#[cfg(feature = "python")]
 mod progress_bar;
-use initial::set_initial;
+#[cfg(feature = "python")]
+mod transform;
+#[cfg(feature = "python")]
+use initial::{set_initial, set_initial_elements};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
+#[cfg(feature = "python")]
 #[pymodule]
 #[pyo3(name = "_bima")] // Name must match Cargo.toml
 fn _bima(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(set_initial, m)?)?;
+    m.add_function(wrap_pyfunction!(set_initial_elements, m)?)?;
     m.add_function(wrap_pyfunction!(energy::calc_energy, m)?)?;
+    m.add_function(wrap_pyfunction!(orbit::calc_elements, m)?)?;
+    m.add_function(wrap_pyfunction!(generate::generate_cluster, m)?)?;
+    m.add_function(wrap_pyfunction!(generate::generate_ring, m)?)?;
+    m.add_function(wrap_pyfunction!(generate::generate_galaxy, m)?)?;
+    m.add_function(wrap_pyfunction!(load::load_table, m)?)?;
+    m.add_function(wrap_pyfunction!(load::load_snapshot, m)?)?;
+    m.add_function(wrap_pyfunction!(load::load_result, m)?)?;
+    m.add_function(wrap_pyfunction!(transform::to_barycentric, m)?)?;
+    m.add_function(wrap_pyfunction!(transform::rotate_euler, m)?)?;
+    m.add_function(wrap_pyfunction!(transform::to_invariable_plane, m)?)?;
+    m.add_function(wrap_pyfunction!(transform::boost, m)?)?;
+    m.add_function(wrap_pyfunction!(transform::scale_virial, m)?)?;
+    m.add_function(wrap_pyfunction!(transform::scale_energy, m)?)?;
+    m.add_function(wrap_pyfunction!(transform::perturb, m)?)?;
     m.add_class::<simulation::Simulation>()?;
+    m.add_class::<simulation::Ensemble>()?;
     m.add_class::<initial::Initial>()?;
+    m.add_class::<units::UnitSystem>()?;
     Ok(())
 }
@@ -0,0 +1,279 @@
+use crate::initial::Initial;
+use bima_rs::vec3::Vec3;
+use hdf5::{self, File, Group};
+#[cfg(feature = "python")]
+use pyo3::{PyErr, exceptions::PyValueError, prelude::*};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+#[derive(Debug)]
+pub enum LoadErr {
+    Io(std::io::Error),
+    Hdf5Err(hdf5::Error),
+    MissingColumn(String),
+    // line number, starting from 1
+    Parse(usize),
+    Dimension,
+    NoTime,
+    Empty,
+}
+
+impl fmt::Display for LoadErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadErr::Io(e) => write!(f, "{}", e),
+            LoadErr::Hdf5Err(e) => write!(f, "{}", e),
+            LoadErr::MissingColumn(name) => write!(f, "Column {} is missing", name),
+            LoadErr::Parse(line) => write!(f, "Failed to parse line {}", line),
+            LoadErr::Dimension => write!(f, "Dimension not same"),
+            LoadErr::NoTime => write!(f, "Time index out of range"),
+            LoadErr::Empty => write!(f, "No bodies found"),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<LoadErr> for PyErr {
+    fn from(value: LoadErr) -> Self {
+        PyValueError::new_err(value.to_string())
+    }
+}
+
+impl From<std::io::Error> for LoadErr {
+    fn from(e: std::io::Error) -> Self {
+        LoadErr::Io(e)
+    }
+}
+
+impl From<hdf5::Error> for LoadErr {
+    fn from(e: hdf5::Error) -> Self {
+        LoadErr::Hdf5Err(e)
+    }
+}
+
+fn initial(m: f64, r: Vec3, v: Vec3) -> Initial {
+    Initial {
+        m,
+        r,
+        v,
+        beta: 0.0,
+        tau: None,
+    }
+}
+
+// Comma separated if the line has a comma, whitespace separated otherwise.
+fn split(line: &str) -> Vec<&str> {
+    if line.contains(',') {
+        line.split(',').map(|s| s.trim()).collect()
+    } else {
+        line.split_whitespace().collect()
+    }
+}
+
+// A table with the columns m, x, y, z, vx, vy, vz and optionally beta and tau,
+// in any order. Lines starting with `#` are skipped, and the first line names
+// the columns unless `columns` is given. An empty or `nan` tau means no drag.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (path, columns=None))]
+pub fn load_table(path: &str, columns: Option<Vec<String>>) -> PyResult<Vec<Initial>> {
+    Ok(read_table(path, columns)?)
+}
+
+pub fn read_table(path: &str, columns: Option<Vec<String>>) -> Result<Vec<Initial>, LoadErr> {
+    let content = fs::read_to_string(path)?;
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    let columns = match columns {
+        Some(columns) => columns,
+        None => match lines.next() {
+            Some((_, header)) => split(header).into_iter().map(String::from).collect(),
+            None => return Err(LoadErr::Empty),
+        },
+    };
+    let index: HashMap<&str, usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.as_str(), i))
+        .collect();
+    let required = ["m", "x", "y", "z", "vx", "vy", "vz"];
+    let mut at = [0; 7];
+    for (k, name) in required.iter().enumerate() {
+        at[k] = *index
+            .get(name)
+            .ok_or_else(|| LoadErr::MissingColumn(name.to_string()))?;
+    }
+    let mut bodies = Vec::new();
+    for (n, line) in lines {
+        let values: Vec<&str> = split(line);
+        if values.len() != columns.len() {
+            return Err(LoadErr::Parse(n + 1));
+        }
+        let parse = |i: usize| values[i].parse::<f64>().map_err(|_| LoadErr::Parse(n + 1));
+        let v = at
+            .iter()
+            .map(|i| parse(*i))
+            .collect::<Result<Vec<f64>, _>>()?;
+        let mut body = initial(
+            v[0],
+            Vec3::new(v[1], v[2], v[3]),
+            Vec3::new(v[4], v[5], v[6]),
+        );
+        if let Some(i) = index.get("beta") {
+            body.beta = parse(*i)?;
+        }
+        if let Some(i) = index.get("tau") {
+            body.tau = if values[*i].is_empty() {
+                None
+            } else {
+                Some(parse(*i)?).filter(|tau| !tau.is_nan())
+            };
+        }
+        bodies.push(body);
+    }
+    if bodies.is_empty() {
+        return Err(LoadErr::Empty);
+    }
+    Ok(bodies)
+}
+
+// Every particle type of a Gadget style snapshot in order, with the masses
+// from `Masses` or from `MassTable` in the header. Velocities are taken as
+// they are stored.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (path))]
+pub fn load_snapshot(path: &str) -> PyResult<Vec<Initial>> {
+    Ok(read_snapshot(path)?)
+}
+
+pub fn read_snapshot(path: &str) -> Result<Vec<Initial>, LoadErr> {
+    let file = File::open(path)?;
+    let mass_table = file
+        .group("Header")
+        .and_then(|header| header.attr("MassTable"))
+        .and_then(|attr| attr.read_raw::<f64>())
+        .unwrap_or_default();
+    let mut bodies = Vec::new();
+    for part_type in 0..6 {
+        let Ok(group) = file.group(&format!("PartType{}", part_type)) else {
+            continue;
+        };
+        let r = group.dataset("Coordinates")?.read_raw::<f64>()?;
+        let v = group.dataset("Velocities")?.read_raw::<f64>()?;
+        let n = r.len() / 3;
+        if r.len() != 3 * n || v.len() != r.len() {
+            return Err(LoadErr::Dimension);
+        }
+        let m = match group.dataset("Masses") {
+            Ok(dataset) => dataset.read_raw::<f64>()?,
+            Err(_) => vec![mass_table.get(part_type).copied().unwrap_or(0.0); n],
+        };
+        if m.len() != n {
+            return Err(LoadErr::Dimension);
+        }
+        bodies.extend((0..n).map(|i| {
+            initial(
+                m[i],
+                Vec3::new(r[3 * i], r[3 * i + 1], r[3 * i + 2]),
+                Vec3::new(v[3 * i], v[3 * i + 1], v[3 * i + 2]),
+            )
+        }));
+    }
+    if bodies.is_empty() {
+        return Err(LoadErr::Empty);
+    }
+    Ok(bodies)
+}
+
+// the chunks of one column of an object, in order
+fn read_column(obj: &Group, name: &str) -> Result<Vec<f64>, LoadErr> {
+    let group = obj.group(name)?;
+    let mut chunks = group.member_names()?;
+    chunks.sort();
+    let mut column = Vec::new();
+    for chunk in chunks {
+        column.extend(group.dataset(&chunk)?.read_raw::<f64>()?);
+    }
+    Ok(column)
+}
+
+// the value of one column of an object at `row`, reading only its chunk
+fn read_row(obj: &Group, name: &str, row: usize) -> Result<f64, LoadErr> {
+    let group = obj.group(name)?;
+    let mut chunks = group.member_names()?;
+    chunks.sort();
+    let mut start = 0;
+    for chunk in chunks {
+        let dataset = group.dataset(&chunk)?;
+        let len = dataset.size();
+        if row < start + len {
+            return Ok(dataset.read_raw::<f64>()?[row - start]);
+        }
+        start += len;
+    }
+    Err(LoadErr::Dimension)
+}
+
+// The bodies of an earlier run at the time `index` of the longest lived
+// object, negative from the end, followed by the test particles. Objects that
+// had escaped by then are left out. Returns the bodies and the time.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (path, index=-1))]
+pub fn load_result(path: &str, index: i64) -> PyResult<(Vec<Initial>, f64)> {
+    Ok(read_result(path, index)?)
+}
+
+pub fn read_result(path: &str, index: i64) -> Result<(Vec<Initial>, f64), LoadErr> {
+    let file = File::open(path)?;
+    let mut objects = Vec::new();
+    for name in ["objects", "test_particles"] {
+        let Ok(group) = file.group(name) else {
+            continue;
+        };
+        let mut ids = group
+            .member_names()?
+            .into_iter()
+            .filter_map(|id| id.parse::<usize>().ok())
+            .collect::<Vec<usize>>();
+        ids.sort();
+        for id in ids {
+            objects.push(group.group(&id.to_string())?);
+        }
+    }
+    let times = objects
+        .iter()
+        .map(|obj| read_column(obj, "t"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let longest = times.iter().max_by_key(|t| t.len()).ok_or(LoadErr::Empty)?;
+    let n = longest.len() as i64;
+    let index = if index < 0 { n + index } else { index };
+    if index < 0 || index >= n {
+        return Err(LoadErr::NoTime);
+    }
+    let t = longest[index as usize];
+    // the times of the objects are sorted, and may differ from `t` by rounding
+    let tolerance = 1e-9 * t.abs().max(1.0);
+    let mut bodies = Vec::new();
+    for (obj, times) in objects.iter().zip(times.iter()) {
+        let row = times.partition_point(|time| *time < t - tolerance);
+        if times.get(row).is_none_or(|time| *time > t + tolerance) {
+            continue;
+        }
+        let m = obj.dataset("m")?.read_raw::<f64>()?;
+        let mut state = [0.0; 6];
+        for (value, name) in state.iter_mut().zip(["x", "y", "z", "vx", "vy", "vz"]) {
+            *value = read_row(obj, name, row)?;
+        }
+        bodies.push(initial(
+            m[0],
+            Vec3::new(state[0], state[1], state[2]),
+            Vec3::new(state[3], state[4], state[5]),
+        ));
+    }
+    Ok((bodies, t))
+}
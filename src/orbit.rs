@@ -0,0 +1,303 @@
+use bima_rs::vec3::Vec3;
+use numpy::{IntoPyArray, PyArray1, PyReadonlyArray2};
+use pyo3::{PyErr, exceptions::PyValueError, prelude::*};
+use std::f64::consts::PI;
+
+const MAX_ITERATION: usize = 100;
+const TOLERANCE: f64 = 1e-14;
+
+pub enum ElementErr {
+    Dimension,
+    NoCentralMass,
+    // the semi-major axis and eccentricity do not describe a conic
+    Invalid(usize),
+    InvalidFrame,
+}
+
+impl From<ElementErr> for PyErr {
+    fn from(value: ElementErr) -> Self {
+        match value {
+            ElementErr::Dimension => PyValueError::new_err("Dimension not same"),
+            ElementErr::NoCentralMass => PyValueError::new_err("Central mass must be positive"),
+            ElementErr::Invalid(i) => PyValueError::new_err(format!(
+                "Invalid orbital elements for body {}: a must be positive for e < 1 and negative for e > 1",
+                i
+            )),
+            ElementErr::InvalidFrame => PyValueError::new_err("Invalid frame"),
+        }
+    }
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a.x() * b.x() + a.y() * b.y() + a.z() * b.z()
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new(
+        a.y() * b.z() - a.z() * b.y(),
+        a.z() * b.x() - a.x() * b.z(),
+        a.x() * b.y() - a.y() * b.x(),
+    )
+}
+
+// Which body the elements of every body are relative to.
+#[derive(Clone, Copy, Debug)]
+pub enum Frame {
+    // the central body
+    Heliocentric,
+    // the centre of mass of the central body and all the bodies before it
+    Jacobi,
+}
+
+impl TryFrom<u8> for Frame {
+    type Error = ElementErr;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Frame::Heliocentric),
+            1 => Ok(Frame::Jacobi),
+            _ => Err(ElementErr::InvalidFrame),
+        }
+    }
+}
+
+// Angles are in radians. `a` is negative for hyperbolic orbits, and is the
+// pericentre distance for parabolic ones.
+#[derive(Clone, Copy, Debug)]
+pub struct Elements {
+    pub a: f64,
+    pub e: f64,
+    pub i: f64,
+    pub node: f64,
+    pub peri: f64,
+    pub mean_anomaly: f64,
+}
+
+impl Elements {
+    fn is_valid(&self) -> bool {
+        let valid_e = self.e >= 0.0 && self.e.is_finite();
+        if self.e == 1.0 {
+            valid_e && self.a > 0.0
+        } else {
+            valid_e && self.a * (1. - self.e) > 0.0
+        }
+    }
+    // semi-latus rectum
+    fn p(&self) -> f64 {
+        if self.e == 1.0 {
+            2. * self.a
+        } else {
+            self.a * (1. - self.e * self.e)
+        }
+    }
+    // Osculating elements of the relative position and velocity, the inverse
+    // of `to_cartesian`. Circular and equatorial orbits measure the angles from
+    // the ascending node and the x axis respectively.
+    pub fn from_cartesian(r: Vec3, v: Vec3, mu: f64) -> Self {
+        let h = cross(r, v);
+        let h_hat = h.hat();
+        let e_vec = ((v.norm_2() - mu / r.norm()) * r - dot(r, v) * v) / mu;
+        let e = e_vec.norm();
+        let energy = 0.5 * v.norm_2() - mu / r.norm();
+        let a = if e == 1.0 {
+            h.norm_2() / (2. * mu)
+        } else {
+            -mu / (2. * energy)
+        };
+        let i = (h.z() / h.norm()).clamp(-1., 1.).acos();
+        let n = Vec3::new(-h.y(), h.x(), 0.0);
+        let n_hat = if n.norm() > TOLERANCE * h.norm() {
+            n.hat()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let node = n_hat.y().atan2(n_hat.x()).rem_euclid(2. * PI);
+        let angle = |u: Vec3| dot(u, cross(h_hat, n_hat)).atan2(dot(u, n_hat));
+        let (peri, f) = if e > TOLERANCE {
+            let e_hat = e_vec.hat();
+            let f = dot(r, cross(h_hat, e_hat)).atan2(dot(r, e_hat));
+            (angle(e_hat).rem_euclid(2. * PI), f.rem_euclid(2. * PI))
+        } else {
+            (0.0, angle(r).rem_euclid(2. * PI))
+        };
+        let mean_anomaly = if e < 1.0 {
+            let ea =
+                2. * ((1. - e).sqrt() * (f / 2.).sin()).atan2((1. + e).sqrt() * (f / 2.).cos());
+            (ea - e * ea.sin()).rem_euclid(2. * PI)
+        } else if e > 1.0 {
+            let ha = 2. * (((e - 1.) / (e + 1.)).sqrt() * (f / 2.).tan()).atanh();
+            e * ha.sinh() - ha
+        } else {
+            let d = (f / 2.).tan();
+            d + d * d * d / 3.
+        };
+        Elements {
+            a,
+            e,
+            i,
+            node,
+            peri,
+            mean_anomaly,
+        }
+    }
+    pub fn true_anomaly(&self) -> f64 {
+        let (e, m) = (self.e, self.mean_anomaly);
+        if e < 1.0 {
+            let m = m.rem_euclid(2. * PI);
+            let mut ea = if e > 0.8 { PI } else { m };
+            for _ in 0..MAX_ITERATION {
+                let delta = (ea - e * ea.sin() - m) / (1. - e * ea.cos());
+                ea -= delta;
+                if delta.abs() < TOLERANCE {
+                    break;
+                }
+            }
+            2. * ((1. + e).sqrt() * (ea / 2.).sin()).atan2((1. - e).sqrt() * (ea / 2.).cos())
+        } else if e > 1.0 {
+            let mut ha = (2. * m / e).asinh();
+            for _ in 0..MAX_ITERATION {
+                let delta = (e * ha.sinh() - ha - m) / (e * ha.cosh() - 1.);
+                ha -= delta;
+                if delta.abs() < TOLERANCE {
+                    break;
+                }
+            }
+            2. * (((e + 1.) / (e - 1.)).sqrt() * (ha / 2.).tanh()).atan()
+        } else {
+            // Barker's equation M = D + D³/3 with D = tan(ν/2)
+            let w = 1.5 * m;
+            let y = (w + (w * w + 1.).sqrt()).cbrt();
+            2. * (y - 1. / y).atan()
+        }
+    }
+    // Position and velocity relative to the primary, `mu` being G times the
+    // mass of the primary and the body.
+    pub fn to_cartesian(self, mu: f64) -> (Vec3, Vec3) {
+        let nu = self.true_anomaly();
+        let p = self.p();
+        let r = p / (1. + self.e * nu.cos());
+        let k = (mu / p).sqrt();
+        let (x, y) = (r * nu.cos(), r * nu.sin());
+        let (vx, vy) = (-k * nu.sin(), k * (self.e + nu.cos()));
+        let (so, co) = self.node.sin_cos();
+        let (sw, cw) = self.peri.sin_cos();
+        let (si, ci) = self.i.sin_cos();
+        // perifocal to reference frame, rotated by ω, i then Ω
+        let p_hat = Vec3::new(co * cw - so * sw * ci, so * cw + co * sw * ci, sw * si);
+        let q_hat = Vec3::new(-co * sw - so * cw * ci, -so * sw + co * cw * ci, cw * si);
+        (x * p_hat + y * q_hat, vx * p_hat + vy * q_hat)
+    }
+}
+
+// Positions and velocities of the central body followed by the bodies, with
+// the central body at rest at the origin.
+pub fn to_cartesian(
+    central: f64,
+    m: &[f64],
+    elements: &[Elements],
+    g: f64,
+    frame: Frame,
+) -> Result<Vec<(Vec3, Vec3)>, ElementErr> {
+    if central <= 0.0 {
+        return Err(ElementErr::NoCentralMass);
+    }
+    if m.len() != elements.len() {
+        return Err(ElementErr::Dimension);
+    }
+    if let Some(i) = elements.iter().position(|el| !el.is_valid()) {
+        return Err(ElementErr::Invalid(i));
+    }
+    let mut states = Vec::with_capacity(m.len() + 1);
+    states.push((Vec3::zero(), Vec3::zero()));
+    // interior mass with its centre of mass position and velocity
+    let (mut mass, mut r_cm, mut v_cm) = (central, Vec3::zero(), Vec3::zero());
+    for (m, el) in m.iter().zip(elements.iter()) {
+        let (r, v) = match frame {
+            Frame::Heliocentric => el.to_cartesian(g * (central + m)),
+            Frame::Jacobi => {
+                let (r, v) = el.to_cartesian(g * (mass + m));
+                (r + r_cm, v + v_cm)
+            }
+        };
+        r_cm = (mass * r_cm + *m * r) / (mass + m);
+        v_cm = (mass * v_cm + *m * v) / (mass + m);
+        mass += m;
+        states.push((r, v));
+    }
+    Ok(states)
+}
+
+// The rows of `body` and of every primary are `t, x, y, z, vx, vy, vz` at the
+// same times, as in the output of a run. The elements are relative to the
+// centre of mass of the primaries and cover the times every one of them has.
+// Returns t, a, e, i, node, peri, true anomaly, mean anomaly and period.
+#[pyfunction]
+#[pyo3(signature = (body, m, primaries, masses, g=1.0))]
+pub fn calc_elements<'py>(
+    py: Python<'py>,
+    body: PyReadonlyArray2<'py, f64>,
+    m: f64,
+    primaries: Vec<PyReadonlyArray2<'py, f64>>,
+    masses: Vec<f64>,
+    g: f64,
+) -> PyResult<[Py<PyArray1<f64>>; 9]> {
+    if primaries.is_empty() {
+        return Err(PyValueError::new_err("primaries cannot be empty"));
+    }
+    if primaries.len() != masses.len() {
+        return Err(PyValueError::new_err(
+            "masses and primaries must have the same length",
+        ));
+    }
+    let mass: f64 = masses.iter().sum();
+    if mass <= 0.0 {
+        return Err(PyValueError::new_err("Total mass of the primaries is zero"));
+    }
+    let body = body.as_array();
+    let primaries: Vec<_> = primaries.iter().map(|p| p.as_array()).collect();
+    if std::iter::once(&body)
+        .chain(primaries.iter())
+        .any(|arr| arr.ncols() < 7)
+    {
+        return Err(PyValueError::new_err(
+            "Malformed data. Should have at least 7 columns",
+        ));
+    }
+    let n = primaries
+        .iter()
+        .fold(body.nrows(), |n, arr| n.min(arr.nrows()));
+    let mu = g * (mass + m);
+    let mut columns: [Vec<f64>; 9] = Default::default();
+    for k in 0..n {
+        let row = body.row(k);
+        let (mut r, mut v) = (
+            Vec3::new(row[1], row[2], row[3]),
+            Vec3::new(row[4], row[5], row[6]),
+        );
+        for (arr, m) in primaries.iter().zip(masses.iter()) {
+            let row = arr.row(k);
+            r -= *m / mass * Vec3::new(row[1], row[2], row[3]);
+            v -= *m / mass * Vec3::new(row[4], row[5], row[6]);
+        }
+        let el = Elements::from_cartesian(r, v, mu);
+        let period = if el.a > 0.0 {
+            2. * PI * (el.a * el.a * el.a / mu).sqrt()
+        } else {
+            f64::INFINITY
+        };
+        let values = [
+            row[0],
+            el.a,
+            el.e,
+            el.i,
+            el.node,
+            el.peri,
+            el.true_anomaly(),
+            el.mean_anomaly,
+            period,
+        ];
+        for (column, value) in columns.iter_mut().zip(values) {
+            column.push(value);
+        }
+    }
+    Ok(columns.map(|column| column.into_pyarray(py).into()))
+}
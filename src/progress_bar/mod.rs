@@ -1,10 +1,11 @@
-use pyo3::prelude::*;
+use crate::simulation::driver::{DriverErr, Progress};
+use std::fmt;
 use std::time::Instant;
-use std::u64;
 pub mod py_stdout;
 
 pub trait Wrt {
-    fn write(&self, str: &str) -> PyResult<()>;
+    type Err;
+    fn write(&self, str: &str) -> Result<(), Self::Err>;
 }
 
 pub struct ProgressBar<W: Wrt> {
@@ -14,18 +15,18 @@ pub struct ProgressBar<W: Wrt> {
     writer: W,
 }
 
-fn print_bar_start<'py, W: Wrt>(wrt: &W, length: usize) -> PyResult<()> {
+fn print_bar_start<W: Wrt>(wrt: &W, length: usize) -> Result<(), W::Err> {
     let progress_str = format!("\r[{}] 0% (0) [??? it/s]", " ".repeat(length));
     wrt.write(&progress_str)?;
     Ok(())
 }
-fn print_bar<'py, W: Wrt>(
+fn print_bar<W: Wrt>(
     wrt: &W,
     percentage: f64,
     length: usize,
     total_iteration: usize,
     speed: u64,
-) -> PyResult<()> {
+) -> Result<(), W::Err> {
     let current = (percentage * length as f64) as usize;
     let progress_str = format!(
         "\r[{}{}] {:.2}% ({total_iteration}) [{speed} it/s]",
@@ -46,7 +47,7 @@ fn calc_speed(num_iteration: usize, delta_ms: u128) -> u64 {
 }
 
 impl<W: Wrt> ProgressBar<W> {
-    pub fn new(writer: W, length: usize) -> PyResult<Self> {
+    pub fn new(writer: W, length: usize) -> Result<Self, W::Err> {
         print_bar_start(&writer, length)?;
         Ok(ProgressBar {
             length,
@@ -58,7 +59,7 @@ impl<W: Wrt> ProgressBar<W> {
 }
 
 impl<W: Wrt> ProgressBar<W> {
-    pub fn update(&mut self, iteration: usize, percentage: f64) -> Result<(), PyErr> {
+    pub fn update(&mut self, iteration: usize, percentage: f64) -> Result<(), W::Err> {
         let now = Instant::now();
         let delta_ms = now.duration_since(self.last_time).as_millis();
         let speed = calc_speed(iteration - self.last_iteration, delta_ms);
@@ -71,3 +72,13 @@ impl<W: Wrt> ProgressBar<W> {
         Ok(())
     }
 }
+
+impl<W: Wrt> Progress for ProgressBar<W>
+where
+    W::Err: fmt::Display,
+{
+    fn update(&mut self, iteration: usize, percentage: f64) -> Result<(), DriverErr> {
+        ProgressBar::update(self, iteration, percentage)
+            .map_err(|e| DriverErr::Progress(e.to_string()))
+    }
+}
@@ -1,22 +1,29 @@
 use crate::progress_bar::Wrt;
 use pyo3::prelude::*;
 
-pub struct PyStdout<'py> {
-    stdout: Bound<'py, PyAny>,
+// `sys.stdout`, written to with the GIL taken for each line, so the runs can
+// go on without it. A pending Ctrl-C comes back as an error.
+pub struct PyStdout {
+    stdout: Py<PyAny>,
 }
 
-impl<'py> PyStdout<'py> {
-    pub fn new(py: &Python<'py>) -> PyResult<Self> {
+impl PyStdout {
+    pub fn new(py: &Python<'_>) -> PyResult<Self> {
         let sys = PyModule::import(*py, "sys")?;
-        let stdout = sys.getattr("stdout")?;
+        let stdout = sys.getattr("stdout")?.unbind();
         Ok(PyStdout { stdout })
     }
 }
 
-impl<'py> Wrt for PyStdout<'py> {
+impl Wrt for PyStdout {
+    type Err = PyErr;
     fn write(&self, str: &str) -> PyResult<()> {
-        self.stdout.call_method1("write", (str,))?;
-        self.stdout.call_method0("flush")?;
-        Ok(())
+        Python::with_gil(|py| {
+            py.check_signals()?;
+            let stdout = self.stdout.bind(py);
+            stdout.call_method1("write", (str,))?;
+            stdout.call_method0("flush")?;
+            Ok(())
+        })
     }
 }
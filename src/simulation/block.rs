@@ -0,0 +1,347 @@
+use crate::simulation::boundary::Frame;
+use crate::simulation::external::External;
+use crate::simulation::nongravity::NonGravity;
+use bima_rs::body::Body;
+use bima_rs::close_encounter::CloseEncounter;
+use bima_rs::vec3::Vec3;
+use bima_rs::vec6::Vec6;
+use rayon::ThreadPool;
+use rayon::prelude::*;
+
+// the shortest step is delta_t / 2^MAX_LEVEL
+const MAX_LEVEL: u32 = 40;
+const END: u64 = 1 << MAX_LEVEL;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheme {
+    // fourth order predictor-corrector, with the step of Aarseth from the
+    // derivatives of the acceleration
+    Hermite,
+    // kick-drift-kick, with a step of eta |a| / |jerk|
+    LeapFrog,
+}
+
+// Individual steps of delta_t / 2^k for every body, all of them meeting at
+// the multiples of delta_t.
+#[derive(Clone, Copy, Debug)]
+pub struct Block {
+    pub delta_t: f64,
+    pub eta: f64,
+    pub scheme: Scheme,
+}
+
+// What acts on the bodies besides their gravity, as in `step::constant_step`.
+pub struct Forces<'a> {
+    pub close_encounter: &'a CloseEncounter,
+    pub external: &'a mut External,
+    pub frame: &'a Frame,
+    pub nongravity: Option<&'a NonGravity>,
+    pub pool: Option<&'a ThreadPool>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    r: Vec3,
+    v: Vec3,
+    a: Vec3,
+    jerk: Vec3,
+    // in units of delta_t / 2^MAX_LEVEL from the start of the interval
+    tick: u64,
+    level: u32,
+}
+
+impl Particle {
+    fn step(&self) -> u64 {
+        1 << (MAX_LEVEL - self.level)
+    }
+    // position and velocity `dt` later, to third order
+    fn predict(&self, dt: f64) -> (Vec3, Vec3) {
+        let r = self.r + dt * self.v + (dt * dt / 2.0) * self.a + (dt * dt * dt / 6.0) * self.jerk;
+        let v = self.v + dt * self.a + (dt * dt / 2.0) * self.jerk;
+        (r, v)
+    }
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a.x() * b.x() + a.y() * b.y() + a.z() * b.z()
+}
+
+// Acceleration and jerk due to one body at `d` moving at `dv` relative to
+// the point, m / (divisor |d|) along d with the divisor of the close
+// encounter method, as in `bima_rs::force::gravity`.
+fn pull(m: f64, d: Vec3, dv: Vec3, close_encounter: &CloseEncounter) -> (Vec3, Vec3) {
+    let r2 = d.norm_2();
+    let r = r2.sqrt();
+    // the divisor and its derivative in r
+    let (divisor, slope) = match close_encounter {
+        CloseEncounter::Regularized => (r2, 2.0 * r),
+        CloseEncounter::Soften(s) => (r2 + s * s, 2.0 * r),
+        CloseEncounter::Truncated(s) if r2 > s * s => (r2, 2.0 * r),
+        CloseEncounter::Truncated(s) => (s * s, 0.0),
+    };
+    let g = m / (divisor * r);
+    let g_dot = -m * (slope * r + divisor) / (divisor * divisor * r2) * dot(d, dv) / r;
+    (g * d, g * dv + g_dot * d)
+}
+
+// the level of the longest power of two step not above `dt`
+fn level(delta_t: f64, dt: f64) -> u32 {
+    let level = (delta_t / dt).log2().ceil();
+    // without a jerk the step is as long as it gets
+    if level.is_nan() {
+        0
+    } else {
+        level.clamp(0.0, MAX_LEVEL as f64) as u32
+    }
+}
+
+// Hierarchical block timesteps over the bodies and the test particles. The
+// levels are kept from one interval to the next while the bodies stay the
+// same, the accelerations and jerks are computed again at its start. Only
+// the direct sum is used, with the jerk of the external field and of the
+// non-gravitational forces left out.
+pub struct Blocks {
+    block: Block,
+    levels: Vec<u32>,
+}
+
+impl Blocks {
+    pub fn new(block: Block) -> Self {
+        Blocks {
+            block,
+            levels: Vec::new(),
+        }
+    }
+    fn dt(&self, ticks: u64) -> f64 {
+        self.block.delta_t * ticks as f64 / END as f64
+    }
+    // acceleration and jerk of the particle `i` at (r, v) among the predicted
+    // bodies
+    fn acc(
+        &self,
+        i: usize,
+        w: Vec6,
+        bodies: &[Body],
+        tests: &[Body],
+        forces: &Forces,
+    ) -> (Vec3, Vec3) {
+        let (mut a, mut jerk) = (Vec3::zero(), Vec3::zero());
+        for (j, body) in bodies.iter().enumerate() {
+            if j != i {
+                let (da, djerk) = pull(body.m, body.r - w.r, body.v - w.v, forces.close_encounter);
+                a += da;
+                jerk += djerk;
+            }
+        }
+        if !forces.external.is_empty() {
+            a += forces.external.acc(w.r);
+        }
+        if let Some(nongravity) = forces.nongravity {
+            let id = match i.checked_sub(bodies.len()) {
+                None => nongravity.id(i),
+                Some(k) => tests[k].id,
+            };
+            a += nongravity.acc(id, w, bodies);
+        }
+        (a, jerk)
+    }
+    // the next level of a particle that reached `tick` with the step `dt`
+    fn next_level(&self, particle: &Particle, dt: f64, a0: Vec3, jerk0: Vec3) -> u32 {
+        let (a1, jerk1) = (particle.a, particle.jerk);
+        let wanted = match self.block.scheme {
+            Scheme::Hermite => {
+                // second and third derivatives from the two ends of the step
+                let snap0 = (-6.0 * (a0 - a1) - dt * (4.0 * jerk0 + 2.0 * jerk1)) / (dt * dt);
+                let crackle = (12.0 * (a0 - a1) + 6.0 * dt * (jerk0 + jerk1)) / (dt * dt * dt);
+                let snap1 = snap0 + dt * crackle;
+                let (a, j, s, c) = (a1.norm(), jerk1.norm(), snap1.norm(), crackle.norm());
+                (self.block.eta * (a * s + j * j) / (j * c + s * s)).sqrt()
+            }
+            Scheme::LeapFrog => self.block.eta * a1.norm() / jerk1.norm(),
+        };
+        let wanted = level(self.block.delta_t, wanted);
+        if wanted >= particle.level {
+            wanted
+        } else if particle.level > 0 && particle.tick.is_multiple_of(2 * particle.step()) {
+            // longer by a factor of two at most, on a multiple of it
+            particle.level - 1
+        } else {
+            particle.level
+        }
+    }
+    // Moves the bodies and the test particles from `t` to `t + delta_t`.
+    pub fn advance(&mut self, t: f64, bodies: &mut [Body], tests: &mut [Body], forces: Forces) {
+        let n = bodies.len();
+        forces.external.set_time(t, forces.frame.offset(t));
+        let now: Vec<Body> = bodies.iter().chain(tests.iter()).cloned().collect();
+        let mut particles: Vec<Particle> = (0..now.len())
+            .map(|i| {
+                let w = now[i].to_vec6();
+                let (a, jerk) = self.acc(i, w, &now[..n], tests, &forces);
+                Particle {
+                    r: w.r,
+                    v: w.v,
+                    a,
+                    jerk,
+                    tick: 0,
+                    level: 0,
+                }
+            })
+            .collect();
+        if self.levels.len() == particles.len() {
+            for (particle, level) in particles.iter_mut().zip(self.levels.iter()) {
+                particle.level = *level;
+            }
+        } else {
+            for particle in particles.iter_mut() {
+                let dt = self.block.eta * particle.a.norm() / particle.jerk.norm();
+                particle.level = level(self.block.delta_t, dt);
+            }
+        }
+        let mut predicted = now;
+        // every step divides the interval, so the last one ends on it
+        while let Some(next) = particles
+            .iter()
+            .map(|p| p.tick + p.step())
+            .min()
+            .filter(|next| *next <= END)
+        {
+            let t_next = t + self.dt(next);
+            forces
+                .external
+                .set_time(t_next, forces.frame.offset(t_next));
+            for (body, particle) in predicted.iter_mut().zip(particles.iter()).take(n) {
+                (body.r, body.v) = particle.predict(self.dt(next - particle.tick));
+            }
+            let active: Vec<usize> = (0..particles.len())
+                .filter(|&i| particles[i].tick + particles[i].step() == next)
+                .collect();
+            let correct = |&i: &usize| {
+                let old = particles[i];
+                let dt = self.dt(old.step());
+                let (r_p, v_p) = old.predict(dt);
+                let mut new = old;
+                match self.block.scheme {
+                    Scheme::Hermite => {
+                        let (a1, jerk1) =
+                            self.acc(i, Vec6::new(r_p, v_p), &predicted[..n], tests, &forces);
+                        new.v = old.v
+                            + (dt / 2.0) * (old.a + a1)
+                            + (dt * dt / 12.0) * (old.jerk - jerk1);
+                        new.r =
+                            old.r + (dt / 2.0) * (old.v + new.v) + (dt * dt / 12.0) * (old.a - a1);
+                        (new.a, new.jerk) = (a1, jerk1);
+                    }
+                    Scheme::LeapFrog => {
+                        let v_half = old.v + (dt / 2.0) * old.a;
+                        new.r = old.r + dt * v_half;
+                        let (a1, jerk1) =
+                            self.acc(i, Vec6::new(new.r, v_p), &predicted[..n], tests, &forces);
+                        new.v = v_half + (dt / 2.0) * a1;
+                        (new.a, new.jerk) = (a1, jerk1);
+                    }
+                }
+                new.tick = next;
+                new.level = self.next_level(&new, dt, old.a, old.jerk);
+                new
+            };
+            let corrected: Vec<Particle> = match forces.pool {
+                Some(pool) => pool.install(|| active.par_iter().map(correct).collect()),
+                None => active.iter().map(correct).collect(),
+            };
+            for (i, particle) in active.into_iter().zip(corrected) {
+                particles[i] = particle;
+            }
+        }
+        self.levels = particles.iter().map(|p| p.level).collect();
+        for (body, particle) in bodies.iter_mut().chain(tests.iter_mut()).zip(particles) {
+            body.r = particle.r;
+            body.v = particle.v;
+            body.a = particle.a;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::cm::Barycentre;
+
+    fn advance(block: Block, bodies: &mut [Body]) -> Vec<u32> {
+        let mut external = External::new(Vec::new(), Barycentre::from_bodies(bodies).unwrap());
+        let frame = Frame::new((0..bodies.len()).collect());
+        let mut blocks = Blocks::new(block);
+        let forces = Forces {
+            close_encounter: &CloseEncounter::Regularized,
+            external: &mut external,
+            frame: &frame,
+            nongravity: None,
+            pool: None,
+        };
+        blocks.advance(0.0, bodies, &mut [], forces);
+        blocks.levels
+    }
+
+    #[test]
+    fn jerk_is_the_rate_of_the_pull() {
+        let (d, dv) = (Vec3::new(0.3, -0.2, 0.5), Vec3::new(0.1, 0.4, -0.3));
+        let h = 1e-6;
+        for close_encounter in [
+            CloseEncounter::Regularized,
+            CloseEncounter::Soften(0.2),
+            CloseEncounter::Truncated(0.1),
+        ] {
+            let (_, jerk) = pull(2.0, d, dv, &close_encounter);
+            let (before, _) = pull(2.0, d - h * dv, dv, &close_encounter);
+            let (after, _) = pull(2.0, d + h * dv, dv, &close_encounter);
+            let rate = (after - before) / (2.0 * h);
+            assert!(
+                (jerk - rate).norm() < 1e-6 * jerk.norm(),
+                "{:?}",
+                close_encounter
+            );
+        }
+    }
+
+    #[test]
+    fn wide_bodies_take_longer_steps() {
+        // a tight binary and a body far from it
+        let mut bodies = vec![
+            Body::new(
+                0,
+                0.5,
+                Vec3::new(-0.005, 0.0, 0.0),
+                Vec3::new(0.0, -5.0, 0.0),
+                None,
+            ),
+            Body::new(
+                1,
+                0.5,
+                Vec3::new(0.005, 0.0, 0.0),
+                Vec3::new(0.0, 5.0, 0.0),
+                None,
+            ),
+            Body::new(
+                2,
+                0.01,
+                Vec3::new(10.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.3, 0.0),
+                None,
+            ),
+        ];
+        let block = Block {
+            delta_t: 0.1,
+            eta: 0.02,
+            scheme: Scheme::Hermite,
+        };
+        let levels = advance(block, &mut bodies);
+        assert!(
+            levels[0] > levels[2] + 4 && levels[1] > levels[2] + 4,
+            "{:?}",
+            levels
+        );
+        // a circular binary stays on its circle
+        let separation = (bodies[1].r - bodies[0].r).norm();
+        assert!((separation - 0.01).abs() < 1e-6, "{}", separation);
+    }
+}
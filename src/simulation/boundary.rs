@@ -0,0 +1,181 @@
+use crate::simulation::cm::Barycentre;
+use bima_rs::body::Body;
+use bima_rs::vec3::{Vec3, ZERO_VEC3};
+
+#[derive(Clone, Debug)]
+pub enum Boundary {
+    // farther than the radius from the centre of mass
+    Radius(f64),
+    // farther than the radius, moving outward and with positive energy
+    Unbound(f64),
+}
+
+pub const COLUMNS: [&str; 8] = ["id", "t", "x", "y", "z", "vx", "vy", "vz"];
+
+#[derive(Clone, Debug)]
+pub struct Escape {
+    pub id: usize,
+    pub t: f64,
+    pub r: Vec3,
+    pub v: Vec3,
+}
+
+impl Escape {
+    pub fn to_vec(&self, cm: &Barycentre) -> Vec<f64> {
+        let (r, v) = cm.absolute(self.t, self.r, self.v);
+        vec![
+            self.id as f64,
+            self.t,
+            r.x(),
+            r.y(),
+            r.z(),
+            v.x(),
+            v.y(),
+            v.z(),
+        ]
+    }
+}
+
+fn centre_of_mass(bodies: &[Body]) -> (Vec3, Vec3) {
+    let m_total = bodies.iter().fold(0., |acc, b| acc + b.m);
+    if m_total == 0.0 {
+        return (ZERO_VEC3, ZERO_VEC3);
+    }
+    let r = bodies.iter().fold(ZERO_VEC3, |acc, b| b.m * b.r + acc) / m_total;
+    let v = bodies.iter().fold(ZERO_VEC3, |acc, b| b.m * b.v + acc) / m_total;
+    (r, v)
+}
+
+// specific energy of a body relative to the centre of mass, `skip` is its
+// index if it is one of the bodies
+fn specific_energy(body: &Body, skip: Option<usize>, bodies: &[Body], v_cm: Vec3) -> f64 {
+    let kinetic = 0.5 * (body.v - v_cm).norm_2();
+    let potential = bodies
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| Some(*j) != skip)
+        .fold(0., |acc, (_, other)| {
+            acc + other.m / (body.r - other.r).norm()
+        });
+    kinetic - potential
+}
+
+impl Boundary {
+    fn crossed(&self, body: &Body, skip: Option<usize>, bodies: &[Body], cm: (Vec3, Vec3)) -> bool {
+        let (r_cm, v_cm) = cm;
+        let r = body.r - r_cm;
+        match self {
+            Boundary::Radius(radius) => r.norm() > *radius,
+            Boundary::Unbound(radius) => {
+                let v = body.v - v_cm;
+                let outward = r.x() * v.x() + r.y() * v.y() + r.z() * v.z() > 0.0;
+                r.norm() > *radius && outward && specific_energy(body, skip, bodies, v_cm) > 0.0
+            }
+        }
+    }
+    /// Indices of the bodies and of the test particles that crossed the
+    /// boundary, in ascending order.
+    pub fn escaped(&self, bodies: &[Body], tests: &[Body]) -> (Vec<usize>, Vec<usize>) {
+        let cm = centre_of_mass(bodies);
+        let bodies_out = bodies
+            .iter()
+            .enumerate()
+            .filter(|(i, body)| self.crossed(body, Some(*i), bodies, cm))
+            .map(|(i, _)| i)
+            .collect();
+        let tests_out = tests
+            .iter()
+            .enumerate()
+            .filter(|(_, test)| self.crossed(test, None, bodies, cm))
+            .map(|(i, _)| i)
+            .collect();
+        (bodies_out, tests_out)
+    }
+}
+
+// Keep track of which object each body in the system belongs to, and of the
+// shift applied every time the remaining bodies are moved back to their own
+// centre of mass, so the output stays in the original frame. The test
+// particles already carry the id of their object.
+pub struct Frame {
+    ids: Vec<usize>,
+    r: Vec3,
+    v: Vec3,
+}
+
+impl Frame {
+    pub fn new(ids: Vec<usize>) -> Self {
+        Frame {
+            ids,
+            r: ZERO_VEC3,
+            v: ZERO_VEC3,
+        }
+    }
+    pub fn ids(&self) -> &[usize] {
+        &self.ids
+    }
+    // position of the frame the system is integrated in
+    pub fn offset(&self, t: f64) -> Vec3 {
+        self.r + self.v * t
+    }
+    pub fn output(&self, bodies: &[Body], tests: &[Body], t: f64) -> Vec<Body> {
+        let bodies = bodies.iter().zip(self.ids.iter()).map(|(body, id)| {
+            let mut body = body.clone();
+            body.id = *id;
+            body
+        });
+        bodies
+            .chain(tests.iter().cloned())
+            .map(|mut body| {
+                body.r += self.r + self.v * t;
+                body.v += self.v;
+                body
+            })
+            .collect()
+    }
+    fn escape(&self, id: usize, body: &Body, t: f64) -> Escape {
+        Escape {
+            id,
+            t,
+            r: body.r + self.r + self.v * t,
+            v: body.v + self.v,
+        }
+    }
+    pub fn remove(
+        &mut self,
+        bodies: &mut Vec<Body>,
+        indices: &[usize],
+        tests: &mut Vec<Body>,
+        test_indices: &[usize],
+        t: f64,
+    ) -> Vec<Escape> {
+        let mut escapes = Vec::with_capacity(indices.len() + test_indices.len());
+        for &i in indices.iter().rev() {
+            let body = bodies.remove(i);
+            let id = self.ids.remove(i);
+            escapes.push(self.escape(id, &body, t));
+        }
+        for &i in test_indices.iter().rev() {
+            let test = tests.remove(i);
+            escapes.push(self.escape(test.id, &test, t));
+        }
+        escapes.sort_by_key(|e| e.id);
+        if indices.is_empty() {
+            return escapes;
+        }
+        let (r_cm, v_cm) = centre_of_mass(bodies);
+        for (i, body) in bodies.iter_mut().enumerate() {
+            // the force calculation identifies bodies by their index
+            body.id = i;
+            body.r -= r_cm;
+            body.v -= v_cm;
+        }
+        for test in tests.iter_mut() {
+            test.r -= r_cm;
+            test.v -= v_cm;
+        }
+        self.r += r_cm - v_cm * t;
+        self.v += v_cm;
+        escapes
+    }
+}
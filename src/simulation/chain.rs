@@ -0,0 +1,371 @@
+use bima_rs::vec3::Vec3;
+use std::fmt;
+
+// substeps of the leapfrog extrapolated to zero
+const SEQUENCE: [usize; 8] = [2, 4, 6, 8, 10, 12, 14, 16];
+// relative error of the extrapolation
+const TOLERANCE: f64 = 1e-12;
+// steps of the search for the last step ending on the interval
+const MAX_ITER: usize = 60;
+// halvings of the step in a row before the extrapolation is given up
+const MAX_HALVINGS: usize = 50;
+
+// The extrapolation did not converge however short the step, as at a
+// collision within the chain.
+#[derive(Debug)]
+pub struct ChainErr;
+
+impl fmt::Display for ChainErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The chain regularisation did not converge")
+    }
+}
+
+// The separations along the chain, their rates and the time.
+#[derive(Clone)]
+struct State {
+    t: f64,
+    x: Vec<Vec3>,
+    w: Vec<Vec3>,
+}
+
+impl State {
+    fn flatten(&self) -> Vec<f64> {
+        let mut y = vec![self.t];
+        for a in self.x.iter().chain(self.w.iter()) {
+            y.extend([a.x(), a.y(), a.z()]);
+        }
+        y
+    }
+    fn unflatten(y: &[f64]) -> Self {
+        let n = (y.len() - 1) / 6;
+        let vec = |k: usize| Vec3::new(y[1 + 3 * k], y[2 + 3 * k], y[3 + 3 * k]);
+        State {
+            t: y[0],
+            x: (0..n).map(vec).collect(),
+            w: (n..2 * n).map(vec).collect(),
+        }
+    }
+}
+
+// Bodies of one group in the order of the chain, each link joining the two
+// closest of what is left at either end.
+struct Chain {
+    m: Vec<f64>,
+    // the body at each place of the chain
+    order: Vec<usize>,
+    // minus the energy
+    b: f64,
+}
+
+impl Chain {
+    // about the centre of mass, from the separations
+    fn unchain(&self, x: &[Vec3]) -> Vec<Vec3> {
+        let mut q = vec![Vec3::zero(); self.m.len()];
+        for k in 0..x.len() {
+            q[k + 1] = q[k] + x[k];
+        }
+        let total: f64 = self.m.iter().sum();
+        let cm = q
+            .iter()
+            .zip(self.m.iter())
+            .fold(Vec3::zero(), |cm, (q, m)| cm + *m * *q)
+            / total;
+        q.into_iter().map(|q| q - cm).collect()
+    }
+    // accelerations along the chain and the potential, the separations of
+    // near places summed from the links to keep their digits
+    fn acc(&self, x: &[Vec3]) -> (Vec<Vec3>, f64) {
+        let n = self.m.len();
+        let q = self.unchain(x);
+        let mut a = vec![Vec3::zero(); n];
+        let mut u = 0.0;
+        for i in 0..n {
+            for j in i + 1..n {
+                let d = match j - i {
+                    1 => x[i],
+                    2 => x[i] + x[i + 1],
+                    _ => q[j] - q[i],
+                };
+                let r = d.norm();
+                let f = d / (r * r * r);
+                a[i] += self.m[j] * f;
+                a[j] -= self.m[i] * f;
+                u += self.m[i] * self.m[j] / r;
+            }
+        }
+        (a, u)
+    }
+    fn kinetic(&self, w: &[Vec3]) -> f64 {
+        let v = self.unchain(w);
+        0.5 * v
+            .iter()
+            .zip(self.m.iter())
+            .map(|(v, m)| m * v.norm_2())
+            .sum::<f64>()
+    }
+    fn drift(&self, state: &mut State, h: f64) {
+        let dt = h / (self.kinetic(&state.w) + self.b);
+        for (x, w) in state.x.iter_mut().zip(state.w.iter()) {
+            *x += dt * *w;
+        }
+        state.t += dt;
+    }
+    fn kick(&self, state: &mut State, h: f64) {
+        let (a, u) = self.acc(&state.x);
+        let dt = h / u;
+        for (k, w) in state.w.iter_mut().enumerate() {
+            *w += dt * (a[k + 1] - a[k]);
+        }
+    }
+    // leapfrog in the fictitious time of the logarithmic Hamiltonian, exact
+    // on the orbit of two bodies up to the time along it
+    fn leapfrog(&self, state: &State, step: f64, n: usize) -> State {
+        let h = step / n as f64;
+        let mut state = state.clone();
+        self.drift(&mut state, 0.5 * h);
+        for i in 0..n {
+            self.kick(&mut state, h);
+            self.drift(&mut state, if i + 1 == n { 0.5 * h } else { h });
+        }
+        state
+    }
+    // Bulirsch-Stoer over the leapfrog, with the number of substeps it took,
+    // None if it did not converge
+    fn extrapolate(&self, state: &State, step: f64) -> Option<(State, usize)> {
+        // table[k][j], extrapolated j times from k + 1 leapfrogs
+        let mut table: Vec<Vec<Vec<f64>>> = Vec::with_capacity(SEQUENCE.len());
+        for k in 0..SEQUENCE.len() {
+            let mut row = vec![self.leapfrog(state, step, SEQUENCE[k]).flatten()];
+            for j in 1..=k {
+                let ratio = (SEQUENCE[k] as f64 / SEQUENCE[k - j] as f64).powi(2);
+                let next = row[j - 1]
+                    .iter()
+                    .zip(table[k - 1][j - 1].iter())
+                    .map(|(y, e)| y + (y - e) / (ratio - 1.0))
+                    .collect();
+                row.push(next);
+            }
+            if k >= 2 && self.error(state, &row[k], &row[k - 1]) < TOLERANCE {
+                return Some((State::unflatten(&row[k]), k));
+            }
+            table.push(row);
+        }
+        None
+    }
+    // largest difference for the time, the separations and their rates, each
+    // against its own scale
+    fn error(&self, state: &State, a: &[f64], b: &[f64]) -> f64 {
+        let n = state.x.len();
+        let scale = |range: std::ops::Range<usize>| {
+            range
+                .clone()
+                .map(|i| a[i].abs())
+                .fold(0.0, f64::max)
+                .max(f64::MIN_POSITIVE)
+        };
+        let (x, w) = (1..1 + 3 * n, 1 + 3 * n..1 + 6 * n);
+        let dt = (a[0] - state.t).abs().max(f64::MIN_POSITIVE);
+        let diff = |range: std::ops::Range<usize>, scale: f64| {
+            range.map(|i| (a[i] - b[i]).abs()).fold(0.0, f64::max) / scale
+        };
+        ((a[0] - b[0]).abs() / dt)
+            .max(diff(x.clone(), scale(x)))
+            .max(diff(w.clone(), scale(w)))
+    }
+}
+
+// Moves the bodies of masses `m` at `r`, `v` about their centre of mass by
+// `dt`, with the algorithmic chain regularisation of Mikkola and Aarseth:
+// the leapfrog of the logarithmic Hamiltonian on the separations along the
+// chain, extrapolated to zero step.
+pub fn advance(m: &[f64], r: &mut [Vec3], v: &mut [Vec3], dt: f64) -> Result<(), ChainErr> {
+    let n = m.len();
+    // the closest pair first, then the closest body to either end
+    let mut closest = (0, 1, f64::INFINITY);
+    for i in 0..n {
+        for j in i + 1..n {
+            let d = (r[j] - r[i]).norm();
+            if d < closest.2 {
+                closest = (i, j, d);
+            }
+        }
+    }
+    let mut order = vec![closest.0, closest.1];
+    while order.len() < n {
+        let (first, last) = (order[0], order[order.len() - 1]);
+        let (k, front, _) = (0..n)
+            .filter(|k| !order.contains(k))
+            .flat_map(|k| {
+                [
+                    (k, true, (r[k] - r[first]).norm()),
+                    (k, false, (r[k] - r[last]).norm()),
+                ]
+            })
+            .fold((0, false, f64::INFINITY), |best, c| {
+                if c.2 < best.2 { c } else { best }
+            });
+        if front {
+            order.insert(0, k);
+        } else {
+            order.push(k);
+        }
+    }
+    let links = |a: &[Vec3]| -> Vec<Vec3> {
+        order
+            .windows(2)
+            .map(|pair| a[pair[1]] - a[pair[0]])
+            .collect()
+    };
+    let mut state = State {
+        t: 0.0,
+        x: links(r),
+        w: links(v),
+    };
+    let mut chain = Chain {
+        m: order.iter().map(|&i| m[i]).collect(),
+        order,
+        b: 0.0,
+    };
+    let (_, u) = chain.acc(&state.x);
+    chain.b = u - chain.kinetic(&state.w);
+    // ds = U dt, a tenth of the shortest time scale of the links
+    let shortest = state
+        .x
+        .iter()
+        .enumerate()
+        .map(|(k, x)| (x.norm().powi(3) / (chain.m[k] + chain.m[k + 1])).sqrt())
+        .fold(f64::INFINITY, f64::min);
+    let mut step = 0.1 * u * shortest.min(dt);
+    let end = |t: f64| t >= dt * (1.0 - 1e-14);
+    let mut halvings = 0;
+    while !end(state.t) {
+        let Some((next, k)) = chain.extrapolate(&state, step) else {
+            step *= 0.5;
+            halvings += 1;
+            if halvings > MAX_HALVINGS {
+                return Err(ChainErr);
+            }
+            continue;
+        };
+        if next.t <= dt {
+            halvings = 0;
+            state = next;
+            step *= match k {
+                0..=3 => 1.5,
+                4..=5 => 1.0,
+                _ => 0.6,
+            };
+            continue;
+        }
+        // The step that ends on dt, between none and this one. Without it
+        // the furthest state short of dt is taken, or a shorter step, and
+        // the steps go on from there.
+        let (mut lo, mut hi) = ((0.0, state.t - dt), (step, next.t - dt));
+        let (mut landed, mut short) = (None, None);
+        for _ in 0..MAX_ITER {
+            let guess = lo.0 - lo.1 * (hi.0 - lo.0) / (hi.1 - lo.1);
+            let Some((tried, _)) = chain.extrapolate(&state, guess) else {
+                break;
+            };
+            let f = tried.t - dt;
+            if f.abs() <= 1e-14 * dt {
+                landed = Some(tried);
+                break;
+            }
+            // Illinois, halving the end that stays
+            if f < 0.0 {
+                lo = (guess, f);
+                hi.1 *= 0.5;
+                short = Some(tried);
+            } else {
+                hi = (guess, f);
+                lo.1 *= 0.5;
+            }
+        }
+        match (landed, short) {
+            (Some(landed), _) => {
+                state = landed;
+                break;
+            }
+            (None, short) => {
+                match short {
+                    Some(short) => (state, step) = (short, lo.0),
+                    None => step *= 0.5,
+                }
+                halvings += 1;
+                if halvings > MAX_HALVINGS {
+                    return Err(ChainErr);
+                }
+            }
+        }
+    }
+    let q = chain.unchain(&state.x);
+    let p = chain.unchain(&state.w);
+    for (k, &i) in chain.order.iter().enumerate() {
+        r[i] = q[k];
+        v[i] = p[k];
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn energy(m: &[f64], r: &[Vec3], v: &[Vec3]) -> f64 {
+        let mut e = 0.0;
+        for i in 0..m.len() {
+            e += 0.5 * m[i] * v[i].norm_2();
+            for j in i + 1..m.len() {
+                e -= m[i] * m[j] / (r[j] - r[i]).norm();
+            }
+        }
+        e
+    }
+
+    #[test]
+    fn agrees_with_kepler_on_two_bodies() {
+        let m = [0.3, 0.7];
+        let (r, v) = (Vec3::new(0.8, 0.1, -0.2), Vec3::new(0.1, 0.9, 0.3));
+        let mut rs = [-0.7 * r, 0.3 * r];
+        let mut vs = [-0.7 * v, 0.3 * v];
+        advance(&m, &mut rs, &mut vs, 3.0).unwrap();
+        let (r1, v1) = crate::simulation::ks::kepler(1.0, r, v, 3.0);
+        assert!(
+            (rs[1] - rs[0] - r1).norm() < 1e-9,
+            "{:?}",
+            (rs[1] - rs[0]).to_tuple()
+        );
+        assert!((vs[1] - vs[0] - v1).norm() < 1e-9);
+    }
+
+    #[test]
+    fn keeps_the_energy_of_the_pythagorean_problem() {
+        // masses 3, 4 and 5 at rest on the corners of a 3-4-5 triangle,
+        // about their centre of mass
+        let m = [3.0, 4.0, 5.0];
+        let mut r = [
+            Vec3::new(1.0, 3.0, 0.0),
+            Vec3::new(-2.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+        ];
+        let mut v = [Vec3::zero(); 3];
+        let before = energy(&m, &r, &v);
+        for _ in 0..10 {
+            advance(&m, &mut r, &mut v, 1.0).unwrap();
+        }
+        let error = ((energy(&m, &r, &v) - before) / before).abs();
+        assert!(error < 1e-9, "error={}", error);
+        let cm = (0..3).fold(Vec3::zero(), |cm, i| cm + m[i] * r[i]);
+        assert!(cm.norm() < 1e-9);
+    }
+
+    #[test]
+    fn gives_up_on_a_collision() {
+        let m = [1.0, 1.0, 1.0];
+        let mut r = [Vec3::zero(), Vec3::zero(), Vec3::new(1.0, 0.0, 0.0)];
+        let mut v = [Vec3::zero(); 3];
+        assert!(advance(&m, &mut r, &mut v, 1.0).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use bima_rs::body::Body;
+use bima_rs::record::Record;
+use bima_rs::record::line::Line;
+use bima_rs::vec3::Vec3;
+
+// Centre of mass of the initial bodies, moving uniformly from the time `t`
+// they are at. The bodies are integrated relative to it, both in position and
+// velocity.
+#[derive(Clone, Copy, Debug)]
+pub struct Barycentre {
+    pub r: Vec3,
+    pub v: Vec3,
+    pub t: f64,
+}
+
+impl Barycentre {
+    pub fn from_bodies(bodies: &[Body]) -> Option<Self> {
+        let total: f64 = bodies.iter().map(|b| b.m).sum();
+        if total == 0.0 {
+            return None;
+        }
+        let (r, v) = bodies
+            .iter()
+            .fold((Vec3::zero(), Vec3::zero()), |(r, v), b| {
+                (r + b.m * b.r, v + b.m * b.v)
+            });
+        Some(Barycentre {
+            r: r / total,
+            v: v / total,
+            t: 0.0,
+        })
+    }
+    // the same centre of mass, with the bodies at the time `t`
+    pub fn at(self, t: f64) -> Self {
+        Barycentre { t, ..self }
+    }
+    pub fn position(&self, t: f64) -> Vec3 {
+        self.r + (t - self.t) * self.v
+    }
+    // position and velocity in the original frame
+    pub fn absolute(&self, t: f64, r: Vec3, v: Vec3) -> (Vec3, Vec3) {
+        (r + self.position(t), v + self.v)
+    }
+    pub fn line(&self, line: &Line) -> Line {
+        let (r, v) = self.absolute(line.t, line.r, line.v);
+        Line::new(line.t, r, v, line.a)
+    }
+    // Same layout as `Record::to_vec`, with the velocities restored as well.
+    pub fn to_vec(self, record: Record) -> Vec<Vec<Vec<f64>>> {
+        record
+            .objects
+            .into_iter()
+            .map(|trajectory| {
+                trajectory
+                    .path
+                    .iter()
+                    .map(|line| {
+                        let line = self.line(line);
+                        let mut row = vec![
+                            line.t,
+                            line.r.x(),
+                            line.r.y(),
+                            line.r.z(),
+                            line.v.x(),
+                            line.v.y(),
+                            line.v.z(),
+                        ];
+                        if let Some(a) = line.a {
+                            row.extend([a.x(), a.y(), a.z()]);
+                        }
+                        row
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,1025 @@
+use crate::simulation::Simulation;
+use crate::simulation::boundary;
+use crate::simulation::cm::Barycentre;
+use crate::simulation::create_system;
+use crate::simulation::event::{self, Detector};
+use crate::simulation::ewald;
+use crate::simulation::external::External;
+use crate::simulation::integrate::{IntegrateErr, integrate};
+use crate::simulation::regular;
+use crate::simulation::store::{self, Store, StoreErr};
+use crate::simulation::utils::{self, EventArg, NonGravityArg, PotentialArg};
+use bima_rs::record::Record;
+use bima_rs::record::line::Line;
+use bima_rs::record::utils::some_acc;
+use bima_rs::vec3::Vec3;
+#[cfg(feature = "python")]
+use pyo3::{PyErr, exceptions::PyValueError};
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub enum RootPathErr {
+    AlreadyExistAsFile,
+    FailedToCreate,
+}
+
+impl fmt::Display for RootPathErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RootPathErr::AlreadyExistAsFile => write!(f, "The given path is a file"),
+            RootPathErr::FailedToCreate => {
+                write!(f, "Something went wrong, failed to create the dir")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DriverErr {
+    // a method or parameter that does not exist
+    Input(String),
+    Path(RootPathErr),
+    Io(io::Error),
+    Store(StoreErr),
+    Hdf5Err(hdf5::Error),
+    Progress(String),
+    Integrate(IntegrateErr),
+}
+
+impl DriverErr {
+    pub fn input(e: impl fmt::Display) -> Self {
+        DriverErr::Input(e.to_string())
+    }
+}
+
+impl fmt::Display for DriverErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverErr::Input(e) => write!(f, "{}", e),
+            DriverErr::Path(e) => write!(f, "{}", e),
+            DriverErr::Io(e) => write!(f, "{}", e),
+            DriverErr::Store(e) => write!(f, "{}", e),
+            DriverErr::Hdf5Err(e) => write!(f, "{}", e),
+            DriverErr::Progress(e) => write!(f, "Failed to report the progress: {}", e),
+            DriverErr::Integrate(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<DriverErr> for PyErr {
+    fn from(value: DriverErr) -> Self {
+        PyValueError::new_err(value.to_string())
+    }
+}
+
+impl From<RootPathErr> for DriverErr {
+    fn from(e: RootPathErr) -> Self {
+        DriverErr::Path(e)
+    }
+}
+
+impl From<io::Error> for DriverErr {
+    fn from(e: io::Error) -> Self {
+        DriverErr::Io(e)
+    }
+}
+
+impl From<StoreErr> for DriverErr {
+    fn from(e: StoreErr) -> Self {
+        DriverErr::Store(e)
+    }
+}
+
+impl From<hdf5::Error> for DriverErr {
+    fn from(e: hdf5::Error) -> Self {
+        DriverErr::Hdf5Err(e)
+    }
+}
+
+// The methods and parameters of a run, with the same codes as the arguments
+// of `Simulation.run_disk`.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub force_method: u8,
+    // expansion order of the fast multipole method
+    pub force_par: Option<f64>,
+    // side of the periodic box of the Ewald sum
+    pub box_size: Option<f64>,
+    // keep the positions continuous instead of wrapping them into the box
+    pub unwrap: bool,
+    pub integrator: u8,
+    pub timestep_method: u8,
+    // accuracy parameter of block timesteps
+    pub timestep_par: Option<f64>,
+    pub close_encounter: u8,
+    // time the bodies are at, the output times continue from it
+    pub t_start: f64,
+    pub t_stop: f64,
+    pub delta_t: Option<f64>,
+    pub ce_par: Option<f64>,
+    pub save_acc: bool,
+    pub boundary: Option<u8>,
+    pub boundary_par: Option<f64>,
+    pub events: Vec<EventArg>,
+    pub external: Vec<PotentialArg>,
+    pub nongravity: Option<NonGravityArg>,
+    // threads for the forces, 0 for one per core
+    pub threads: usize,
+}
+
+// Called at most every 100 ms while integrating, and once more at the end
+// with a percentage of 1.
+pub trait Progress {
+    fn update(&mut self, iteration: usize, percentage: f64) -> Result<(), DriverErr>;
+}
+
+fn gen_dir_path(abs_path: &str) -> Result<PathBuf, RootPathErr> {
+    let dir_path = PathBuf::from(abs_path);
+    match fs::create_dir_all(&dir_path) {
+        Ok(_) => return Ok(dir_path),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(_) => return Err(RootPathErr::FailedToCreate),
+    }
+    let metadata = dir_path
+        .metadata()
+        .expect("Already check above, so must exist");
+    if metadata.is_file() {
+        return Err(RootPathErr::AlreadyExistAsFile);
+    }
+    Ok(dir_path)
+}
+
+// Where the lines of every object go, relative to the barycentre and in the
+// order they were integrated. Called every `BUFFER` steps and at the end.
+pub trait Sink {
+    fn append(&mut self, obj_id: usize, lines: Vec<Line>, cm: &Barycentre)
+    -> Result<(), DriverErr>;
+}
+
+// Kept in memory, the barycentre is added back by `Barycentre::to_vec`.
+impl Sink for Record {
+    fn append(&mut self, obj_id: usize, lines: Vec<Line>, _: &Barycentre) -> Result<(), DriverErr> {
+        self.add_many(obj_id, lines);
+        Ok(())
+    }
+}
+
+impl Sink for Store {
+    fn append(
+        &mut self,
+        obj_id: usize,
+        lines: Vec<Line>,
+        cm: &Barycentre,
+    ) -> Result<(), DriverErr> {
+        Ok(Store::append(self, obj_id, lines, cm)?)
+    }
+}
+
+// Nothing is shown
+impl Progress for () {
+    fn update(&mut self, _: usize, _: f64) -> Result<(), DriverErr> {
+        Ok(())
+    }
+}
+
+// number of steps kept before they are handed to the sink
+const BUFFER: usize = 65536;
+
+// trajectories of every object, the escapes, the events and the
+// regularised pairs
+pub type Output = (
+    Vec<Vec<Vec<f64>>>,
+    Vec<Vec<f64>>,
+    Vec<Vec<f64>>,
+    Vec<Vec<f64>>,
+);
+// the escapes, the events and the regularised pairs, one row each
+pub type Tables = (Vec<Vec<f64>>, Vec<Vec<f64>>, Vec<Vec<f64>>);
+
+// Integrates until `t_stop` or a stopping event.
+pub fn run<S: Sink, P: Progress>(
+    simulation: &Simulation,
+    options: Options,
+    sink: &mut S,
+    progress: &mut P,
+) -> Result<Tables, DriverErr> {
+    utils::check_stop(options.t_start, options.t_stop).map_err(DriverErr::input)?;
+    let save_acc = options.save_acc;
+    let cm = simulation.cm.at(options.t_start);
+    let (system, mut solver) = create_system(&simulation.bodies, &options)?;
+    // the positions go out wrapped into the box in the original frame
+    let wrap = options.box_size.filter(|_| !options.unwrap);
+    let place = |t: f64, r: Vec3| match wrap {
+        Some(size) => {
+            let origin = cm.position(t);
+            ewald::wrap(size, r + origin) - origin
+        }
+        None => r,
+    };
+    let boundary =
+        utils::get_boundary(options.boundary, options.boundary_par).map_err(DriverErr::input)?;
+    let external = External::new(
+        utils::get_potentials(Some(options.external), simulation.units.g)
+            .map_err(DriverErr::input)?,
+        cm,
+    );
+    let nongravity = utils::get_nongravity(options.nongravity, &simulation.beta, &simulation.tau)
+        .map_err(DriverErr::input)?;
+    let masses = &simulation.bodies.iter().map(|b| b.m).collect();
+    let mut record = Record::empty(masses, save_acc);
+    let mut detector = Detector::new(
+        utils::get_events(Some(options.events), record.len()).map_err(DriverErr::input)?,
+        record.len(),
+        cm,
+    );
+    solver.pool = match options.threads {
+        1 => None,
+        threads => Some(
+            ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(DriverErr::input)?,
+        ),
+    };
+    let (rx, handle) = integrate(
+        system,
+        options.t_stop,
+        boundary,
+        external,
+        nongravity,
+        solver,
+    );
+    let mut escapes = Vec::new();
+    let mut regularised = Vec::new();
+    let mut detections = Vec::new();
+    let mut latest_time = Instant::now();
+    let mut iteration = 1;
+    let mut buffered = 0;
+    for data in rx {
+        let (t, percentage, bodies) = (data.t, data.percentage, data.bodies);
+        let now = Instant::now();
+        if now.duration_since(latest_time).as_millis() >= 100 {
+            latest_time = now;
+            progress.update(iteration, percentage)?;
+        }
+        iteration += 1;
+        escapes.extend(data.escapes);
+        regularised.extend(data.regularised);
+        let mut stopped = None;
+        if let Some(bodies) = bodies.as_ref().filter(|_| !detector.is_empty()) {
+            let (found, bodies) = detector.check(t, bodies);
+            detections.extend(found);
+            stopped = bodies;
+        }
+        if let Some(bodies) = stopped {
+            let t = detections.last().map(|d| d.t).unwrap_or(t);
+            for body in bodies.into_iter() {
+                let a = some_acc(body.a, save_acc);
+                let line = Line::new(t, place(t, body.r), body.v, a);
+                record.add(body.id, line);
+            }
+            break;
+        }
+        if let Some(bodies) = bodies {
+            for body in bodies.into_iter() {
+                let a = some_acc(body.a, save_acc);
+                let line = Line::new(t, place(t, body.r), body.v, a);
+                record.add(body.id, line);
+            }
+            buffered += 1;
+        }
+        if buffered >= BUFFER {
+            buffered = 0;
+            for obj_id in 0..record.len() {
+                sink.append(obj_id, record.take(obj_id).path, &cm)?;
+            }
+        }
+    }
+    // last one
+    progress.update(iteration, 1.0)?;
+    // the integration is cut short on purpose when an event stops the run
+    match handle.join().expect("Failed to join thread") {
+        Ok(()) | Err(IntegrateErr::Closed) => {}
+        Err(e) => return Err(DriverErr::Integrate(e)),
+    }
+    for obj_id in 0..record.len() {
+        sink.append(obj_id, record.take(obj_id).path, &cm)?;
+    }
+    let escapes = escapes.iter().map(|e| e.to_vec(&cm)).collect();
+    let detections = detections.iter().map(|d| d.to_vec(&cm)).collect();
+    let regularised = regularised.iter().map(|e| e.to_vec()).collect();
+    Ok((escapes, detections, regularised))
+}
+
+// Everything kept in memory, with the barycentre added back.
+pub fn run_memory<P: Progress>(
+    simulation: &Simulation,
+    options: Options,
+    progress: &mut P,
+) -> Result<Output, DriverErr> {
+    let masses = &simulation.bodies.iter().map(|b| b.m).collect();
+    let mut record = Record::empty(masses, options.save_acc);
+    let cm = simulation.cm.at(options.t_start);
+    let (escapes, detections, regularised) = run(simulation, options, &mut record, progress)?;
+    Ok((cm.to_vec(record), escapes, detections, regularised))
+}
+
+// Writes `res.h5` in the directory `abs_path` as it goes. Returns the path of
+// the file.
+pub fn run_disk<P: Progress>(
+    simulation: &Simulation,
+    options: Options,
+    abs_path: &str,
+    replace: bool,
+    progress: &mut P,
+) -> Result<String, DriverErr> {
+    let dir_path = gen_dir_path(abs_path)?;
+    let dir_path = fs::canonicalize(dir_path)?;
+    let file_path = dir_path.join("res.h5");
+    let mut store = Store::new(
+        file_path,
+        simulation.bodies.len(),
+        simulation
+            .bodies
+            .iter()
+            .map(|b| b.m / simulation.units.g)
+            .collect(),
+        &simulation.units,
+        replace,
+        options.save_acc,
+    )?;
+    let (escapes, detections, regularised) = run(simulation, options, &mut store, progress)?;
+    store.store_table("escapes", &boundary::COLUMNS, &escapes)?;
+    store.store_table("events", &event::COLUMNS, &detections)?;
+    store.store_table("regularised", &regular::COLUMNS, &regularised)?;
+    Ok(store.path.to_string_lossy().into())
+}
+
+// Where the progress of one run of an ensemble is kept for `run_ensemble`
+// to add up.
+// The run stops once `cancelled` is set, when the progress of the ensemble
+// failed to be reported.
+struct Share<'a> {
+    iteration: &'a AtomicUsize,
+    // bits of the f64
+    percentage: &'a AtomicU64,
+    cancelled: &'a AtomicBool,
+}
+
+impl Progress for Share<'_> {
+    fn update(&mut self, iteration: usize, percentage: f64) -> Result<(), DriverErr> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(DriverErr::Progress("cancelled".to_string()));
+        }
+        self.iteration.store(iteration, Ordering::Relaxed);
+        self.percentage
+            .store(percentage.min(1.).to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Runs every simulation with the same options, `jobs` of them at a time, 0
+// for one per core. Run `k` is written to `abs_path/k/res.h5`, or with
+// `single_file` to the group `runs/k` of `abs_path/res.h5`. `progress` is
+// given the iterations of all the runs and their mean percentage. Returns the
+// file and the group of every run, in order.
+pub fn run_ensemble<P: Progress>(
+    simulations: &[Simulation],
+    options: Options,
+    abs_path: &str,
+    replace: bool,
+    single_file: bool,
+    jobs: usize,
+    progress: &mut P,
+) -> Result<Vec<(String, String)>, DriverErr> {
+    let dir_path = fs::canonicalize(gen_dir_path(abs_path)?)?;
+    let file = if single_file {
+        let file = store::create_file(&dir_path.join("res.h5"), replace)?;
+        file.create_group("runs")?;
+        Some(file)
+    } else {
+        None
+    };
+    let n = simulations.len();
+    let iterations: Vec<AtomicUsize> = (0..n).map(|_| AtomicUsize::new(0)).collect();
+    let percentages: Vec<AtomicU64> = (0..n).map(|_| AtomicU64::new(0)).collect();
+    let cancelled = AtomicBool::new(false);
+    let member = |k: usize| -> Result<(String, String), DriverErr> {
+        let simulation = &simulations[k];
+        let m = simulation
+            .bodies
+            .iter()
+            .map(|b| b.m / simulation.units.g)
+            .collect();
+        let n_objects = simulation.bodies.len();
+        let (mut store, group) = match &file {
+            Some(file) => {
+                let group = format!("runs/{}", k);
+                let store = Store::in_group(
+                    file,
+                    dir_path.join("res.h5"),
+                    &group,
+                    n_objects,
+                    m,
+                    &simulation.units,
+                    options.save_acc,
+                )?;
+                (store, group)
+            }
+            None => {
+                let path = gen_dir_path(&dir_path.join(k.to_string()).to_string_lossy())?;
+                let store = Store::new(
+                    path.join("res.h5"),
+                    n_objects,
+                    m,
+                    &simulation.units,
+                    replace,
+                    options.save_acc,
+                )?;
+                (store, "/".to_string())
+            }
+        };
+        let mut share = Share {
+            iteration: &iterations[k],
+            percentage: &percentages[k],
+            cancelled: &cancelled,
+        };
+        let (escapes, detections, regularised) =
+            run(simulation, options.clone(), &mut store, &mut share)?;
+        store.store_table("escapes", &boundary::COLUMNS, &escapes)?;
+        store.store_table("events", &event::COLUMNS, &detections)?;
+        store.store_table("regularised", &regular::COLUMNS, &regularised)?;
+        Ok((store.path.to_string_lossy().into(), group))
+    };
+    let total = || {
+        let iteration = iterations.iter().map(|i| i.load(Ordering::Relaxed)).sum();
+        let percentage = percentages
+            .iter()
+            .map(|p| f64::from_bits(p.load(Ordering::Relaxed)))
+            .sum::<f64>()
+            / n.max(1) as f64;
+        (iteration, percentage)
+    };
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(DriverErr::input)?;
+    let done = AtomicBool::new(false);
+    // the progress stays on this thread, the runs go to the pool
+    let (results, reported) = thread::scope(|s| {
+        let handle = s.spawn(|| {
+            let results: Vec<_> = pool.install(|| (0..n).into_par_iter().map(member).collect());
+            done.store(true, Ordering::Release);
+            results
+        });
+        let mut reported = Ok(());
+        while !done.load(Ordering::Acquire) {
+            thread::sleep(Duration::from_millis(100));
+            if reported.is_ok() {
+                let (iteration, percentage) = total();
+                reported = progress.update(iteration, percentage.min(1. - f64::EPSILON));
+                cancelled.store(reported.is_err(), Ordering::Relaxed);
+            }
+        }
+        (handle.join().expect("Failed to join thread"), reported)
+    });
+    reported?;
+    progress.update(total().0, 1.0)?;
+    results.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::initial::Initial;
+    use crate::units::UnitSystem;
+    use bima_rs::vec3::Vec3;
+
+    fn binary(v_cm: Vec3) -> Simulation {
+        let initial = [
+            Initial {
+                m: 1.0,
+                r: Vec3::new(-0.5, 0.0, 0.0),
+                v: Vec3::new(0.0, -0.5, 0.0) + v_cm,
+                beta: 0.0,
+                tau: None,
+            },
+            Initial {
+                m: 1.0,
+                r: Vec3::new(0.5, 0.0, 0.0),
+                v: Vec3::new(0.0, 0.5, 0.0) + v_cm,
+                beta: 0.0,
+                tau: None,
+            },
+        ];
+        Simulation::from_initial(&initial, UnitSystem::nbody()).unwrap()
+    }
+
+    // equal masses on an orbit of e = 0.9, from the apocentre at a
+    // separation of 1
+    fn eccentric() -> Simulation {
+        let v = 0.5 * 0.1f64.sqrt();
+        let initial: Vec<Initial> = [(-0.5, -v), (0.5, v)]
+            .map(|(x, vy)| Initial {
+                m: 0.5,
+                r: Vec3::new(x, 0.0, 0.0),
+                v: Vec3::new(0.0, vy, 0.0),
+                beta: 0.0,
+                tau: None,
+            })
+            .to_vec();
+        Simulation::from_initial(&initial, UnitSystem::nbody()).unwrap()
+    }
+
+    // of `eccentric`, from a row of each body
+    fn binary_energy(a: &[f64], b: &[f64]) -> f64 {
+        let kinetic = |row: &[f64]| 0.25 * (row[4].powi(2) + row[5].powi(2) + row[6].powi(2));
+        let r = ((a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2) + (a[3] - b[3]).powi(2)).sqrt();
+        kinetic(a) + kinetic(b) - 0.25 / r
+    }
+
+    // Euler with a timestep that adds up exactly
+    fn options(t_stop: f64) -> Options {
+        Options {
+            t_stop,
+            delta_t: Some(0.25),
+            close_encounter: 2,
+            ..Default::default()
+        }
+    }
+
+    // keeps the lengths of every append
+    #[derive(Default)]
+    struct Chunks {
+        lines: Vec<Vec<Line>>,
+        appends: Vec<(usize, usize)>,
+    }
+
+    impl Sink for Chunks {
+        fn append(
+            &mut self,
+            obj_id: usize,
+            lines: Vec<Line>,
+            _: &Barycentre,
+        ) -> Result<(), DriverErr> {
+            self.appends.push((obj_id, lines.len()));
+            if self.lines.len() <= obj_id {
+                self.lines.resize(obj_id + 1, Vec::new());
+            }
+            self.lines[obj_id].extend(lines);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn records_every_step() {
+        let (objects, escapes, events, _) =
+            run_memory(&binary(Vec3::zero()), options(1.0), &mut ()).unwrap();
+        assert_eq!(objects.len(), 2);
+        assert!(escapes.is_empty() && events.is_empty());
+        for object in objects.iter() {
+            let t: Vec<f64> = object.iter().map(|row| row[0]).collect();
+            assert_eq!(t, vec![0.0, 0.25, 0.5, 0.75]);
+            assert!(object.iter().all(|row| row.len() == 7));
+        }
+    }
+
+    #[test]
+    fn saves_the_acceleration() {
+        let options = Options {
+            save_acc: true,
+            ..options(1.0)
+        };
+        let (objects, _, _, _) = run_memory(&binary(Vec3::zero()), options, &mut ()).unwrap();
+        assert!(objects.iter().flatten().all(|row| row.len() == 10));
+    }
+
+    #[test]
+    fn restores_the_moving_barycentre() {
+        let v_cm = Vec3::new(1.0, 2.0, 3.0);
+        let (objects, _, _, _) = run_memory(&binary(v_cm), options(1.0), &mut ()).unwrap();
+        let first = &objects[0][0];
+        assert_eq!(&first[1..7], &[-0.5, 0.0, 0.0, 1.0, 1.5, 3.0]);
+        // the barycentre keeps moving with the same velocity
+        for (a, b) in objects[0].iter().zip(objects[1].iter()) {
+            for (axis, v) in [1.0, 2.0, 3.0].iter().enumerate() {
+                let r_cm = 0.5 * (a[1 + axis] + b[1 + axis]);
+                assert!((r_cm - a[0] * v).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn continues_from_the_start_time() {
+        let v_cm = Vec3::new(1.0, 2.0, 3.0);
+        let continued = Options {
+            t_start: 2.0,
+            ..options(3.0)
+        };
+        let (objects, _, _, _) = run_memory(&binary(v_cm), continued, &mut ()).unwrap();
+        let (start, _, _, _) = run_memory(&binary(v_cm), options(1.0), &mut ()).unwrap();
+        for (object, start) in objects.iter().zip(start.iter()) {
+            let t: Vec<f64> = object.iter().map(|row| row[0]).collect();
+            assert_eq!(t, vec![2.0, 2.25, 2.5, 2.75]);
+            // the same orbit, shifted in time only
+            for (row, first) in object.iter().zip(start.iter()) {
+                assert_eq!(&row[1..7], &first[1..7]);
+            }
+        }
+    }
+
+    #[test]
+    fn appends_in_order_of_the_steps() {
+        let steps = BUFFER + 10;
+        let options = Options {
+            delta_t: Some(1.0),
+            ..options(steps as f64)
+        };
+        let mut chunks = Chunks::default();
+        run(&binary(Vec3::zero()), options, &mut chunks, &mut ()).unwrap();
+        assert_eq!(
+            chunks.appends,
+            vec![(0, BUFFER), (1, BUFFER), (0, 10), (1, 10)]
+        );
+        for lines in chunks.lines.iter() {
+            assert_eq!(lines.len(), steps);
+            assert!(lines.iter().enumerate().all(|(k, line)| line.t == k as f64));
+        }
+    }
+
+    #[test]
+    fn stops_at_an_event() {
+        let options = Options {
+            // the bodies of the Euler binary drift apart
+            events: vec![(2, 0, 1, 1.1, true)],
+            delta_t: Some(0.25),
+            ..options(100.0)
+        };
+        let (objects, _, events, _) = run_memory(&binary(Vec3::zero()), options, &mut ()).unwrap();
+        assert_eq!(events.len(), 1);
+        let t_event = events[0][1];
+        let last = objects[0].last().unwrap();
+        assert_eq!(last[0], t_event);
+        assert!(t_event < 100.0);
+    }
+
+    #[test]
+    fn records_the_escapes() {
+        let options = Options {
+            boundary: Some(0),
+            boundary_par: Some(0.55),
+            ..options(100.0)
+        };
+        let (objects, escapes, _, _) = run_memory(&binary(Vec3::zero()), options, &mut ()).unwrap();
+        assert_eq!(escapes.len(), 2);
+        for (escape, object) in escapes.iter().zip(objects.iter()) {
+            let t_last = object.last().unwrap()[0];
+            assert!(escape[1] > t_last);
+        }
+    }
+
+    #[test]
+    fn same_result_for_any_number_of_threads() {
+        // a small cluster with a few test particles
+        let initial: Vec<Initial> = (0..40)
+            .map(|i| {
+                let x = i as f64;
+                Initial {
+                    m: if i % 10 == 9 { 0.0 } else { 1.0 / 36.0 },
+                    r: Vec3::new((1.3 * x).sin(), (2.1 * x).cos(), (0.7 * x).sin()),
+                    v: Vec3::new(0.1 * (0.9 * x).cos(), 0.1 * (1.7 * x).sin(), 0.0),
+                    beta: 0.0,
+                    tau: None,
+                }
+            })
+            .collect();
+        let simulation = Simulation::from_initial(&initial, UnitSystem::nbody()).unwrap();
+        let run_with = |integrator: u8, close_encounter: u8, threads: usize| {
+            let options = Options {
+                integrator,
+                close_encounter,
+                ce_par: Some(0.05),
+                delta_t: Some(0.01),
+                threads,
+                ..options(0.5)
+            };
+            run_memory(&simulation, options, &mut ()).unwrap().0
+        };
+        // softened through `Particles`, and truncated through the pairs
+        for close_encounter in [1, 0] {
+            for integrator in 0..4 {
+                let serial = run_with(integrator, close_encounter, 1);
+                assert_eq!(serial, run_with(integrator, close_encounter, 3));
+                assert_eq!(serial, run_with(integrator, close_encounter, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_methods() {
+        let integrator = Options {
+            integrator: 9,
+            ..options(1.0)
+        };
+        let result = run_memory(&binary(Vec3::zero()), integrator, &mut ());
+        assert!(matches!(result, Err(DriverErr::Input(_))));
+        // a stop after the start
+        for (t_start, t_stop) in [(0.0, 0.0), (0.0, -1.0), (2.0, 1.0), (0.0, f64::NAN)] {
+            let stop = Options {
+                t_start,
+                ..options(t_stop)
+            };
+            let result = run_memory(&binary(Vec3::zero()), stop, &mut ());
+            assert!(matches!(result, Err(DriverErr::Input(_))));
+        }
+        for order in [0.0, 2.5, 13.0] {
+            let fmm = Options {
+                force_method: 2,
+                force_par: Some(order),
+                ..options(1.0)
+            };
+            let result = run_memory(&binary(Vec3::zero()), fmm, &mut ());
+            assert!(matches!(result, Err(DriverErr::Input(_))));
+        }
+        // 256 only in a periodic box
+        for cells in [8.0, 48.0, 64.5, 256.0, 512.0] {
+            let pm = Options {
+                force_method: 4,
+                force_par: Some(cells),
+                ..options(1.0)
+            };
+            let result = run_memory(&binary(Vec3::zero()), pm, &mut ());
+            assert!(matches!(result, Err(DriverErr::Input(_))));
+        }
+        // block steps with Hermite or leapfrog and the direct sum only, and
+        // Hermite with block steps only
+        for (integrator, timestep_method, force_method) in [(1, 2, 0), (3, 2, 2), (4, 0, 0)] {
+            let block = Options {
+                integrator,
+                timestep_method,
+                force_method,
+                ..options(1.0)
+            };
+            let result = run_memory(&binary(Vec3::zero()), block, &mut ());
+            assert!(matches!(result, Err(DriverErr::Input(_))));
+        }
+        // a positive radius with the direct sum, without block steps or
+        // non-gravitational forces
+        let nongravity = Some((0, 1.0, false, false, false));
+        for (ce_par, timestep_method, integrator, nongravity, force_method) in [
+            (-1.0, 0, 0, None, 0),
+            (1.0, 2, 4, None, 0),
+            (1.0, 0, 0, nongravity, 0),
+            (1.0, 0, 0, None, 2),
+        ] {
+            let regular = Options {
+                ce_par: Some(ce_par),
+                timestep_method,
+                integrator,
+                nongravity,
+                force_method,
+                ..options(1.0)
+            };
+            let result = run_memory(&binary(Vec3::zero()), regular, &mut ());
+            assert!(matches!(result, Err(DriverErr::Input(_))));
+        }
+        // drag with a positive timescale only
+        for tau in [0.0, -1.0, f64::NAN] {
+            let initial: Vec<Initial> = [(-0.5, None), (0.5, Some(tau))]
+                .map(|(x, tau)| Initial {
+                    m: 1.0,
+                    r: Vec3::new(x, 0.0, 0.0),
+                    v: Vec3::zero(),
+                    beta: 0.0,
+                    tau,
+                })
+                .to_vec();
+            let simulation = Simulation::from_initial(&initial, UnitSystem::nbody()).unwrap();
+            let drag = Options {
+                nongravity: Some((0, 1.0, false, false, true)),
+                ..options(1.0)
+            };
+            let result = run_memory(&simulation, drag, &mut ());
+            assert!(matches!(result, Err(DriverErr::Input(_))));
+        }
+        // finite parameters, a positive mass and positive scale lengths
+        for (potential, par) in [
+            (0, vec![f64::NAN, 0.0, 0.0, 0.0]),
+            (1, vec![-1.0, 1.0, 0.0, 0.0, 0.0]),
+            (1, vec![1.0, 1.0, 0.0, f64::INFINITY, 0.0]),
+            (2, vec![1.0, -1.0, 0.0, 0.0, 0.0]),
+            (3, vec![1.0, 0.0, 0.0, 0.0, 0.0]),
+            (4, vec![0.0, 1.0, 0.1, 0.0, 0.0, 0.0]),
+            (4, vec![1.0, 1.0, -0.1, 0.0, 0.0, 0.0]),
+        ] {
+            let external = Options {
+                external: vec![(potential, par)],
+                ..options(1.0)
+            };
+            let result = run_memory(&binary(Vec3::zero()), external, &mut ());
+            assert!(matches!(result, Err(DriverErr::Input(_))));
+        }
+        // the box goes with the Ewald sum and the particle mesh only
+        for (force_method, box_size) in [(0, Some(2.0)), (3, None), (3, Some(-1.0))] {
+            let periodic = Options {
+                force_method,
+                box_size,
+                ..options(1.0)
+            };
+            let result = run_memory(&binary(Vec3::zero()), periodic, &mut ());
+            assert!(matches!(result, Err(DriverErr::Input(_))));
+        }
+    }
+
+    #[test]
+    fn block_steps_keep_an_eccentric_binary() {
+        let simulation = eccentric();
+        // leapfrog and Hermite, over four orbits
+        for (integrator, tolerance) in [(3, 1e-3), (4, 1e-4)] {
+            let options = Options {
+                integrator,
+                timestep_method: 2,
+                delta_t: Some(0.5),
+                ..options(10.0)
+            };
+            let (objects, _, _, _) = run_memory(&simulation, options, &mut ()).unwrap();
+            let (a, b) = (&objects[0], &objects[1]);
+            for (k, row) in a.iter().enumerate() {
+                assert!((row[0] - 0.5 * k as f64).abs() < 1e-12, "t={}", row[0]);
+            }
+            let (first, last) = (
+                binary_energy(&a[0], &b[0]),
+                binary_energy(a.last().unwrap(), b.last().unwrap()),
+            );
+            let error = ((last - first) / first).abs();
+            assert!(
+                error < tolerance,
+                "integrator={} error={}",
+                integrator,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn regularises_an_eccentric_binary() {
+        // RK4 with a step of a fifth of the period
+        let run = |ce_par| {
+            let options = Options {
+                integrator: 1,
+                delta_t: Some(0.5),
+                ce_par,
+                ..options(10.0)
+            };
+            let (objects, _, _, regularised) = run_memory(&eccentric(), options, &mut ()).unwrap();
+            let (a, b) = (&objects[0], &objects[1]);
+            let (first, last) = (
+                binary_energy(&a[0], &b[0]),
+                binary_energy(a.last().unwrap(), b.last().unwrap()),
+            );
+            (((last - first) / first).abs(), regularised)
+        };
+        let (error, regularised) = run(Some(2.0));
+        assert!(error < 1e-9, "error={}", error);
+        assert_eq!(regularised, vec![vec![0.0, 0.0, 1.0, 1.0, 1.0, 2.0]]);
+        let (error, regularised) = run(None);
+        assert!(error > 1e-3 && regularised.is_empty(), "error={}", error);
+    }
+
+    #[test]
+    fn fmm_follows_the_direct_sum() {
+        // a lattice wide enough for cells far from each other
+        let initial: Vec<Initial> = (0..1000)
+            .map(|i| Initial {
+                m: 1.0 / 1000.0,
+                r: Vec3::new((i % 10) as f64, (i / 10 % 10) as f64, (i / 100) as f64)
+                    + 0.1 * Vec3::new((i as f64).sin(), (i as f64).cos(), 0.0),
+                v: Vec3::zero(),
+                beta: 0.0,
+                tau: None,
+            })
+            .collect();
+        let simulation = Simulation::from_initial(&initial, UnitSystem::nbody()).unwrap();
+        let run = |force_method, force_par| {
+            let options = Options {
+                force_method,
+                force_par,
+                integrator: 1,
+                ..options(1.0)
+            };
+            run_memory(&simulation, options, &mut ()).unwrap().0
+        };
+        let direct = run(0, None);
+        let fmm = run(2, Some(8.0));
+        for (a, b) in direct.iter().zip(fmm.iter()) {
+            let (a, b) = (a.last().unwrap(), b.last().unwrap());
+            for c in 1..7 {
+                assert!((a[c] - b[c]).abs() < 1e-6, "{} {}", a[c], b[c]);
+            }
+        }
+    }
+
+    #[test]
+    fn mesh_follows_the_direct_sum() {
+        let initial: Vec<Initial> = (0..200)
+            .map(|i| {
+                let x = i as f64;
+                Initial {
+                    m: 1.0 / 200.0,
+                    r: Vec3::new((1.3 * x).sin(), (0.7 * x + 1.0).cos(), (2.9 * x).sin()),
+                    v: Vec3::zero(),
+                    beta: 0.0,
+                    tau: None,
+                }
+            })
+            .collect();
+        let simulation = Simulation::from_initial(&initial, UnitSystem::nbody()).unwrap();
+        let run = |force_method, force_par| {
+            let options = Options {
+                force_method,
+                force_par,
+                integrator: 1,
+                close_encounter: 1,
+                ce_par: Some(0.1),
+                delta_t: Some(0.1),
+                ..options(0.5)
+            };
+            run_memory(&simulation, options, &mut ()).unwrap().0
+        };
+        let direct = run(0, None);
+        let mesh = run(5, Some(32.0));
+        let (mut error, mut norm) = (0.0, 0.0);
+        for (a, b) in direct.iter().zip(mesh.iter()) {
+            let (first, a, b) = (&a[0], a.last().unwrap(), b.last().unwrap());
+            for c in 1..4 {
+                error += (a[c] - b[c]).powi(2);
+                norm += (a[c] - first[c]).powi(2);
+            }
+        }
+        // against how far the bodies moved
+        let error = (error / norm).sqrt();
+        assert!(error < 2e-2, "error={}", error);
+    }
+
+    #[test]
+    fn wraps_into_the_periodic_box() {
+        // the binary drifts through the box a few times
+        let run = |unwrap| {
+            let options = Options {
+                force_method: 3,
+                box_size: Some(2.0),
+                unwrap,
+                ..options(8.0)
+            };
+            run_memory(&binary(Vec3::new(1.0, 0.0, 0.0)), options, &mut ())
+                .unwrap()
+                .0
+        };
+        let (wrapped, unwrapped) = (run(false), run(true));
+        for (a, b) in wrapped.iter().flatten().zip(unwrapped.iter().flatten()) {
+            for c in 1..4 {
+                assert!((-1e-12..2.0 + 1e-12).contains(&a[c]), "{}", a[c]);
+                let images = ((b[c] - a[c]) / 2.0).round();
+                assert!((b[c] - a[c] - 2.0 * images).abs() < 1e-12);
+            }
+        }
+        assert!(unwrapped.iter().flatten().any(|row| row[1] > 4.0));
+    }
+
+    #[test]
+    fn runs_every_member_on_its_own() {
+        let dir = std::env::temp_dir().join(format!("bima-ensemble-{}", std::process::id()));
+        let simulations = [binary(Vec3::zero()), binary(Vec3::new(0.0, 0.0, 1.0))];
+        let runs = run_ensemble(
+            &simulations,
+            options(1.0),
+            &dir.to_string_lossy(),
+            true,
+            false,
+            2,
+            &mut (),
+        )
+        .unwrap();
+        assert_eq!(runs.len(), 2);
+        for ((path, group), simulation) in runs.iter().zip(simulations.iter()) {
+            assert_eq!(group, "/");
+            let (objects, _, _, _) = run_memory(simulation, options(1.0), &mut ()).unwrap();
+            let (bodies, t) = crate::load::read_result(path, -1).unwrap();
+            assert_eq!(t, 0.75);
+            for (body, object) in bodies.iter().zip(objects.iter()) {
+                let last = object.last().unwrap();
+                assert_eq!(body.r.z(), last[3]);
+            }
+        }
+        assert_ne!(runs[0].0, runs[1].0);
+    }
+}
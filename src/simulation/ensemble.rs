@@ -0,0 +1,104 @@
+use crate::initial::Initial;
+use crate::progress_bar::ProgressBar;
+use crate::progress_bar::py_stdout::PyStdout;
+use crate::simulation::Simulation;
+use crate::simulation::driver::{self, Options};
+use crate::simulation::utils;
+use crate::units::UnitSystem;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+// Many simulations run together with the same methods, see
+// `driver::run_ensemble`.
+#[pyclass]
+pub struct Ensemble {
+    simulations: Vec<Simulation>,
+}
+
+#[pymethods]
+impl Ensemble {
+    #[new]
+    #[pyo3(signature = (initial, units=None))]
+    fn new(initial: Vec<Vec<Bound<'_, Initial>>>, units: Option<UnitSystem>) -> PyResult<Self> {
+        let units = units.unwrap_or_default();
+        let simulations = initial
+            .iter()
+            .enumerate()
+            .map(|(k, initial)| {
+                let initial: Vec<Initial> =
+                    initial.iter().map(|obj| obj.borrow().clone()).collect();
+                Simulation::from_initial(&initial, units.clone()).ok_or_else(|| {
+                    PyValueError::new_err(format!("Total mass of set {} is zero", k))
+                })
+            })
+            .collect::<PyResult<Vec<Simulation>>>()?;
+        Ok(Ensemble { simulations })
+    }
+    fn __len__(&self) -> usize {
+        self.simulations.len()
+    }
+    #[pyo3(signature = (abs_path, force_method, integrator, timestep_method, close_encounter, t_stop, delta_t=None, ce_par=None, save_acc=None, replace=None, boundary=None, boundary_par=None, events=None, external=None, nongravity=None, threads=1, force_par=None, box_size=None, unwrap=false, timestep_par=None, single_file=false, jobs=0))]
+    fn run_disk<'py>(
+        &self,
+        py: Python<'py>,
+        abs_path: &str,
+        force_method: u8,
+        integrator: u8,
+        timestep_method: u8,
+        close_encounter: u8,
+        t_stop: f64,
+        delta_t: Option<f64>,
+        ce_par: Option<f64>,
+        save_acc: Option<bool>,
+        replace: Option<bool>,
+        boundary: Option<u8>,
+        boundary_par: Option<f64>,
+        events: Option<Vec<utils::EventArg>>,
+        external: Option<Vec<utils::PotentialArg>>,
+        nongravity: Option<utils::NonGravityArg>,
+        threads: usize,
+        force_par: Option<f64>,
+        box_size: Option<f64>,
+        unwrap: bool,
+        timestep_par: Option<f64>,
+        single_file: bool,
+        jobs: usize,
+    ) -> PyResult<Vec<(String, String)>> {
+        let options = Options {
+            force_method,
+            force_par,
+            box_size,
+            unwrap,
+            integrator,
+            timestep_method,
+            timestep_par,
+            close_encounter,
+            t_start: 0.0,
+            t_stop,
+            delta_t,
+            ce_par,
+            save_acc: save_acc.unwrap_or(false),
+            boundary,
+            boundary_par,
+            events: events.unwrap_or_default(),
+            external: external.unwrap_or_default(),
+            nongravity,
+            threads,
+        };
+        let writer = PyStdout::new(&py)?;
+        let mut progress_bar = ProgressBar::new(writer, 50)?;
+        // the GIL is only taken to show the progress
+        let runs = py.allow_threads(|| {
+            driver::run_ensemble(
+                &self.simulations,
+                options,
+                abs_path,
+                replace.unwrap_or(false),
+                single_file,
+                jobs,
+                &mut progress_bar,
+            )
+        })?;
+        Ok(runs)
+    }
+}
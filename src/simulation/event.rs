@@ -0,0 +1,211 @@
+use crate::simulation::cm::Barycentre;
+use bima_rs::body::Body;
+use bima_rs::vec3::Vec3;
+
+const MAX_REFINE: usize = 64;
+const TOLERANCE: f64 = 1e-12;
+
+#[derive(Clone, Debug)]
+pub enum Condition {
+    // (body, other): the separation stops decreasing, e.g. a periapsis
+    Approach(usize, usize),
+    // (body, axis, value): the coordinate along the axis crosses the value
+    Plane(usize, usize, f64),
+    // (body, other, value): the separation crosses the value
+    Distance(usize, usize, f64),
+}
+
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub condition: Condition,
+    pub stop: bool,
+}
+
+pub const COLUMNS: [&str; 8] = ["event", "t", "x", "y", "z", "vx", "vy", "vz"];
+
+#[derive(Clone, Debug)]
+pub struct Detection {
+    pub event: usize,
+    pub stop: bool,
+    pub t: f64,
+    pub r: Vec3,
+    pub v: Vec3,
+}
+
+impl Detection {
+    pub fn to_vec(&self, cm: &Barycentre) -> Vec<f64> {
+        let (r, v) = cm.absolute(self.t, self.r, self.v);
+        vec![
+            self.event as f64,
+            self.t,
+            r.x(),
+            r.y(),
+            r.z(),
+            v.x(),
+            v.y(),
+            v.z(),
+        ]
+    }
+}
+
+#[derive(Clone, Copy)]
+struct State {
+    r: Vec3,
+    v: Vec3,
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a.x() * b.x() + a.y() * b.y() + a.z() * b.z()
+}
+
+fn component(a: Vec3, axis: usize) -> f64 {
+    match axis {
+        0 => a.x(),
+        1 => a.y(),
+        _ => a.z(),
+    }
+}
+
+// cubic Hermite interpolation between two stored steps, s in [0, 1]
+fn hermite(s0: &State, s1: &State, h: f64, s: f64) -> State {
+    let s2 = s * s;
+    let s3 = s2 * s;
+    let r = (2. * s3 - 3. * s2 + 1.) * s0.r
+        + ((s3 - 2. * s2 + s) * h) * s0.v
+        + (-2. * s3 + 3. * s2) * s1.r
+        + ((s3 - s2) * h) * s1.v;
+    let v = ((6. * s2 - 6. * s) / h) * s0.r
+        + (3. * s2 - 4. * s + 1.) * s0.v
+        + ((-6. * s2 + 6. * s) / h) * s1.r
+        + (3. * s2 - 2. * s) * s1.v;
+    State { r, v }
+}
+
+impl Condition {
+    fn bodies(&self) -> (usize, Option<usize>) {
+        match self {
+            Condition::Approach(i, j) => (*i, Some(*j)),
+            Condition::Plane(i, _, _) => (*i, None),
+            Condition::Distance(i, j, _) => (*i, Some(*j)),
+        }
+    }
+    // `origin` is where the original frame sits, planes are given in it
+    fn value(&self, body: &State, other: Option<&State>, origin: Vec3) -> f64 {
+        match (self, other) {
+            (Condition::Approach(..), Some(other)) => dot(body.r - other.r, body.v - other.v),
+            (Condition::Distance(_, _, d), Some(other)) => (body.r - other.r).norm() - d,
+            (Condition::Plane(_, axis, p), _) => component(body.r + origin, *axis) - p,
+            _ => unreachable!("pair conditions always have the other body"),
+        }
+    }
+    fn crossed(&self, g0: f64, g1: f64) -> bool {
+        match self {
+            Condition::Approach(..) => g0 < 0.0 && g1 >= 0.0,
+            _ => (g0 < 0.0 && g1 >= 0.0) || (g0 > 0.0 && g1 <= 0.0),
+        }
+    }
+}
+
+// Checks the events on every stored step. The previous step is kept so the
+// time of the event can be refined between the two steps.
+pub struct Detector {
+    events: Vec<Event>,
+    cm: Barycentre,
+    t: f64,
+    previous: Vec<Option<State>>,
+}
+
+impl Detector {
+    pub fn new(events: Vec<Event>, n_objects: usize, cm: Barycentre) -> Self {
+        Detector {
+            events,
+            cm,
+            t: 0.0,
+            previous: vec![None; n_objects],
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+    // The detections are sorted by time, and nothing after the first stopping
+    // event is reported. When the run has to stop, the state of every body at
+    // the time of that event is returned as well.
+    pub fn check(&mut self, t: f64, bodies: &[Body]) -> (Vec<Detection>, Option<Vec<Body>>) {
+        let mut current = vec![None; self.previous.len()];
+        for body in bodies.iter() {
+            current[body.id] = Some(State {
+                r: body.r,
+                v: body.v,
+            });
+        }
+        let h = t - self.t;
+        let mut detections = Vec::new();
+        for (index, event) in self.events.iter().enumerate() {
+            let (i, j) = event.condition.bodies();
+            let (Some(p_i), Some(c_i)) = (self.previous[i], current[i]) else {
+                continue;
+            };
+            let (p_j, c_j) = match j {
+                Some(j) => match (self.previous[j], current[j]) {
+                    (Some(p_j), Some(c_j)) => (Some(p_j), Some(c_j)),
+                    _ => continue,
+                },
+                None => (None, None),
+            };
+            let g0 = event
+                .condition
+                .value(&p_i, p_j.as_ref(), self.cm.position(self.t));
+            let g1 = event
+                .condition
+                .value(&c_i, c_j.as_ref(), self.cm.position(t));
+            if !event.condition.crossed(g0, g1) {
+                continue;
+            }
+            let g = |s: f64| {
+                let b = hermite(&p_i, &c_i, h, s);
+                let o = p_j.zip(c_j).map(|(p, c)| hermite(&p, &c, h, s));
+                let origin = self.cm.position(self.t + s * h);
+                event.condition.value(&b, o.as_ref(), origin)
+            };
+            let (mut lo, mut hi) = (0.0, 1.0);
+            for _ in 0..MAX_REFINE {
+                let mid = 0.5 * (lo + hi);
+                if event.condition.crossed(g(lo), g(mid)) {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+                if (hi - lo) * h < TOLERANCE {
+                    break;
+                }
+            }
+            let state = hermite(&p_i, &c_i, h, hi);
+            detections.push(Detection {
+                event: index,
+                stop: event.stop,
+                t: self.t + hi * h,
+                r: state.r,
+                v: state.v,
+            });
+        }
+        detections.sort_by(|a, b| a.t.total_cmp(&b.t));
+        let mut stopped = None;
+        if let Some(first) = detections.iter().position(|d| d.stop) {
+            detections.truncate(first + 1);
+            let s = (detections[first].t - self.t) / h;
+            let bodies = bodies
+                .iter()
+                .filter_map(|body| {
+                    let previous = self.previous[body.id]?;
+                    let next = current[body.id]?;
+                    let state = hermite(&previous, &next, h, s);
+                    Some(Body::new(body.id, body.m, state.r, state.v, Some(body.a)))
+                })
+                .collect();
+            stopped = Some(bodies);
+        }
+        self.t = t;
+        self.previous = current;
+        (detections, stopped)
+    }
+}
@@ -0,0 +1,213 @@
+use crate::simulation::fmm::pair;
+use bima_rs::body::Body;
+use bima_rs::close_encounter::CloseEncounter;
+use bima_rs::vec3::Vec3;
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+// split of the sum between real and reciprocal space, in the unit box
+const ALPHA: f64 = 2.0;
+// images summed in real space, on either side along each axis
+const IMAGES: i32 = 2;
+// largest |h|² of the wave vectors 2πh summed in reciprocal space
+const WAVES: i32 = 10;
+// intervals of the table over half of the box
+const GRID: usize = 32;
+
+// The correction over a quarter of the unit box, shared by every run.
+static TABLE: OnceLock<Vec<Vec3>> = OnceLock::new();
+
+fn cube(n: i32) -> impl Iterator<Item = Vec3> {
+    (-n..=n).flat_map(move |i| {
+        (-n..=n).flat_map(move |j| (-n..=n).map(move |k| Vec3::new(i as f64, j as f64, k as f64)))
+    })
+}
+
+// Acceleration at `x` due to a unit mass at the origin, its images in the
+// unit box and a uniform background of the opposite mean density, summed
+// the way of Ewald.
+fn periodic(x: Vec3) -> Vec3 {
+    let mut a = Vec3::zero();
+    for n in cube(IMAGES) {
+        let d = x - n;
+        let r = d.norm();
+        if r == 0.0 {
+            continue;
+        }
+        let s =
+            libm::erfc(ALPHA * r) + 2.0 * ALPHA * r / PI.sqrt() * (-ALPHA * ALPHA * r * r).exp();
+        a -= (s / (r * r * r)) * d;
+    }
+    for h in cube(WAVES.isqrt()) {
+        let h2 = h.norm_2();
+        if h2 == 0.0 || h2 > WAVES as f64 {
+            continue;
+        }
+        let phase = 2.0 * PI * (h.x() * x.x() + h.y() * x.y() + h.z() * x.z());
+        a -= (2.0 / h2 * (-PI * PI * h2 / (ALPHA * ALPHA)).exp() * phase.sin()) * h;
+    }
+    a
+}
+
+// What the images and the background add to the pull of the nearest image,
+// zero at the origin by symmetry.
+fn correction(x: Vec3) -> Vec3 {
+    let r = x.norm();
+    if r == 0.0 {
+        return Vec3::zero();
+    }
+    periodic(x) + x / (r * r * r)
+}
+
+// at ((i * (GRID + 1)) + j) * (GRID + 1) + k for the point (i, j, k) / 2GRID
+fn tabulate() -> Vec<Vec3> {
+    let step = 0.5 / GRID as f64;
+    let mut table = Vec::with_capacity((GRID + 1).pow(3));
+    for i in 0..=GRID {
+        for j in 0..=GRID {
+            for k in 0..=GRID {
+                let x = Vec3::new(i as f64, j as f64, k as f64) * step;
+                table.push(correction(x));
+            }
+        }
+    }
+    table
+}
+
+// Periodic gravity in a cubic box of side `size`: each body pulls with its
+// nearest image, with the close encounter method, plus the correction for
+// all the other images, interpolated from a table of the Ewald sum. The
+// correction is odd along its own axis and even along the other two, so the
+// table only covers positive offsets.
+pub struct Ewald {
+    size: f64,
+    table: &'static [Vec3],
+}
+
+impl Ewald {
+    pub fn new(size: f64) -> Self {
+        Ewald {
+            size,
+            table: TABLE.get_or_init(tabulate),
+        }
+    }
+    // the offset between -size / 2 and size / 2 along each axis
+    fn nearest(&self, d: Vec3) -> Vec3 {
+        let l = self.size;
+        Vec3::new(
+            d.x() - l * (d.x() / l).round(),
+            d.y() - l * (d.y() / l).round(),
+            d.z() - l * (d.z() / l).round(),
+        )
+    }
+    // trilinear in the table, `x` in the unit box
+    fn correction(&self, x: Vec3) -> Vec3 {
+        let scale = 2.0 * GRID as f64;
+        let u = [
+            x.x().abs() * scale,
+            x.y().abs() * scale,
+            x.z().abs() * scale,
+        ];
+        let i = u.map(|u| (u as usize).min(GRID - 1));
+        let f = [0, 1, 2].map(|c| u[c] - i[c] as f64);
+        let mut c = Vec3::zero();
+        for corner in 0..8 {
+            let bit = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let w = (0..3)
+                .map(|c| if bit[c] == 1 { f[c] } else { 1.0 - f[c] })
+                .product::<f64>();
+            let index = ((i[0] + bit[0]) * (GRID + 1) + i[1] + bit[1]) * (GRID + 1) + i[2] + bit[2];
+            c += w * self.table[index];
+        }
+        // each component odd along its own axis
+        c * Vec3::new(x.x().signum(), x.y().signum(), x.z().signum())
+    }
+    // Acceleration at `r` from the bodies and all their images, leaving out
+    // the body `skip`.
+    pub fn acc(
+        &self,
+        r: Vec3,
+        bodies: &[Body],
+        skip: Option<usize>,
+        close_encounter: &CloseEncounter,
+    ) -> Vec3 {
+        let l2 = self.size * self.size;
+        bodies
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| Some(*j) != skip)
+            .map(|(_, body)| {
+                let d = self.nearest(body.r - r);
+                pair(body.m, d, close_encounter) - (body.m / l2) * self.correction(d / self.size)
+            })
+            .fold(Vec3::zero(), |total, a| total + a)
+    }
+}
+
+// the position moved into the box, from 0 to `size` along each axis
+pub fn wrap(size: f64, r: Vec3) -> Vec3 {
+    Vec3::new(
+        r.x().rem_euclid(size),
+        r.y().rem_euclid(size),
+        r.z().rem_euclid(size),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_follows_the_sum() {
+        let ewald = Ewald::new(1.0);
+        let mut worst: f64 = 0.0;
+        for i in 0..200 {
+            let f = i as f64;
+            let x = Vec3::new(
+                0.5 * (1.3 * f).sin(),
+                0.5 * (0.7 * f + 1.0).cos(),
+                0.49 * (2.9 * f).sin(),
+            );
+            let expected = correction(x);
+            let error = (ewald.correction(x) - expected).norm();
+            worst = worst.max(error / expected.norm().max(1.0));
+        }
+        assert!(worst < 1e-3, "worst={}", worst);
+    }
+
+    #[test]
+    fn lattice_feels_no_force() {
+        let size = 3.0;
+        let bodies: Vec<Body> = cube(1)
+            .filter(|n| n.x() >= 0.0 && n.y() >= 0.0 && n.z() >= 0.0)
+            .enumerate()
+            .map(|(i, n)| Body::new(i, 1.0, (0.5 * size) * n, Vec3::zero(), None))
+            .collect();
+        let ewald = Ewald::new(size);
+        for body in bodies.iter() {
+            let a = ewald.acc(body.r, &bodies, Some(body.id), &CloseEncounter::Regularized);
+            assert!(a.norm() < 1e-6, "a={:?}", a.to_tuple());
+        }
+    }
+
+    #[test]
+    fn repeats_from_box_to_box() {
+        let size = 2.0;
+        let bodies = vec![
+            Body::new(0, 1.0, Vec3::new(0.1, 0.25, 0.3), Vec3::zero(), None),
+            Body::new(1, 2.0, Vec3::new(1.5, 0.4, 1.9), Vec3::zero(), None),
+        ];
+        let ewald = Ewald::new(size);
+        let r = Vec3::new(0.7, 1.2, 0.4);
+        let close_encounter = CloseEncounter::Regularized;
+        let a = ewald.acc(r, &bodies, None, &close_encounter);
+        let shifted = ewald.acc(
+            r + Vec3::new(size, -size, 3.0 * size),
+            &bodies,
+            None,
+            &close_encounter,
+        );
+        assert!((a - shifted).norm() < 1e-12);
+        assert!((wrap(size, Vec3::new(-0.5, 4.5, 1.0)) - Vec3::new(1.5, 0.5, 1.0)).norm() < 1e-15);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::simulation::cm::Barycentre;
+use bima_rs::vec3::{Vec3, ZERO_VEC3};
+
+// Fixed background fields, all centred on `r` in the original frame (G = 1).
+// A body at the centre of a potential is not pulled by it, nor is a body in
+// the plane of a Miyamoto-Nagai disk with b = 0 pulled out of the plane.
+// Fields given as Python callables are not supported, the forces are
+// computed away from the interpreter.
+#[derive(Clone, Debug)]
+pub enum Potential {
+    PointMass { m: f64, r: Vec3 },
+    Plummer { m: f64, a: f64, r: Vec3 },
+    Hernquist { m: f64, a: f64, r: Vec3 },
+    // `m` is the characteristic mass 4π ρ0 rs³
+    Nfw { m: f64, rs: f64, r: Vec3 },
+    MiyamotoNagai { m: f64, a: f64, b: f64, r: Vec3 },
+    Uniform(Vec3),
+}
+
+impl Potential {
+    pub fn acc(&self, r: Vec3) -> Vec3 {
+        match self {
+            Potential::PointMass { m, r: c } => {
+                let d = r - *c;
+                let d2 = d.norm_2();
+                if d2 == 0.0 {
+                    return ZERO_VEC3;
+                }
+                -m / (d2 * d2.sqrt()) * d
+            }
+            Potential::Plummer { m, a, r: c } => {
+                let d = r - *c;
+                let s2 = d.norm_2() + a * a;
+                -m / (s2 * s2.sqrt()) * d
+            }
+            Potential::Hernquist { m, a, r: c } => {
+                let d = r - *c;
+                let dist = d.norm();
+                if dist == 0.0 {
+                    return ZERO_VEC3;
+                }
+                -m / (dist * (dist + a) * (dist + a)) * d
+            }
+            Potential::Nfw { m, rs, r: c } => {
+                let d = r - *c;
+                let dist = d.norm();
+                if dist == 0.0 {
+                    return ZERO_VEC3;
+                }
+                let enclosed = (1. + dist / rs).ln() - dist / (dist + rs);
+                -m * enclosed / (dist * dist * dist) * d
+            }
+            Potential::MiyamotoNagai { m, a, b, r: c } => {
+                let d = r - *c;
+                let zeta = (d.z() * d.z() + b * b).sqrt();
+                let az = a + zeta;
+                let s2 = d.x() * d.x() + d.y() * d.y() + az * az;
+                if s2 == 0.0 {
+                    return ZERO_VEC3;
+                }
+                let k = -m / (s2 * s2.sqrt());
+                let z = if zeta == 0.0 { 0.0 } else { d.z() * az / zeta };
+                Vec3::new(k * d.x(), k * d.y(), k * z)
+            }
+            Potential::Uniform(g) => *g,
+        }
+    }
+}
+
+// The potentials together with where the original frame sits in the frame
+// the system is integrated in.
+#[derive(Clone, Debug)]
+pub struct External {
+    pub potentials: Vec<Potential>,
+    cm: Barycentre,
+    pub offset: Vec3,
+}
+
+impl External {
+    pub fn new(potentials: Vec<Potential>, cm: Barycentre) -> Self {
+        External {
+            potentials,
+            cm,
+            offset: cm.r,
+        }
+    }
+    // `shift` is the offset of the frame after bodies were removed
+    pub fn set_time(&mut self, t: f64, shift: Vec3) {
+        self.offset = self.cm.position(t) + shift;
+    }
+    pub fn is_empty(&self) -> bool {
+        self.potentials.is_empty()
+    }
+    pub fn acc(&self, r: Vec3) -> Vec3 {
+        let r = r + self.offset;
+        self.potentials
+            .iter()
+            .fold(ZERO_VEC3, |acc, potential| acc + potential.acc(r))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn centre() -> Vec3 {
+        Vec3::new(1.0, -2.0, 0.5)
+    }
+
+    // every potential with a centre, the disk as thin as `b`
+    fn potentials(b: f64) -> Vec<Potential> {
+        let r = centre();
+        vec![
+            Potential::PointMass { m: 1.0, r },
+            Potential::Plummer { m: 1.0, a: 0.5, r },
+            Potential::Hernquist { m: 1.0, a: 0.5, r },
+            Potential::Nfw { m: 1.0, rs: 0.5, r },
+            Potential::MiyamotoNagai {
+                m: 1.0,
+                a: 0.5,
+                b,
+                r,
+            },
+            Potential::MiyamotoNagai {
+                m: 1.0,
+                a: 0.0,
+                b: 0.0,
+                r,
+            },
+        ]
+    }
+
+    #[test]
+    fn centre_pulls_nothing() {
+        for b in [0.0, 0.1] {
+            for potential in potentials(b) {
+                let a = potential.acc(centre());
+                assert_eq!(a.to_tuple(), (0.0, 0.0, 0.0), "{:?}", potential);
+            }
+        }
+    }
+
+    #[test]
+    fn thin_disk_pulls_within_its_plane() {
+        let disk = &potentials(0.0)[4];
+        let r = centre() + Vec3::new(1.0, 0.0, 0.0);
+        let a = disk.acc(r);
+        assert!(
+            a.x() < 0.0 && a.y() == 0.0 && a.z() == 0.0,
+            "{:?}",
+            a.to_tuple()
+        );
+        // towards the plane from either side
+        assert!(disk.acc(r + Vec3::new(0.0, 0.0, 1e-3)).z() < 0.0);
+        assert!(disk.acc(r - Vec3::new(0.0, 0.0, 1e-3)).z() > 0.0);
+    }
+}
@@ -0,0 +1,126 @@
+use std::f64::consts::PI;
+
+// (re, im)
+pub type Complex = [f64; 2];
+
+fn mul(a: Complex, b: Complex) -> Complex {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+// In place radix-2 transform of a length that is a power of two, with
+// e^(-2πi jk/n) forward and e^(2πi jk/n) backward, without the 1/n.
+pub fn fft(data: &mut [Complex], twiddles: &[Complex]) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let stride = n / len;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let u = data[start + k];
+                let v = mul(data[start + k + len / 2], twiddles[k * stride]);
+                data[start + k] = [u[0] + v[0], u[1] + v[1]];
+                data[start + k + len / 2] = [u[0] - v[0], u[1] - v[1]];
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// the n / 2 factors of `fft` for a length n
+pub fn twiddles(n: usize, inverse: bool) -> Vec<Complex> {
+    let sign = if inverse { 1.0 } else { -1.0 };
+    (0..n / 2)
+        .map(|k| {
+            let angle = sign * 2.0 * PI * k as f64 / n as f64;
+            [angle.cos(), angle.sin()]
+        })
+        .collect()
+}
+
+// `fft` along the three axes of a cube of side n, at (a * n + b) * n + c.
+pub fn fft3(data: &mut [Complex], n: usize, inverse: bool) {
+    let twiddles = twiddles(n, inverse);
+    let mut line = vec![[0.0; 2]; n];
+    for stride in [1, n, n * n] {
+        for l in 0..n * n {
+            let start = l / stride * stride * n + l % stride;
+            for (k, value) in line.iter_mut().enumerate() {
+                *value = data[start + k * stride];
+            }
+            fft(&mut line, &twiddles);
+            for (k, value) in line.iter().enumerate() {
+                data[start + k * stride] = *value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the sum as written, O(n²)
+    fn dft(data: &[Complex]) -> Vec<Complex> {
+        let n = data.len();
+        (0..n)
+            .map(|k| {
+                data.iter().enumerate().fold([0.0, 0.0], |total, (j, x)| {
+                    let angle = -2.0 * PI * (j * k) as f64 / n as f64;
+                    let v = mul(*x, [angle.cos(), angle.sin()]);
+                    [total[0] + v[0], total[1] + v[1]]
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn agrees_with_the_sum() {
+        let data: Vec<Complex> = (0..16)
+            .map(|i| [(i as f64).sin(), (0.3 * i as f64).cos()])
+            .collect();
+        let mut fast = data.clone();
+        fft(&mut fast, &twiddles(16, false));
+        for (a, b) in fast.iter().zip(dft(&data).iter()) {
+            assert!((a[0] - b[0]).abs() < 1e-12 && (a[1] - b[1]).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn goes_back_in_three_dimensions() {
+        let n = 8;
+        let data: Vec<Complex> = (0..n * n * n)
+            .map(|i| [(1.7 * i as f64).sin(), 0.0])
+            .collect();
+        let mut round = data.clone();
+        fft3(&mut round, n, false);
+        // a plane wave along the first axis only has one mode
+        let mut wave: Vec<Complex> = (0..n * n * n)
+            .map(|i| {
+                let angle = 2.0 * PI * (i / (n * n)) as f64 / n as f64;
+                [angle.cos(), angle.sin()]
+            })
+            .collect();
+        fft3(&mut wave, n, false);
+        let peak = n * n * n;
+        for (i, value) in wave.iter().enumerate() {
+            let expected = if i == n * n { peak as f64 } else { 0.0 };
+            assert!((value[0] - expected).abs() < 1e-9 && value[1].abs() < 1e-9);
+        }
+        fft3(&mut round, n, true);
+        for (a, b) in round.iter().zip(data.iter()) {
+            assert!((a[0] / peak as f64 - b[0]).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use bima_rs::body::Body;
+use bima_rs::close_encounter::CloseEncounter;
+use bima_rs::vec3::Vec3;
+
+// most bodies in a leaf
+const LEAF: usize = 32;
+// a cell feels another through their expansions when its radius plus the
+// extent of the bodies of the other is below THETA times their distance
+const THETA: f64 = 0.6;
+// for bodies on top of each other
+const MAX_DEPTH: usize = 32;
+
+// Multi-indices n = (a, b, c) with |n| = a + b + c up to the order, sorted by
+// |n|, and the tables of the operators between expansions.
+struct Indices {
+    order: usize,
+    list: Vec<[usize; 3]>,
+    // position in `list`, at (a * (order + 1) + b) * (order + 1) + c
+    table: Vec<usize>,
+    inv_fact: Vec<f64>,
+    // (k, j, k - j, k! / j!) for every j <= k, to shift an expansion
+    shift: Vec<(usize, usize, usize, f64)>,
+    // (n, k, n + k, (-1)^|k| / n!) for every |n| + |k| <= order
+    m2l: Vec<(usize, usize, usize, f64)>,
+}
+
+impl Indices {
+    fn new(order: usize) -> Self {
+        let mut list = Vec::new();
+        for degree in 0..=order {
+            for a in (0..=degree).rev() {
+                for b in (0..=degree - a).rev() {
+                    list.push([a, b, degree - a - b]);
+                }
+            }
+        }
+        let w = order + 1;
+        let mut table = vec![usize::MAX; w * w * w];
+        for (i, [a, b, c]) in list.iter().enumerate() {
+            table[(a * w + b) * w + c] = i;
+        }
+        let mut inv_fact = vec![1.0; w];
+        for k in 1..w {
+            inv_fact[k] = inv_fact[k - 1] / k as f64;
+        }
+        let mut indices = Indices {
+            order,
+            list,
+            table,
+            inv_fact,
+            shift: Vec::new(),
+            m2l: Vec::new(),
+        };
+        let fact = |n: &[usize; 3]| -> f64 {
+            n.iter().map(|&a| 1. / indices.inv_fact[a]).product::<f64>()
+        };
+        let mut shift = Vec::new();
+        let mut m2l = Vec::new();
+        for (k, nk) in indices.list.iter().enumerate() {
+            for (j, nj) in indices.list.iter().enumerate() {
+                if (0..3).all(|axis| nj[axis] <= nk[axis]) {
+                    let diff = [nk[0] - nj[0], nk[1] - nj[1], nk[2] - nj[2]];
+                    shift.push((k, j, indices.index(diff), fact(nk) / fact(nj)));
+                }
+                let degree = nk.iter().sum::<usize>() + nj.iter().sum::<usize>();
+                if degree <= order {
+                    let sum = [nk[0] + nj[0], nk[1] + nj[1], nk[2] + nj[2]];
+                    let sign = if nj.iter().sum::<usize>() % 2 == 0 {
+                        1.
+                    } else {
+                        -1.
+                    };
+                    m2l.push((k, j, indices.index(sum), sign / fact(nk)));
+                }
+            }
+        }
+        indices.shift = shift;
+        indices.m2l = m2l;
+        indices
+    }
+    fn len(&self) -> usize {
+        self.list.len()
+    }
+    fn index(&self, [a, b, c]: [usize; 3]) -> usize {
+        let w = self.order + 1;
+        self.table[(a * w + b) * w + c]
+    }
+    fn powers(&self, d: Vec3) -> [Vec<f64>; 3] {
+        let mut powers = [
+            vec![1.0; self.order + 1],
+            vec![1.0; self.order + 1],
+            vec![1.0; self.order + 1],
+        ];
+        for (axis, x) in [d.x(), d.y(), d.z()].into_iter().enumerate() {
+            for k in 1..=self.order {
+                powers[axis][k] = powers[axis][k - 1] * x;
+            }
+        }
+        powers
+    }
+    // d^n / n! for every n
+    fn scaled_powers(&self, d: Vec3) -> Vec<f64> {
+        let [px, py, pz] = self.powers(d);
+        self.list
+            .iter()
+            .map(|&[a, b, c]| {
+                px[a] * py[b] * pz[c] * self.inv_fact[a] * self.inv_fact[b] * self.inv_fact[c]
+            })
+            .collect()
+    }
+    // ∂^n 1/|r| for every n, from
+    // |n| r² D_n = -(2|n| - 1) Σ n_i x_i D_{n-e_i} - (|n| - 1) Σ n_i (n_i - 1) D_{n-2e_i}
+    fn derivatives(&self, r: Vec3) -> Vec<f64> {
+        let x = [r.x(), r.y(), r.z()];
+        let r2 = r.norm_2();
+        let mut d = vec![0.0; self.len()];
+        d[0] = 1. / r2.sqrt();
+        for (i, n) in self.list.iter().enumerate().skip(1) {
+            let degree = n.iter().sum::<usize>() as f64;
+            let mut value = 0.0;
+            for axis in 0..3 {
+                let mut m = *n;
+                if n[axis] >= 1 {
+                    m[axis] -= 1;
+                    value -= (2. * degree - 1.) * n[axis] as f64 * x[axis] * d[self.index(m)];
+                }
+                if n[axis] >= 2 {
+                    m[axis] -= 1;
+                    value -= (degree - 1.) * (n[axis] * (n[axis] - 1)) as f64 * d[self.index(m)];
+                }
+            }
+            d[i] = value / (degree * r2);
+        }
+        d
+    }
+    // ∇ Σ L_n h^n
+    fn gradient(&self, local: &[f64], h: Vec3) -> Vec3 {
+        let [px, py, pz] = self.powers(h);
+        let mut g = [0.0; 3];
+        for (l, &[a, b, c]) in local.iter().zip(self.list.iter()) {
+            if a > 0 {
+                g[0] += l * a as f64 * px[a - 1] * py[b] * pz[c];
+            }
+            if b > 0 {
+                g[1] += l * b as f64 * px[a] * py[b - 1] * pz[c];
+            }
+            if c > 0 {
+                g[2] += l * c as f64 * px[a] * py[b] * pz[c - 1];
+            }
+        }
+        Vec3::new(g[0], g[1], g[2])
+    }
+}
+
+struct Cell {
+    centre: Vec3,
+    half: f64,
+    // range of `Tree::index` with the bodies of the cell
+    start: usize,
+    end: usize,
+    // the 8 children are next to each other
+    first_child: Option<usize>,
+    // Σ m d^k / k! about the centre
+    multipole: Vec<f64>,
+    // farthest body from the centre
+    extent: f64,
+    // coefficients of h^n about the centre
+    local: Vec<f64>,
+    // leaves summed directly by the bodies of this leaf
+    near: Vec<usize>,
+}
+
+impl Cell {
+    fn new(centre: Vec3, half: f64, start: usize, end: usize, terms: usize) -> Self {
+        Cell {
+            centre,
+            half,
+            start,
+            end,
+            first_child: None,
+            multipole: vec![0.0; terms],
+            extent: 0.0,
+            local: vec![0.0; terms],
+            near: Vec::new(),
+        }
+    }
+    // any point of the cell can be asked for
+    fn radius(&self) -> f64 {
+        self.half * 3f64.sqrt()
+    }
+    fn contains(&self, r: Vec3) -> bool {
+        let d = r - self.centre;
+        d.x().abs() <= self.half && d.y().abs() <= self.half && d.z().abs() <= self.half
+    }
+    fn children(&self) -> impl Iterator<Item = usize> + use<> {
+        self.first_child
+            .into_iter()
+            .flat_map(|first| first..first + 8)
+    }
+}
+
+fn octant(centre: Vec3, r: Vec3) -> usize {
+    (r.x() >= centre.x()) as usize
+        | ((r.y() >= centre.y()) as usize) << 1
+        | ((r.z() >= centre.z()) as usize) << 2
+}
+
+// Acceleration due to one body, with the same close encounter treatment as
+// `bima_rs::force::gravity`.
+pub(super) fn pair(m: f64, d: Vec3, close_encounter: &CloseEncounter) -> Vec3 {
+    let d2 = d.norm_2();
+    let divisor = match close_encounter {
+        CloseEncounter::Regularized => d2,
+        CloseEncounter::Soften(s) => d2 + s * s,
+        CloseEncounter::Truncated(s) => d2.max(s * s),
+    };
+    (m / divisor) * d.hat()
+}
+
+// Fast multipole method over an adaptive octree of the bodies, with
+// cartesian expansions of 1/r up to `order`. Built once per step, then the
+// acceleration can be asked anywhere: the leaf holding the point gives the
+// far field from its local expansion and its near leaves are summed
+// directly. The close encounter method only applies to the near field.
+pub struct Tree<'a> {
+    bodies: &'a [Body],
+    indices: Indices,
+    index: Vec<usize>,
+    cells: Vec<Cell>,
+}
+
+impl<'a> Tree<'a> {
+    pub fn new(bodies: &'a [Body], order: usize) -> Self {
+        let indices = Indices::new(order);
+        let terms = indices.len();
+        let mut tree = Tree {
+            bodies,
+            indices,
+            index: (0..bodies.len()).collect(),
+            cells: Vec::new(),
+        };
+        let (mut low, mut high) = ([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]);
+        for body in bodies.iter() {
+            for (axis, x) in [body.r.x(), body.r.y(), body.r.z()].into_iter().enumerate() {
+                low[axis] = low[axis].min(x);
+                high[axis] = high[axis].max(x);
+            }
+        }
+        if bodies.is_empty() {
+            (low, high) = ([0.0; 3], [0.0; 3]);
+        }
+        let centre = Vec3::new(
+            0.5 * (low[0] + high[0]),
+            0.5 * (low[1] + high[1]),
+            0.5 * (low[2] + high[2]),
+        );
+        let extent = (0..3)
+            .map(|axis| high[axis] - low[axis])
+            .fold(0.0, f64::max);
+        let half = if extent > 0.0 {
+            0.5 * extent * (1. + 1e-9)
+        } else {
+            1.0
+        };
+        tree.cells
+            .push(Cell::new(centre, half, 0, bodies.len(), terms));
+        tree.split();
+        tree.upward();
+        tree.interact();
+        tree.downward();
+        tree
+    }
+    fn split(&mut self) {
+        let terms = self.indices.len();
+        let mut stack = vec![(0, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            let (centre, half, start, end) = {
+                let cell = &self.cells[id];
+                (cell.centre, cell.half, cell.start, cell.end)
+            };
+            if end - start <= LEAF || depth >= MAX_DEPTH {
+                continue;
+            }
+            let bodies = self.bodies;
+            self.index[start..end].sort_by_key(|&i| octant(centre, bodies[i].r));
+            let first = self.cells.len();
+            let mut begin = start;
+            for child in 0..8 {
+                let count = self.index[begin..end]
+                    .iter()
+                    .take_while(|&&i| octant(centre, bodies[i].r) == child)
+                    .count();
+                let sign = |bit: usize| if child & bit != 0 { 0.5 } else { -0.5 };
+                let offset = half * Vec3::new(sign(1), sign(2), sign(4));
+                self.cells.push(Cell::new(
+                    centre + offset,
+                    0.5 * half,
+                    begin,
+                    begin + count,
+                    terms,
+                ));
+                stack.push((first + child, depth + 1));
+                begin += count;
+            }
+            self.cells[id].first_child = Some(first);
+        }
+    }
+    // multipoles of the leaves, then of their parents, children come after
+    // their parent in `cells`
+    fn upward(&mut self) {
+        for id in (0..self.cells.len()).rev() {
+            let cell = &self.cells[id];
+            let mut multipole = vec![0.0; self.indices.len()];
+            let mut extent: f64 = 0.0;
+            if cell.first_child.is_none() {
+                for &i in self.index[cell.start..cell.end].iter() {
+                    let body = &self.bodies[i];
+                    extent = extent.max((body.r - cell.centre).norm());
+                    let powers = self.indices.scaled_powers(body.r - cell.centre);
+                    for (m, p) in multipole.iter_mut().zip(powers) {
+                        *m += body.m * p;
+                    }
+                }
+            } else {
+                for child in cell.children() {
+                    let child = &self.cells[child];
+                    if child.start == child.end {
+                        continue;
+                    }
+                    extent = extent.max((child.centre - cell.centre).norm() + child.extent);
+                    let powers = self.indices.scaled_powers(child.centre - cell.centre);
+                    for &(k, j, diff, _) in self.indices.shift.iter() {
+                        multipole[k] += child.multipole[j] * powers[diff];
+                    }
+                }
+            }
+            self.cells[id].multipole = multipole;
+            self.cells[id].extent = extent;
+        }
+    }
+    // local expansions and near lists from a traversal of every pair of
+    // cells, the first one feeling the second
+    fn interact(&mut self) {
+        let mut stack = vec![(0, 0)];
+        while let Some((a, b)) = stack.pop() {
+            let (target, source) = (&self.cells[a], &self.cells[b]);
+            if source.start == source.end {
+                continue;
+            }
+            if a == b {
+                match target.first_child {
+                    Some(_) => {
+                        for i in target.children() {
+                            for j in target.children() {
+                                stack.push((i, j));
+                            }
+                        }
+                    }
+                    None => self.cells[a].near.push(b),
+                }
+                continue;
+            }
+            let r = target.centre - source.centre;
+            if target.radius() + source.extent < THETA * r.norm() {
+                let derivatives = self.indices.derivatives(r);
+                let mut local = std::mem::take(&mut self.cells[a].local);
+                let multipole = &self.cells[b].multipole;
+                for &(n, k, sum, coefficient) in self.indices.m2l.iter() {
+                    local[n] += coefficient * multipole[k] * derivatives[sum];
+                }
+                self.cells[a].local = local;
+                continue;
+            }
+            match (target.first_child, source.first_child) {
+                (None, None) => self.cells[a].near.push(b),
+                (Some(_), None) => stack.extend(target.children().map(|i| (i, b))),
+                (None, Some(_)) => stack.extend(source.children().map(|j| (a, j))),
+                (Some(_), Some(_)) => {
+                    if target.half >= source.half {
+                        stack.extend(target.children().map(|i| (i, b)));
+                    } else {
+                        stack.extend(source.children().map(|j| (a, j)));
+                    }
+                }
+            }
+        }
+    }
+    // the local expansions of the parents shifted to their children
+    fn downward(&mut self) {
+        for id in 0..self.cells.len() {
+            let cell = &self.cells[id];
+            if cell.local.iter().all(|l| *l == 0.0) {
+                continue;
+            }
+            let local = cell.local.clone();
+            let centre = cell.centre;
+            for child in cell.children() {
+                let powers = self
+                    .indices
+                    .scaled_powers(self.cells[child].centre - centre);
+                let child_local = &mut self.cells[child].local;
+                for &(k, j, diff, factor) in self.indices.shift.iter() {
+                    child_local[j] += local[k] * factor * powers[diff];
+                }
+            }
+        }
+    }
+    // Acceleration at `r`, leaving out the body `skip`. Outside of the root
+    // cell every body is summed directly.
+    pub fn acc(&self, r: Vec3, skip: Option<usize>, close_encounter: &CloseEncounter) -> Vec3 {
+        let direct = |ids: &mut dyn Iterator<Item = &usize>| {
+            ids.filter(|&&i| Some(i) != skip)
+                .map(|&i| {
+                    let body = &self.bodies[i];
+                    pair(body.m, body.r - r, close_encounter)
+                })
+                .fold(Vec3::zero(), |total, a| total + a)
+        };
+        if !self.cells[0].contains(r) {
+            return direct(&mut (0..self.bodies.len()).collect::<Vec<_>>().iter());
+        }
+        let mut leaf = &self.cells[0];
+        while let Some(first) = leaf.first_child {
+            leaf = &self.cells[first + octant(leaf.centre, r)];
+        }
+        let mut total = self.indices.gradient(&leaf.local, r - leaf.centre);
+        for &near in leaf.near.iter() {
+            let near = &self.cells[near];
+            total += direct(&mut self.index[near.start..near.end].iter());
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::force::field;
+    use bima_rs::force;
+
+    // a clumpy cloud, the same on every run
+    fn cloud(n: usize) -> Vec<Body> {
+        let mut state = 12345u64;
+        let mut uniform = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        (0..n)
+            .map(|i| {
+                let (u, v, w) = (uniform(), uniform(), uniform());
+                let radius = 1. / ((1. - u).powf(-2. / 3.) - 1.).sqrt().max(0.05);
+                let theta = (2. * v - 1.).acos();
+                let phi = 2. * std::f64::consts::PI * w;
+                let r = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                Body::new(i, 1. / n as f64, radius.min(20.) * r, Vec3::zero(), None)
+            })
+            .collect()
+    }
+
+    fn error(bodies: &[Body], order: usize) -> f64 {
+        let tree = Tree::new(bodies, order);
+        let close_encounter = CloseEncounter::Regularized;
+        let (mut error, mut norm) = (0.0, 0.0);
+        for body in bodies.iter() {
+            let expected = force::direct(body, &bodies.to_vec(), &close_encounter, None);
+            let a = tree.acc(body.r, Some(body.id), &close_encounter);
+            error += (a - expected).norm_2();
+            norm += expected.norm_2();
+        }
+        (error / norm).sqrt()
+    }
+
+    #[test]
+    fn derivatives_of_the_inverse_distance() {
+        let indices = Indices::new(3);
+        let r = Vec3::new(0.3, -1.2, 0.7);
+        let (x, y, z) = (r.x(), r.y(), r.z());
+        let d = indices.derivatives(r);
+        let r2 = r.norm_2();
+        let r5 = r2 * r2 * r2.sqrt();
+        let r7 = r5 * r2;
+        assert!((d[indices.index([1, 0, 0])] + x / (r2 * r2.sqrt())).abs() < 1e-14);
+        assert!((d[indices.index([2, 0, 0])] - (3. * x * x - r2) / r5).abs() < 1e-14);
+        assert!((d[indices.index([0, 1, 1])] - 3. * y * z / r5).abs() < 1e-14);
+        let expected = -15. * x * y * z / r7;
+        assert!((d[indices.index([1, 1, 1])] - expected).abs() < 1e-13);
+    }
+
+    #[test]
+    fn converges_to_the_direct_sum() {
+        let bodies = cloud(2000);
+        let errors: Vec<f64> = [2, 4, 8]
+            .iter()
+            .map(|&order| error(&bodies, order))
+            .collect();
+        assert!(errors[0] < 1e-2, "{:?}", errors);
+        assert!(
+            errors[1] < errors[0] && errors[2] < errors[1],
+            "{:?}",
+            errors
+        );
+        assert!(errors[2] < 1e-4, "{:?}", errors);
+    }
+
+    #[test]
+    fn outside_the_root_is_exact() {
+        let bodies = cloud(100);
+        let tree = Tree::new(&bodies, 3);
+        let r = Vec3::new(50.0, 0.0, 0.0);
+        let expected = field(r, &bodies, &CloseEncounter::Regularized);
+        assert_eq!(tree.acc(r, None, &CloseEncounter::Regularized), expected);
+    }
+}
@@ -0,0 +1,20 @@
+use bima_rs::body::Body;
+use bima_rs::close_encounter::CloseEncounter;
+use bima_rs::vec3::{Vec3, ZERO_VEC3};
+
+// Acceleration at `r` due to the bodies, with the same close encounter
+// treatment as `bima_rs::force::gravity`.
+pub fn field(r: Vec3, bodies: &[Body], close_encounter: &CloseEncounter) -> Vec3 {
+    let mut total = ZERO_VEC3;
+    for other in bodies.iter() {
+        let d = other.r - r;
+        let d2 = d.norm_2();
+        let divisor = match close_encounter {
+            CloseEncounter::Regularized => d2,
+            CloseEncounter::Soften(s) => d2 + s * s,
+            CloseEncounter::Truncated(s) => d2.max(s * s),
+        };
+        total += (other.m / divisor) * d.hat();
+    }
+    total
+}
@@ -1,12 +1,9 @@
 use crate::progress_bar::ProgressBar;
 use crate::progress_bar::py_stdout::PyStdout;
 use crate::simulation::Simulation;
-use crate::simulation::create_system;
-use bima_rs::record::Record;
-use bima_rs::record::line::Line;
-use bima_rs::record::utils::some_acc;
+use crate::simulation::driver::{self, Options, Output};
+use crate::simulation::utils;
 use pyo3::{PyResult, Python};
-use std::time::Instant;
 
 pub fn call<'py>(
     simulation: &Simulation,
@@ -19,41 +16,40 @@ pub fn call<'py>(
     delta_t: Option<f64>,
     ce_par: Option<f64>,
     save_acc: Option<bool>,
-) -> PyResult<Vec<Vec<Vec<f64>>>> {
-    let save_acc = save_acc.unwrap_or(false);
-    let masses = &simulation.bodies.iter().map(|b| b.m).collect();
-    let mut record = Record::empty(masses, save_acc);
-    let writer = PyStdout::new(&py)?;
-    let mut progress_bar = ProgressBar::new(writer, 50)?;
-    let system = create_system(
-        &simulation.bodies,
+    boundary: Option<u8>,
+    boundary_par: Option<f64>,
+    events: Option<Vec<utils::EventArg>>,
+    external: Option<Vec<utils::PotentialArg>>,
+    nongravity: Option<utils::NonGravityArg>,
+    threads: usize,
+    force_par: Option<f64>,
+    box_size: Option<f64>,
+    unwrap: bool,
+    timestep_par: Option<f64>,
+) -> PyResult<Output> {
+    let options = Options {
         force_method,
+        force_par,
+        box_size,
+        unwrap,
         integrator,
         timestep_method,
+        timestep_par,
         close_encounter,
+        t_start: 0.0,
+        t_stop,
         delta_t,
         ce_par,
-    )?;
-    let (rx, handle) = system.integrate(t_stop);
-    let mut latest_time = Instant::now();
-    let mut iteration = 1;
-    for data in rx {
-        let (t, percentage, bodies) = (data.t, data.percentage, data.bodies);
-        let now = Instant::now();
-        if now.duration_since(latest_time).as_millis() >= 100 {
-            latest_time = now;
-            progress_bar.update(iteration, percentage)?;
-        }
-        if let Some(bodies) = bodies {
-            for (i, body) in bodies.into_iter().enumerate() {
-                let a = some_acc(body.a, save_acc);
-                let line = Line::new(t, body.r, body.v, a);
-                record.add(i, line);
-            }
-        }
-        iteration += 1;
-    }
-    progress_bar.update(iteration, 1.)?;
-    let _ = handle.join().expect("Failed to join thread");
-    Ok(record.to_vec(&simulation.cm))
+        save_acc: save_acc.unwrap_or(false),
+        boundary,
+        boundary_par,
+        events: events.unwrap_or_default(),
+        external: external.unwrap_or_default(),
+        nongravity,
+        threads,
+    };
+    let writer = PyStdout::new(&py)?;
+    let mut progress_bar = ProgressBar::new(writer, 50)?;
+    // the GIL is only taken to show the progress
+    Ok(py.allow_threads(|| driver::run_memory(simulation, options, &mut progress_bar))?)
 }
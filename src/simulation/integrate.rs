@@ -0,0 +1,196 @@
+use crate::simulation::block::{Blocks, Forces};
+use crate::simulation::boundary::{Boundary, Escape, Frame};
+use crate::simulation::chain::ChainErr;
+use crate::simulation::external::External;
+use crate::simulation::nongravity::NonGravity;
+use crate::simulation::regular::{Entry, Regular};
+use crate::simulation::step::{self, Solver};
+use bima_rs::body::Body;
+use bima_rs::system::System;
+use bima_rs::timestep::TimestepMethod;
+use may::coroutine::{self, JoinHandle};
+use may::sync::mpsc::{self, Receiver};
+use std::fmt;
+use std::sync::mpsc::SendError;
+
+pub struct Data {
+    pub bodies: Option<Vec<Body>>,
+    pub escapes: Vec<Escape>,
+    // pairs taken into or let go of by the regularisation
+    pub regularised: Vec<Entry>,
+    pub percentage: f64,
+    pub t: f64,
+}
+
+// Why the integration ended before `t_stop`.
+#[derive(Debug)]
+pub enum IntegrateErr {
+    // the receiver was dropped, as when an event stops the run
+    Closed,
+    // the regularisation of a group failed in the step from `t`
+    Chain(ChainErr, f64),
+}
+
+impl fmt::Display for IntegrateErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrateErr::Closed => write!(f, "The receiver of the bodies was dropped"),
+            IntegrateErr::Chain(e, t) => write!(f, "{} in the step from t={}", e, t),
+        }
+    }
+}
+
+impl From<SendError<Data>> for IntegrateErr {
+    fn from(_: SendError<Data>) -> Self {
+        IntegrateErr::Closed
+    }
+}
+
+// Same as `System::integrate`, but the bodies crossing the boundary are
+// taken out of the system between steps, and the external field and the
+// non-gravitational forces are added to the forces. Bodies without mass are
+// moved as test particles, and the gravity of the bodies is computed as the
+// solver says. With block timesteps every interval of the `System` is run by
+// `Blocks`, and the bodies are only output and checked against the boundary
+// at its ends. With the regularisation the close groups are taken out of the
+// system for each step, as `Regular` says. The bodies sent through the
+// channel carry the id of the object they belong to.
+pub fn integrate(
+    mut system: System,
+    t_stop: f64,
+    boundary: Option<Boundary>,
+    mut external: External,
+    mut nongravity: Option<NonGravity>,
+    solver: Solver,
+) -> (Receiver<Data>, JoinHandle<Result<(), IntegrateErr>>) {
+    let (tx, rx) = mpsc::channel::<Data>();
+    let handle: JoinHandle<Result<(), IntegrateErr>> = unsafe {
+        coroutine::spawn(move || {
+            let (mut bodies, mut tests): (Vec<Body>, Vec<Body>) =
+                system.bodies.drain(..).partition(|body| body.m != 0.0);
+            let t_start = system.t;
+            let mut frame = Frame::new(bodies.iter().map(|body| body.id).collect());
+            for (i, body) in bodies.iter_mut().enumerate() {
+                body.id = i;
+            }
+            system.bodies = bodies;
+            if let Some(nongravity) = nongravity.as_mut() {
+                nongravity.set_ids(frame.ids());
+            }
+            match system.timestep_method {
+                TimestepMethod::Constant(dt) => {
+                    if dt <= 0.0 {
+                        return Ok(());
+                    }
+                    let mut tmp = Vec::new();
+                    let mut store = true;
+                    let mut escapes = Vec::new();
+                    let mut blocks = solver.block.map(Blocks::new);
+                    let mut regular = solver.regular.map(Regular::new);
+                    let mut regularised = Vec::new();
+                    while system.t < t_stop && !system.bodies.is_empty() {
+                        let percentage = (system.t - t_start) / (t_stop - t_start);
+                        let bodies = if store {
+                            Some(frame.output(&system.bodies, &tests, system.t))
+                        } else {
+                            None
+                        };
+                        let data = Data {
+                            bodies,
+                            escapes: std::mem::take(&mut escapes),
+                            regularised: std::mem::take(&mut regularised),
+                            percentage,
+                            t: system.t,
+                        };
+                        tx.send(data)?;
+                        let proceed = match blocks.as_mut() {
+                            Some(blocks) => {
+                                let forces = Forces {
+                                    close_encounter: &system.close_encounter,
+                                    external: &mut external,
+                                    frame: &frame,
+                                    nongravity: nongravity.as_ref(),
+                                    pool: solver.pool.as_ref(),
+                                };
+                                blocks.advance(system.t, &mut system.bodies, &mut tests, forces);
+                                true
+                            }
+                            None => {
+                                // the groups stay out over the stages of leapfrog
+                                if let Some(regular) = regular.as_mut().filter(|_| store) {
+                                    external.set_time(system.t, frame.offset(system.t));
+                                    regularised.extend(regular.split(
+                                        system.t,
+                                        dt,
+                                        &mut system.bodies,
+                                        frame.ids(),
+                                        &external,
+                                        &system.close_encounter,
+                                    ));
+                                }
+                                let t_mid = system.t + 0.5 * dt;
+                                external.set_time(t_mid, frame.offset(t_mid));
+                                let proceed = step::constant_step(
+                                    &mut system,
+                                    dt,
+                                    &mut tmp,
+                                    &mut tests,
+                                    &external,
+                                    nongravity.as_ref(),
+                                    &solver,
+                                );
+                                if let Some(regular) = regular.as_mut().filter(|_| proceed) {
+                                    let t_end = system.t + dt;
+                                    external.set_time(t_end, frame.offset(t_end));
+                                    regular
+                                        .join(
+                                            dt,
+                                            &mut system.bodies,
+                                            &external,
+                                            &system.close_encounter,
+                                        )
+                                        .map_err(|e| IntegrateErr::Chain(e, system.t))?;
+                                }
+                                proceed
+                            }
+                        };
+                        if proceed {
+                            store = true;
+                            system.t += dt;
+                            if let Some(boundary) = &boundary {
+                                let (indices, test_indices) =
+                                    boundary.escaped(&system.bodies, &tests);
+                                if !indices.is_empty() || !test_indices.is_empty() {
+                                    escapes = frame.remove(
+                                        &mut system.bodies,
+                                        &indices,
+                                        &mut tests,
+                                        &test_indices,
+                                        system.t,
+                                    );
+                                    if let Some(nongravity) = nongravity.as_mut() {
+                                        nongravity.set_ids(frame.ids());
+                                    }
+                                }
+                            }
+                        } else {
+                            store = false;
+                        }
+                    }
+                    if !escapes.is_empty() || !regularised.is_empty() {
+                        let data = Data {
+                            bodies: None,
+                            escapes,
+                            regularised,
+                            percentage: (system.t - t_start) / (t_stop - t_start),
+                            t: system.t,
+                        };
+                        tx.send(data)?;
+                    }
+                }
+            };
+            Ok(())
+        })
+    };
+    (rx, handle)
+}
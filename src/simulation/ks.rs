@@ -0,0 +1,184 @@
+use bima_rs::vec3::Vec3;
+
+type Vec4 = [f64; 4];
+
+// steps of the search for the fictitious time of a physical one
+const MAX_ITER: usize = 200;
+
+fn dot4(a: Vec4, b: Vec4) -> f64 {
+    a.iter().zip(b.iter()).map(|(a, b)| a * b).sum()
+}
+
+// c0 to c3 of Stumpff, c_k(z) = Σ (-z)^n / (2n + k)!
+fn stumpff(z: f64) -> [f64; 4] {
+    if z.abs() < 1.0 {
+        let mut c = [1.0, 1.0, 0.5, 1.0 / 6.0];
+        for (k, c) in c.iter_mut().enumerate() {
+            let mut term = *c;
+            *c = 0.0;
+            for n in 0..12 {
+                *c += term;
+                term *= -z / ((2 * n + k + 1) * (2 * n + k + 2)) as f64;
+            }
+        }
+        c
+    } else if z > 0.0 {
+        let s = z.sqrt();
+        let (sin, cos) = s.sin_cos();
+        [cos, sin / s, (1.0 - cos) / z, (s - sin) / (z * s)]
+    } else {
+        let s = (-z).sqrt();
+        let (sinh, cosh) = (s.sinh(), s.cosh());
+        [cosh, sinh / s, (cosh - 1.0) / -z, (sinh - s) / (-z * s)]
+    }
+}
+
+// a u with L(u) u = r
+fn from_r(r: Vec3) -> Vec4 {
+    let norm = r.norm();
+    if r.x() >= 0.0 {
+        let u1 = (0.5 * (norm + r.x())).sqrt();
+        [u1, r.y() / (2.0 * u1), r.z() / (2.0 * u1), 0.0]
+    } else {
+        let u2 = (0.5 * (norm - r.x())).sqrt();
+        [r.y() / (2.0 * u2), u2, 0.0, r.z() / (2.0 * u2)]
+    }
+}
+
+// the first three components of L(u) w
+fn l(u: Vec4, w: Vec4) -> Vec3 {
+    Vec3::new(
+        u[0] * w[0] - u[1] * w[1] - u[2] * w[2] + u[3] * w[3],
+        u[1] * w[0] + u[0] * w[1] - u[3] * w[2] - u[2] * w[3],
+        u[2] * w[0] + u[3] * w[1] + u[0] * w[2] + u[1] * w[3],
+    )
+}
+
+// L(u)ᵀ v
+fn l_t(u: Vec4, v: Vec3) -> Vec4 {
+    [
+        u[0] * v.x() + u[1] * v.y() + u[2] * v.z(),
+        -u[1] * v.x() + u[0] * v.y() + u[3] * v.z(),
+        -u[2] * v.x() - u[3] * v.y() + u[0] * v.z(),
+        u[3] * v.x() - u[2] * v.y() + u[1] * v.z(),
+    ]
+}
+
+// The relative orbit `r`, `v` of two bodies of total mass `m` moved by `dt`,
+// in the variables of Kustaanheimo and Stiefel. With dt = r ds the motion is
+// the harmonic oscillator u'' = (h / 2) u of the energy h, solved for any h
+// with the functions of Stumpff, so the collision is regular and the only
+// approximation is finding the fictitious time s of `dt`.
+pub fn kepler(m: f64, r: Vec3, v: Vec3, dt: f64) -> (Vec3, Vec3) {
+    let u0 = from_r(r);
+    let du0 = l_t(u0, v).map(|x| 0.5 * x);
+    let r0 = r.norm();
+    let beta = m / r0 - 0.5 * v.norm_2();
+    // dr / ds
+    let dr0 = 2.0 * dot4(u0, du0);
+    // the physical time and the separation at s, from r'' + 2 beta r = m
+    let time = |s: f64| {
+        let c = stumpff(2.0 * beta * s * s);
+        let t = r0 * s * c[1] + dr0 * s * s * c[2] + m * s * s * s * c[3];
+        let r = r0 * c[0] + dr0 * s * c[1] + m * s * s * c[2];
+        (t, r)
+    };
+    let (mut lo, mut hi) = (0.0, dt / r0);
+    while time(hi).0 < dt {
+        lo = hi;
+        hi *= 2.0;
+    }
+    // Newton inside the bracket, bisection when it leaves it
+    let mut s = 0.5 * (lo + hi);
+    for _ in 0..MAX_ITER {
+        let (t, r) = time(s);
+        let f = t - dt;
+        if f.abs() <= 1e-15 * dt {
+            break;
+        }
+        if f < 0.0 {
+            lo = s;
+        } else {
+            hi = s;
+        }
+        let next = s - f / r;
+        s = if next > lo && next < hi {
+            next
+        } else {
+            0.5 * (lo + hi)
+        };
+        if hi - lo <= 1e-16 * hi {
+            break;
+        }
+    }
+    let c = stumpff(0.5 * beta * s * s);
+    let omega2 = 0.5 * beta;
+    let u: Vec4 = [0, 1, 2, 3].map(|k| u0[k] * c[0] + du0[k] * s * c[1]);
+    let du: Vec4 = [0, 1, 2, 3].map(|k| -omega2 * s * c[1] * u0[k] + c[0] * du0[k]);
+    let r = l(u, u);
+    let v = (2.0 / dot4(u, u)) * l(u, du);
+    (r, v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn energy(m: f64, r: Vec3, v: Vec3) -> f64 {
+        0.5 * v.norm_2() - m / r.norm()
+    }
+
+    #[test]
+    fn goes_back_and_forth_between_the_variables() {
+        for r in [
+            Vec3::new(0.3, -0.2, 0.5),
+            Vec3::new(-0.7, 0.1, -0.4),
+            Vec3::new(-1.0, 0.0, 0.0),
+        ] {
+            let u = from_r(r);
+            assert!((l(u, u) - r).norm() < 1e-15);
+            let v = Vec3::new(0.2, -0.9, 0.4);
+            let back = (2.0 / dot4(u, u)) * l(u, l_t(u, v).map(|x| 0.5 * x));
+            assert!((back - v).norm() < 1e-14);
+        }
+    }
+
+    #[test]
+    fn closes_an_eccentric_orbit() {
+        // e = 0.999 from the apocentre, over one period, through a pericentre
+        // 1 / 1999 of the apocentre
+        let (m, e): (f64, f64) = (1.0, 0.999);
+        let a = 1.0 / (1.0 + e);
+        let r = Vec3::new(1.0, 0.0, 0.0);
+        let v = Vec3::new(0.0, (m / a * (1.0 - e) / (1.0 + e)).sqrt(), 0.0);
+        let period = 2.0 * std::f64::consts::PI * (a * a * a / m).sqrt();
+        let (r1, v1) = kepler(m, r, v, period);
+        assert!((r1 - r).norm() < 1e-10, "{:?}", r1.to_tuple());
+        assert!((v1 - v).norm() < 1e-10);
+        // halfway it is at the pericentre
+        let (r_half, v_half) = kepler(m, r, v, 0.5 * period);
+        assert!((r_half.norm() - a * (1.0 - e)).abs() < 1e-12);
+        let error = (energy(m, r_half, v_half) - energy(m, r, v)) / energy(m, r, v);
+        assert!(error.abs() < 1e-10, "{}", error);
+    }
+
+    #[test]
+    fn follows_a_hyperbola() {
+        let (m, r, v) = (2.0, Vec3::new(-5.0, 0.5, 0.0), Vec3::new(2.0, 0.0, 0.1));
+        let dt = 5.0;
+        let (r1, v1) = kepler(m, r, v, dt);
+        // the same in two halves, and the angular momentum kept
+        let (r_half, v_half) = kepler(m, r, v, 0.5 * dt);
+        let (r2, v2) = kepler(m, r_half, v_half, 0.5 * dt);
+        assert!((r1 - r2).norm() < 1e-10 && (v1 - v2).norm() < 1e-10);
+        let h = |r: Vec3, v: Vec3| {
+            Vec3::new(
+                r.y() * v.z() - r.z() * v.y(),
+                r.z() * v.x() - r.x() * v.z(),
+                r.x() * v.y() - r.y() * v.x(),
+            )
+        };
+        assert!((h(r1, v1) - h(r, v)).norm() < 1e-10);
+        assert!((energy(m, r1, v1) - energy(m, r, v)).abs() < 1e-10);
+    }
+}
@@ -1,49 +1,101 @@
+mod block;
+mod boundary;
+mod chain;
+pub mod cm;
+pub mod driver;
+#[cfg(feature = "python")]
+mod ensemble;
+mod event;
+mod ewald;
+mod external;
+mod fft;
+mod fmm;
+mod force;
+#[cfg(feature = "python")]
 mod in_disk;
+#[cfg(feature = "python")]
 mod in_memory;
-mod store;
-mod utils;
+mod integrate;
+mod ks;
+mod nongravity;
+mod pm;
+mod regular;
+pub mod soa;
+mod step;
+pub mod store;
+pub mod utils;
 use crate::initial::Initial;
+use crate::units::UnitSystem;
 use bima_rs::body::Body;
-use bima_rs::cm::CM;
+use bima_rs::force::ForceMethod;
+use bima_rs::integrator::Integrator;
 use bima_rs::system::System;
+use bima_rs::timestep::TimestepMethod;
+use cm::Barycentre;
+use driver::{DriverErr, Options};
+use ewald::Ewald;
+use pm::Pm;
+use step::Solver;
+#[cfg(feature = "python")]
+pub use ensemble::Ensemble;
+#[cfg(feature = "python")]
 use pyo3::exceptions::PyValueError;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 use std::collections::HashMap;
 
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct Simulation {
-    cm: CM,
+    cm: Barycentre,
     bodies: Vec<Body>,
+    beta: Vec<f64>,
+    tau: Vec<Option<f64>>,
+    units: UnitSystem,
 }
 
-#[pymethods]
 impl Simulation {
-    #[new]
-    #[pyo3(signature = (initial))]
-    fn new(initial: Vec<Bound<'_, Initial>>) -> PyResult<Self> {
+    // None if the total mass is zero
+    pub fn from_initial(initial: &[Initial], units: UnitSystem) -> Option<Self> {
         let bodies = initial
             .iter()
             .enumerate()
-            .map(|(i, obj)| {
-                let initial = obj.borrow();
-                Body::new(i, initial.m, initial.r, initial.v, None)
+            .map(|(i, initial)| {
+                // G = 1 from here on
+                Body::new(i, units.g * initial.m, initial.r, initial.v, None)
             })
             .collect::<Vec<Body>>();
-        let cm =
-            CM::from_bodies(&bodies).map_err(|_| PyValueError::new_err("Total mass is zero"))?;
+        let beta = initial.iter().map(|initial| initial.beta).collect();
+        let tau = initial.iter().map(|initial| initial.tau).collect();
+        let cm = Barycentre::from_bodies(&bodies)?;
         let relative_bodies: Vec<Body> = bodies
             .into_iter()
             .map(|mut body| {
-                body.r -= cm.r();
+                body.r -= cm.r;
+                body.v -= cm.v;
                 return body;
             })
             .collect();
-        Ok(Simulation {
+        Some(Simulation {
             cm,
             bodies: relative_bodies,
+            beta,
+            tau,
+            units,
         })
     }
-    #[pyo3(signature = (force_method, integrator, timestep_method, close_encounter, t_stop, delta_t=None, ce_par=None, save_acc=None))]
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Simulation {
+    #[new]
+    #[pyo3(signature = (initial, units=None))]
+    fn new(initial: Vec<Bound<'_, Initial>>, units: Option<UnitSystem>) -> PyResult<Self> {
+        let initial: Vec<Initial> = initial.iter().map(|obj| obj.borrow().clone()).collect();
+        Simulation::from_initial(&initial, units.unwrap_or_default())
+            .ok_or_else(|| PyValueError::new_err("Total mass is zero"))
+    }
+    #[pyo3(signature = (force_method, integrator, timestep_method, close_encounter, t_stop, delta_t=None, ce_par=None, save_acc=None, boundary=None, boundary_par=None, events=None, external=None, nongravity=None, threads=1, force_par=None, box_size=None, unwrap=false, timestep_par=None))]
     fn run_memory<'py>(
         &self,
         py: Python<'py>,
@@ -55,7 +107,17 @@ impl Simulation {
         delta_t: Option<f64>,
         ce_par: Option<f64>,
         save_acc: Option<bool>,
-    ) -> PyResult<Vec<Vec<Vec<f64>>>> {
+        boundary: Option<u8>,
+        boundary_par: Option<f64>,
+        events: Option<Vec<utils::EventArg>>,
+        external: Option<Vec<utils::PotentialArg>>,
+        nongravity: Option<utils::NonGravityArg>,
+        threads: usize,
+        force_par: Option<f64>,
+        box_size: Option<f64>,
+        unwrap: bool,
+        timestep_par: Option<f64>,
+    ) -> PyResult<driver::Output> {
         in_memory::call(
             &self,
             py,
@@ -67,9 +129,19 @@ impl Simulation {
             delta_t,
             ce_par,
             save_acc,
+            boundary,
+            boundary_par,
+            events,
+            external,
+            nongravity,
+            threads,
+            force_par,
+            box_size,
+            unwrap,
+            timestep_par,
         )
     }
-    #[pyo3(signature = (abs_path, force_method, integrator, timestep_method, close_encounter, t_stop, delta_t=None, ce_par=None, save_acc=None, replace=None))]
+    #[pyo3(signature = (abs_path, force_method, integrator, timestep_method, close_encounter, t_stop, delta_t=None, ce_par=None, save_acc=None, replace=None, boundary=None, boundary_par=None, events=None, external=None, nongravity=None, threads=1, force_par=None, box_size=None, unwrap=false, timestep_par=None))]
     fn run_disk<'py>(
         &self,
         py: Python<'py>,
@@ -83,6 +155,16 @@ impl Simulation {
         ce_par: Option<f64>,
         save_acc: Option<bool>,
         replace: Option<bool>,
+        boundary: Option<u8>,
+        boundary_par: Option<f64>,
+        events: Option<Vec<utils::EventArg>>,
+        external: Option<Vec<utils::PotentialArg>>,
+        nongravity: Option<utils::NonGravityArg>,
+        threads: usize,
+        force_par: Option<f64>,
+        box_size: Option<f64>,
+        unwrap: bool,
+        timestep_par: Option<f64>,
     ) -> PyResult<String> {
         in_disk::call(
             self,
@@ -97,30 +179,94 @@ impl Simulation {
             ce_par,
             save_acc,
             replace,
+            boundary,
+            boundary_par,
+            events,
+            external,
+            nongravity,
+            threads,
+            force_par,
+            box_size,
+            unwrap,
+            timestep_par,
         )
     }
 }
 
-fn create_system(
-    bodies: &Vec<Body>,
-    force_method: u8,
-    integrator: u8,
-    timestep_method: u8,
-    close_encounter: u8,
-    delta_t: Option<f64>,
-    ce_par: Option<f64>,
-) -> PyResult<System> {
-    let force_method = utils::get_force(force_method)?;
-    let integrator = utils::get_integrator(integrator)?;
-    let timestep_method = utils::get_timestep(timestep_method, delta_t)?;
-    let close_encounter = utils::get_close(close_encounter, ce_par)?;
-    Ok(System {
-        t: 0.0,
+// The system, and the solver of the forces when they do not come from its
+// force method, without a thread pool yet.
+fn create_system(bodies: &Vec<Body>, options: &Options) -> Result<(System, Solver), DriverErr> {
+    let force = utils::get_force(options.force_method, options.force_par, options.box_size)
+        .map_err(DriverErr::input)?;
+    let mut solver = Solver {
+        fmm: None,
+        ewald: None,
+        pm: None,
+        block: None,
+        regular: None,
+        pool: None,
+    };
+    let direct = matches!(force, utils::Force::Method(ForceMethod::Direct));
+    let force_method = match force {
+        utils::Force::Method(force_method) => force_method,
+        // the pairs of the `System` are not used
+        utils::Force::Fmm(order) => {
+            solver.fmm = Some(order);
+            ForceMethod::Direct
+        }
+        utils::Force::Ewald(size) => {
+            solver.ewald = Some(Ewald::new(size));
+            ForceMethod::Direct
+        }
+        utils::Force::Pm(grid) => {
+            solver.pm = Some(Pm::new(grid));
+            ForceMethod::Direct
+        }
+    };
+    let timestep = utils::get_timestep(
+        options.timestep_method,
+        options.delta_t,
+        options.timestep_par,
+        options.integrator,
+    )
+    .map_err(DriverErr::input)?;
+    let (timestep_method, integrator) = match timestep {
+        utils::Timestep::Method(timestep_method) => (
+            timestep_method,
+            utils::get_integrator(options.integrator).map_err(DriverErr::input)?,
+        ),
+        // the `System` is not stepped, only its interval is used
+        utils::Timestep::Block(block) => {
+            if !direct {
+                return Err(DriverErr::input(utils::TimestepMethodErr::NotDirect));
+            }
+            solver.block = Some(block);
+            (TimestepMethod::Constant(block.delta_t), Integrator::new_leap_frog())
+        }
+    };
+    let close_encounter =
+        utils::get_close(options.close_encounter, options.ce_par).map_err(DriverErr::input)?;
+    solver.regular =
+        utils::get_regular(options.close_encounter, options.ce_par).map_err(DriverErr::input)?;
+    if solver.regular.is_some() {
+        if solver.block.is_some() {
+            return Err(DriverErr::input(utils::CloseEncounterErr::Block));
+        }
+        if options.nongravity.is_some() {
+            return Err(DriverErr::input(utils::CloseEncounterErr::NonGravity));
+        }
+        if !direct {
+            return Err(DriverErr::input(utils::CloseEncounterErr::NotDirect));
+        }
+    }
+    let system = System {
+        t: options.t_start,
         bodies: bodies.clone(),
         force_method,
         integrator,
         timestep_method,
         close_encounter,
         cache: HashMap::new(),
-    })
+    };
+    Ok((system, solver))
 }
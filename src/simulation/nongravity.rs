@@ -0,0 +1,89 @@
+use bima_rs::body::Body;
+use bima_rs::vec3::{Vec3, ZERO_VEC3};
+use bima_rs::vec6::Vec6;
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a.x() * b.x() + a.y() * b.y() + a.z() * b.z()
+}
+
+// Forces felt relative to a central body, which itself is left untouched.
+#[derive(Clone, Debug)]
+pub struct NonGravity {
+    // object id of the central body and the speed of light in code units
+    pub central: usize,
+    pub c: f64,
+    // first post-Newtonian correction of the central body
+    pub gr: bool,
+    // radiation pressure and Poynting-Robertson drag, scaled by `beta`
+    pub radiation: bool,
+    // velocity-proportional drag with timescale `tau`
+    pub drag: bool,
+    // by object id
+    pub beta: Vec<f64>,
+    pub tau: Vec<Option<f64>>,
+    // object id of every body in the system, by index
+    ids: Vec<usize>,
+    index: Option<usize>,
+}
+
+impl NonGravity {
+    pub fn new(
+        central: usize,
+        c: f64,
+        gr: bool,
+        radiation: bool,
+        drag: bool,
+        beta: Vec<f64>,
+        tau: Vec<Option<f64>>,
+    ) -> Self {
+        let ids = (0..beta.len()).collect();
+        let index = (central < beta.len()).then_some(central);
+        NonGravity {
+            central,
+            c,
+            gr,
+            radiation,
+            drag,
+            beta,
+            tau,
+            ids,
+            index,
+        }
+    }
+    pub fn set_ids(&mut self, ids: &[usize]) {
+        self.ids = ids.to_vec();
+        self.index = ids.iter().position(|i| *i == self.central);
+    }
+    // object id of the body at the index in the system
+    pub fn id(&self, index: usize) -> usize {
+        self.ids[index]
+    }
+    pub fn acc(&self, id: usize, w: Vec6, bodies: &[Body]) -> Vec3 {
+        if id == self.central {
+            return ZERO_VEC3;
+        }
+        let Some(central) = self.index else {
+            return ZERO_VEC3;
+        };
+        let central = &bodies[central];
+        let r = w.r - central.r;
+        let v = w.v - central.v;
+        let mut a = ZERO_VEC3;
+        if self.gr {
+            let c2 = self.c * self.c;
+            let r_norm = r.norm();
+            let k = central.m / (c2 * r_norm * r_norm * r_norm);
+            a += k * ((4. * central.m / r_norm - v.norm_2()) * r + (4. * dot(r, v)) * v);
+        }
+        if self.radiation && self.beta[id] != 0.0 {
+            let rhat = r.hat();
+            let rdot = dot(rhat, v);
+            let k = self.beta[id] * central.m / r.norm_2();
+            a += k * ((1. - rdot / self.c) * rhat - v / self.c);
+        }
+        if let Some(tau) = self.tau[id].filter(|_| self.drag) {
+            a += -1. / tau * v;
+        }
+        a
+    }
+}
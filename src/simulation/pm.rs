@@ -0,0 +1,474 @@
+use crate::simulation::fft::{self, Complex};
+use crate::simulation::fmm::pair;
+use bima_rs::body::Body;
+use bima_rs::close_encounter::CloseEncounter;
+use bima_rs::vec3::Vec3;
+use std::f64::consts::PI;
+
+// scale of the split between the mesh and the pairs, in cells
+const SPLIT: f64 = 1.25;
+// the pairs are summed up to CUTOFF split scales
+const CUTOFF: f64 = 5.0;
+// empty cells around the bodies of an isolated mesh
+const MARGIN: usize = 3;
+
+// The particle mesh: `cells` per side of a power of two, over the periodic
+// box or around the bodies, with the pairs of the short range or not.
+#[derive(Clone, Copy, Debug)]
+pub struct Grid {
+    pub cells: usize,
+    pub short_range: bool,
+    pub box_size: Option<f64>,
+}
+
+// The particle mesh with the transform of the Green function of an isolated
+// mesh, which is the same on every step in units of the cells: built once
+// with the solver, as the table of the Ewald sum is. The transform of the
+// Green function is real, as it is even along every axis.
+pub struct Pm {
+    pub grid: Grid,
+    green: Option<Vec<f64>>,
+}
+
+impl Pm {
+    pub fn new(grid: Grid) -> Self {
+        let green = grid.box_size.is_none().then(|| green(2 * grid.cells));
+        Pm { grid, green }
+    }
+}
+
+// the transform of the Green function on `m` nodes per side for cells of
+// unit width, h² times it for cells of width h
+fn green(m: usize) -> Vec<f64> {
+    let mut green: Vec<Complex> = vec![[0.0; 2]; m * m * m];
+    for a in 0..m {
+        for b in 0..m {
+            for c in 0..m {
+                let node = [a, b, c].map(|a| frequency(a, m).abs());
+                let r = (node[0] * node[0] + node[1] * node[1] + node[2] * node[2]).sqrt();
+                green[index([a, b, c], m)][0] = if r == 0.0 {
+                    -1.0 / (PI.sqrt() * SPLIT)
+                } else {
+                    -libm::erf(r / (2.0 * SPLIT)) / r
+                };
+            }
+        }
+    }
+    fft::fft3(&mut green, m, false);
+    green.into_iter().map(|g| g[0]).collect()
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 { 1.0 } else { x.sin() / x }
+}
+
+// frequency of the index `a` of a transform of length `m`
+fn frequency(a: usize, m: usize) -> f64 {
+    if a < m / 2 {
+        a as f64
+    } else {
+        a as f64 - m as f64
+    }
+}
+
+fn index([a, b, c]: [usize; 3], m: usize) -> usize {
+    (a * m + b) * m + c
+}
+
+// the part of the pull of one body left to the pairs, 1 at d = 0
+fn short(r: f64, split: f64) -> f64 {
+    let u = r / (2.0 * split);
+    libm::erfc(u) + 2.0 * u / PI.sqrt() * (-u * u).exp()
+}
+
+// Bodies sorted into cells at least as wide as the reach of the pairs.
+struct Chain {
+    origin: Vec3,
+    width: f64,
+    side: usize,
+    periodic: bool,
+    cells: Vec<Vec<usize>>,
+}
+
+impl Chain {
+    fn new(bodies: &[Body], origin: Vec3, extent: f64, reach: f64, periodic: bool) -> Self {
+        let side = ((extent / reach) as usize).max(1);
+        let mut chain = Chain {
+            origin,
+            width: extent / side as f64,
+            side,
+            periodic,
+            cells: vec![Vec::new(); side * side * side],
+        };
+        for (i, body) in bodies.iter().enumerate() {
+            if let Some(cell) = chain.cell(body.r) {
+                let cell = chain.wrap(cell).unwrap();
+                chain.cells[index(cell, side)].push(i);
+            }
+        }
+        chain
+    }
+    fn cell(&self, r: Vec3) -> Option<[i64; 3]> {
+        let u = (r - self.origin) / self.width;
+        let cell = [u.x(), u.y(), u.z()].map(|u| u.floor() as i64);
+        self.wrap(cell).map(|_| cell)
+    }
+    fn wrap(&self, cell: [i64; 3]) -> Option<[usize; 3]> {
+        let side = self.side as i64;
+        let mut wrapped = [0; 3];
+        for (w, c) in wrapped.iter_mut().zip(cell) {
+            *w = match self.periodic {
+                true => c.rem_euclid(side) as usize,
+                false if (0..side).contains(&c) => c as usize,
+                false => return None,
+            };
+        }
+        Some(wrapped)
+    }
+    // the bodies of the cell of `r` and of its neighbours, each cell once
+    fn near(&self, r: Vec3) -> impl Iterator<Item = &usize> {
+        let mut cells = Vec::with_capacity(27);
+        if let Some([a, b, c]) = self.cell(r) {
+            for da in -1..=1 {
+                for db in -1..=1 {
+                    for dc in -1..=1 {
+                        if let Some(cell) = self.wrap([a + da, b + db, c + dc]) {
+                            cells.push(index(cell, self.side));
+                        }
+                    }
+                }
+            }
+        }
+        cells.sort_unstable();
+        cells.dedup();
+        cells.into_iter().flat_map(|cell| self.cells[cell].iter())
+    }
+}
+
+// Particle mesh gravity, built once per step. The masses go to the nodes
+// with cloud in cell weights, the potential comes from the transform of the
+// density times the Green function of the long range part, erf(r / 2rₛ) / r
+// with rₛ = 1.25 cells, and the accelerations from its four point
+// differences, back at the point with the same weights; about 1% off the
+// long range force. With the short range the pull of the bodies closer than
+// 5rₛ is summed with the close encounter method, less the long range the
+// mesh already has, otherwise gravity is smoothed over a few cells, which
+// suits collisionless systems. The pairs are found through cells rather than
+// the octree of TreePM, which `bima_rs` does not have yet. In a periodic box
+// the mesh covers it with a uniform background, as the Ewald sum does, else
+// it is fitted around the bodies and padded to twice its size, and points
+// off it feel every body directly.
+pub struct Mesh<'a> {
+    bodies: &'a [Body],
+    grid: Grid,
+    green: Option<&'a [f64]>,
+    h: f64,
+    split: f64,
+    origin: Vec3,
+    // acceleration at every node
+    acc: Vec<Vec3>,
+    chain: Option<Chain>,
+}
+
+impl<'a> Mesh<'a> {
+    pub fn new(bodies: &'a [Body], pm: &'a Pm) -> Self {
+        let grid = pm.grid;
+        let n = grid.cells;
+        let (origin, h) = match grid.box_size {
+            Some(size) => (Vec3::zero(), size / n as f64),
+            None => {
+                let first = bodies.first().map(|b| b.r).unwrap_or_default();
+                let (lo, hi) = bodies.iter().fold((first, first), |(lo, hi), b| {
+                    (
+                        Vec3::new(
+                            lo.x().min(b.r.x()),
+                            lo.y().min(b.r.y()),
+                            lo.z().min(b.r.z()),
+                        ),
+                        Vec3::new(
+                            hi.x().max(b.r.x()),
+                            hi.y().max(b.r.y()),
+                            hi.z().max(b.r.z()),
+                        ),
+                    )
+                });
+                let extent = hi - lo;
+                let side = extent.x().max(extent.y()).max(extent.z());
+                let side = if side > 0.0 { side } else { 1.0 };
+                let h = side / (n - 2 * MARGIN - 1) as f64;
+                (lo - Vec3::new(1.0, 1.0, 1.0) * (MARGIN as f64 * h), h)
+            }
+        };
+        let mut mesh = Mesh {
+            bodies,
+            grid,
+            green: pm.green.as_deref(),
+            h,
+            split: SPLIT * h,
+            origin,
+            acc: Vec::new(),
+            chain: None,
+        };
+        let potential = mesh.potential();
+        mesh.acc = mesh.differences(&potential);
+        if grid.short_range {
+            let reach = CUTOFF * mesh.split;
+            let periodic = grid.box_size.is_some();
+            mesh.chain = Some(Chain::new(bodies, origin, n as f64 * h, reach, periodic));
+        }
+        mesh
+    }
+    fn periodic(&self) -> bool {
+        self.grid.box_size.is_some()
+    }
+    // the eight nodes around `r` with their weights
+    fn stencil(&self, r: Vec3) -> impl Iterator<Item = ([usize; 3], f64)> + use<> {
+        let n = self.grid.cells;
+        let u = (r - self.origin) / self.h;
+        let u = match self.grid.box_size {
+            Some(_) => [u.x(), u.y(), u.z()].map(|u| u.rem_euclid(n as f64)),
+            None => [u.x(), u.y(), u.z()],
+        };
+        let i = u.map(|u| u.floor() as usize);
+        let f = [0, 1, 2].map(|c| u[c] - i[c] as f64);
+        (0..8).map(move |corner| {
+            let bit = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let node = [0, 1, 2].map(|c| (i[c] + bit[c]) % n);
+            let w = (0..3)
+                .map(|c| if bit[c] == 1 { f[c] } else { 1.0 - f[c] })
+                .product::<f64>();
+            (node, w)
+        })
+    }
+    // at the nodes, from the density on a transform twice as wide when
+    // isolated
+    fn potential(&self) -> Vec<f64> {
+        let n = self.grid.cells;
+        let m = if self.periodic() { n } else { 2 * n };
+        let h3 = self.h * self.h * self.h;
+        let mut density: Vec<Complex> = vec![[0.0; 2]; m * m * m];
+        for body in self.bodies.iter() {
+            for (node, w) in self.stencil(body.r) {
+                density[index(node, m)][0] += body.m * w / h3;
+            }
+        }
+        fft::fft3(&mut density, m, false);
+        let h2 = self.h * self.h;
+        let dk = 2.0 * PI / (m as f64 * self.h);
+        for a in 0..m {
+            for b in 0..m {
+                for c in 0..m {
+                    let k = [a, b, c].map(|a| dk * frequency(a, m));
+                    let k2 = k[0] * k[0] + k[1] * k[1] + k[2] * k[2];
+                    // the weights smooth twice, to the nodes and back
+                    let window = k.iter().map(|k| sinc(0.5 * k * self.h)).product::<f64>();
+                    let g = match self.green {
+                        Some(green) => [h2 * green[index([a, b, c], m)], 0.0],
+                        None if k2 == 0.0 => [0.0; 2],
+                        None => [-4.0 * PI / k2 * (-k2 * self.split * self.split).exp(), 0.0],
+                    };
+                    let g = [g[0] / window.powi(4), g[1] / window.powi(4)];
+                    let rho = &mut density[index([a, b, c], m)];
+                    *rho = [g[0] * rho[0] - g[1] * rho[1], g[0] * rho[1] + g[1] * rho[0]];
+                }
+            }
+        }
+        fft::fft3(&mut density, m, true);
+        let scale = (m * m * m) as f64;
+        let mut potential = vec![0.0; n * n * n];
+        for a in 0..n {
+            for b in 0..n {
+                for c in 0..n {
+                    potential[index([a, b, c], n)] = density[index([a, b, c], m)][0] / scale;
+                }
+            }
+        }
+        potential
+    }
+    // minus the gradient, zero on the two outer layers of an isolated mesh
+    fn differences(&self, potential: &[f64]) -> Vec<Vec3> {
+        let n = self.grid.cells;
+        let periodic = self.periodic();
+        let mut acc = vec![Vec3::zero(); n * n * n];
+        for a in 0..n {
+            for b in 0..n {
+                for c in 0..n {
+                    let node = [a, b, c];
+                    if !periodic && node.iter().any(|&i| i < 2 || i + 2 >= n) {
+                        continue;
+                    }
+                    let at = |axis: usize, shift: isize| {
+                        let mut node = node;
+                        node[axis] = (node[axis] as isize + shift).rem_euclid(n as isize) as usize;
+                        potential[index(node, n)]
+                    };
+                    let gradient = [0, 1, 2].map(|axis| {
+                        (4.0 / 3.0) * (at(axis, 1) - at(axis, -1)) / (2.0 * self.h)
+                            - (1.0 / 3.0) * (at(axis, 2) - at(axis, -2)) / (4.0 * self.h)
+                    });
+                    acc[index(node, n)] = Vec3::new(-gradient[0], -gradient[1], -gradient[2]);
+                }
+            }
+        }
+        acc
+    }
+    // whether the weights of `r` only reach nodes with an acceleration
+    fn contains(&self, r: Vec3) -> bool {
+        if self.periodic() {
+            return true;
+        }
+        let u = (r - self.origin) / self.h;
+        let inside = 2.0..(self.grid.cells - 3) as f64;
+        [u.x(), u.y(), u.z()].iter().all(|u| inside.contains(u))
+    }
+    fn offset(&self, d: Vec3) -> Vec3 {
+        match self.grid.box_size {
+            Some(l) => Vec3::new(
+                d.x() - l * (d.x() / l).round(),
+                d.y() - l * (d.y() / l).round(),
+                d.z() - l * (d.z() / l).round(),
+            ),
+            None => d,
+        }
+    }
+    // Acceleration at `r`, leaving out the body `skip`.
+    pub fn acc(&self, r: Vec3, skip: Option<usize>, close_encounter: &CloseEncounter) -> Vec3 {
+        if !self.contains(r) {
+            return self
+                .bodies
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| Some(*i) != skip)
+                .fold(Vec3::zero(), |total, (_, body)| {
+                    total + pair(body.m, body.r - r, close_encounter)
+                });
+        }
+        let mut total = self.stencil(r).fold(Vec3::zero(), |total, (node, w)| {
+            total + w * self.acc[index(node, self.grid.cells)]
+        });
+        // the mass of `skip` is on the mesh too, where it was at the start of
+        // the step
+        if let Some(i) = skip {
+            let body = &self.bodies[i];
+            let d = self.offset(body.r - r);
+            let distance = d.norm();
+            if distance > 0.0 {
+                let long = 1.0 - short(distance, self.split);
+                total -= long * pair(body.m, d, &CloseEncounter::Regularized);
+            }
+        }
+        if let Some(chain) = &self.chain {
+            let reach = CUTOFF * self.split;
+            for &i in chain.near(r) {
+                if Some(i) == skip {
+                    continue;
+                }
+                let body = &self.bodies[i];
+                let d = self.offset(body.r - r);
+                let distance = d.norm();
+                if distance < reach {
+                    let long = 1.0 - short(distance, self.split);
+                    total += pair(body.m, d, close_encounter)
+                        - long * pair(body.m, d, &CloseEncounter::Regularized);
+                }
+            }
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::ewald::Ewald;
+    use crate::simulation::force::field;
+
+    // spread out over a unit cube, the same on every run
+    fn cube(n: usize) -> Vec<Body> {
+        (0..n)
+            .map(|i| {
+                let f = i as f64;
+                let r = Vec3::new(
+                    (1.3 * f).sin().abs(),
+                    (0.7 * f + 1.0).cos().abs(),
+                    (2.9 * f).sin().abs(),
+                );
+                Body::new(i, 1.0 / n as f64, r, Vec3::zero(), None)
+            })
+            .collect()
+    }
+
+    // relative to the size of the expected accelerations
+    fn error(bodies: &[Body], mesh: &Mesh, expected: impl Fn(&Body) -> Vec3) -> f64 {
+        let close_encounter = CloseEncounter::Regularized;
+        let (mut error, mut norm) = (0.0, 0.0);
+        for body in bodies.iter() {
+            let expected = expected(body);
+            error += (mesh.acc(body.r, Some(body.id), &close_encounter) - expected).norm_2();
+            norm += expected.norm_2();
+        }
+        (error / norm).sqrt()
+    }
+
+    #[test]
+    fn pairs_and_mesh_add_up_to_the_direct_sum() {
+        let bodies = cube(400);
+        let grid = Grid {
+            cells: 32,
+            short_range: true,
+            box_size: None,
+        };
+        let pm = Pm::new(grid);
+        let mesh = Mesh::new(&bodies, &pm);
+        let close_encounter = CloseEncounter::Regularized;
+        let error = error(&bodies, &mesh, |body| {
+            let others: Vec<Body> = bodies.iter().filter(|b| b.id != body.id).cloned().collect();
+            field(body.r, &others, &close_encounter)
+        });
+        assert!(error < 5e-3, "error={}", error);
+    }
+
+    #[test]
+    fn far_bodies_pull_as_points() {
+        let bodies = vec![
+            Body::new(0, 1.0, Vec3::zero(), Vec3::zero(), None),
+            Body::new(1, 2.0, Vec3::new(3.0, 4.0, 0.0), Vec3::zero(), None),
+        ];
+        let grid = Grid {
+            cells: 64,
+            short_range: false,
+            box_size: None,
+        };
+        let pm = Pm::new(grid);
+        let mesh = Mesh::new(&bodies, &pm);
+        let a = mesh.acc(bodies[0].r, Some(0), &CloseEncounter::Regularized);
+        let expected = (2.0 / 25.0) * Vec3::new(0.6, 0.8, 0.0);
+        assert!(
+            (a - expected).norm() < 1e-2 * expected.norm(),
+            "{:?}",
+            a.to_tuple()
+        );
+        // off the mesh every body is summed directly
+        let r = Vec3::new(-10.0, 0.0, 0.0);
+        let direct = field(r, &bodies, &CloseEncounter::Regularized);
+        assert_eq!(mesh.acc(r, None, &CloseEncounter::Regularized), direct);
+    }
+
+    #[test]
+    fn periodic_mesh_follows_the_ewald_sum() {
+        let bodies = cube(400);
+        let grid = Grid {
+            cells: 32,
+            short_range: true,
+            box_size: Some(1.0),
+        };
+        let pm = Pm::new(grid);
+        let mesh = Mesh::new(&bodies, &pm);
+        let ewald = Ewald::new(1.0);
+        let close_encounter = CloseEncounter::Regularized;
+        let error = error(&bodies, &mesh, |body| {
+            ewald.acc(body.r, &bodies, Some(body.id), &close_encounter)
+        });
+        assert!(error < 5e-3, "error={}", error);
+    }
+}
@@ -0,0 +1,399 @@
+use crate::simulation::chain::{self, ChainErr};
+use crate::simulation::external::External;
+use crate::simulation::fmm::pair;
+use crate::simulation::ks;
+use bima_rs::body::Body;
+use bima_rs::close_encounter::CloseEncounter;
+use bima_rs::vec3::Vec3;
+use std::collections::HashMap;
+
+pub const COLUMNS: [&str; 6] = ["t", "body", "other", "separation", "start", "members"];
+
+// a group lets go of a pair this many times further than it takes it in
+const RELEASE: f64 = 2.0;
+
+// A pair of objects taken into a regularised group, or let go of, at `t`.
+// `members` is the size of the group, 2 for the transformation of
+// Kustaanheimo and Stiefel and more for a chain.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub t: f64,
+    pub body: usize,
+    pub other: usize,
+    pub separation: f64,
+    pub start: bool,
+    pub members: usize,
+}
+
+impl Entry {
+    pub fn to_vec(&self) -> Vec<f64> {
+        vec![
+            self.t,
+            self.body as f64,
+            self.other as f64,
+            self.separation,
+            if self.start { 1.0 } else { 0.0 },
+            self.members as f64,
+        ]
+    }
+}
+
+// Bodies moved together for one step, about their centre of mass.
+struct Group {
+    members: Vec<usize>,
+    m: Vec<f64>,
+    r: Vec<Vec3>,
+    v: Vec<Vec3>,
+}
+
+impl Group {
+    fn mass(&self) -> f64 {
+        self.m.iter().sum()
+    }
+    // Half a kick of the pull of everything else on the members, less its
+    // pull on the centre of mass at `cm`.
+    fn kick<'a>(
+        &mut self,
+        dt: f64,
+        cm: Vec3,
+        others: impl Iterator<Item = &'a Body> + Clone,
+        external: &External,
+        close_encounter: &CloseEncounter,
+    ) {
+        let field = |r: Vec3| {
+            let mut a = others.clone().fold(Vec3::zero(), |a, body| {
+                a + pair(body.m, body.r - r, close_encounter)
+            });
+            if !external.is_empty() {
+                a += external.acc(r);
+            }
+            a
+        };
+        let centre = field(cm);
+        let tidal: Vec<Vec3> = self.r.iter().map(|r| field(cm + *r) - centre).collect();
+        let mean = tidal
+            .iter()
+            .zip(self.m.iter())
+            .fold(Vec3::zero(), |mean, (a, m)| mean + *m * *a)
+            / self.mass();
+        for (v, a) in self.v.iter_mut().zip(tidal) {
+            *v += (0.5 * dt) * (a - mean);
+        }
+    }
+    // the motion inside the group, without anything else
+    fn advance(&mut self, dt: f64) -> Result<(), ChainErr> {
+        if self.members.len() == 2 {
+            let m = self.mass();
+            let (r, v) = ks::kepler(m, self.r[1] - self.r[0], self.v[1] - self.v[0], dt);
+            let (f0, f1) = (self.m[1] / m, self.m[0] / m);
+            self.r = vec![-f0 * r, f1 * r];
+            self.v = vec![-f0 * v, f1 * v];
+            Ok(())
+        } else {
+            chain::advance(&self.m, &mut self.r, &mut self.v, dt)
+        }
+    }
+    // acceleration of the member `k` from the others
+    fn acc(&self, k: usize) -> Vec3 {
+        (0..self.m.len())
+            .filter(|j| *j != k)
+            .fold(Vec3::zero(), |a, j| {
+                a + pair(
+                    self.m[j],
+                    self.r[j] - self.r[k],
+                    &CloseEncounter::Regularized,
+                )
+            })
+    }
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a.x() * b.x() + a.y() * b.y() + a.z() * b.z()
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[i] = root;
+    root
+}
+
+// Regularisation of close encounters. At the start of a step the bodies
+// coming within `radius` of each other during it are gathered into groups,
+// which stay together until they are `RELEASE` times further apart, and each
+// group takes the place of its members as one body at their centre of mass.
+// After the step a pair is moved as an orbit in the variables of
+// Kustaanheimo and Stiefel and a larger group with the chain, between half
+// kicks of the tides of the rest of the system, so the motion inside the
+// group does not depend on the timestep.
+pub struct Regular {
+    radius: f64,
+    // the object ids of the pairs in a group, with its size
+    together: HashMap<(usize, usize), usize>,
+    groups: Vec<Group>,
+    // the bodies left in the system for the step
+    kept: Vec<usize>,
+}
+
+impl Regular {
+    pub fn new(radius: f64) -> Self {
+        Regular {
+            radius,
+            together: HashMap::new(),
+            groups: Vec::new(),
+            kept: Vec::new(),
+        }
+    }
+    // Takes the groups out of `bodies` for a step of `dt` from `t`, `ids`
+    // being the objects the bodies belong to. Returns the pairs taken in or
+    // let go of since the last step, those with a body that left the system
+    // aside.
+    pub fn split(
+        &mut self,
+        t: f64,
+        dt: f64,
+        bodies: &mut Vec<Body>,
+        ids: &[usize],
+        external: &External,
+        close_encounter: &CloseEncounter,
+    ) -> Vec<Entry> {
+        let n = bodies.len();
+        let key = |i: usize, j: usize| (ids[i].min(ids[j]), ids[i].max(ids[j]));
+        let mut parent: Vec<usize> = (0..n).collect();
+        for i in 0..n {
+            for j in i + 1..n {
+                let (d, dv) = (bodies[j].r - bodies[i].r, bodies[j].v - bodies[i].v);
+                // closest during the step, in a straight line, or now for a
+                // pair at rest with each other
+                let along = if dv.norm_2() > 0.0 {
+                    (-dot(d, dv) / dv.norm_2()).clamp(0.0, dt)
+                } else {
+                    0.0
+                };
+                let closest = (d + along * dv).norm();
+                let kept = self.together.contains_key(&key(i, j));
+                let linked = closest < self.radius || (kept && d.norm() < RELEASE * self.radius);
+                if linked {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[a] = b;
+                }
+            }
+        }
+        let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+        for i in 0..n {
+            let root = find(&mut parent, i);
+            members.entry(root).or_default().push(i);
+        }
+        let mut groups: Vec<Vec<usize>> = members.into_values().filter(|g| g.len() > 1).collect();
+        groups.sort();
+        let separation = |i: usize, j: usize| (bodies[j].r - bodies[i].r).norm();
+        let mut together = HashMap::new();
+        let mut entries = Vec::new();
+        for group in groups.iter() {
+            for (a, &i) in group.iter().enumerate() {
+                for &j in group[a + 1..].iter() {
+                    let key = key(i, j);
+                    together.insert(key, group.len());
+                    if !self.together.contains_key(&key) {
+                        entries.push(Entry {
+                            t,
+                            body: key.0,
+                            other: key.1,
+                            separation: separation(i, j),
+                            start: true,
+                            members: group.len(),
+                        });
+                    }
+                }
+            }
+        }
+        let index: HashMap<usize, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut released: Vec<_> = self
+            .together
+            .iter()
+            .filter(|(key, _)| !together.contains_key(key))
+            .filter_map(|(key, members)| {
+                let (i, j) = (index.get(&key.0)?, index.get(&key.1)?);
+                Some(Entry {
+                    t,
+                    body: key.0,
+                    other: key.1,
+                    separation: separation(*i, *j),
+                    start: false,
+                    members: *members,
+                })
+            })
+            .collect();
+        released.sort_by_key(|e| (e.body, e.other));
+        entries.extend(released);
+        self.together = together;
+        if groups.is_empty() {
+            return entries;
+        }
+        let mut grouped = vec![false; n];
+        self.groups = groups
+            .into_iter()
+            .map(|members| {
+                for &i in members.iter() {
+                    grouped[i] = true;
+                }
+                let m: Vec<f64> = members.iter().map(|&i| bodies[i].m).collect();
+                let total: f64 = m.iter().sum();
+                let centre = |f: &dyn Fn(&Body) -> Vec3| {
+                    members
+                        .iter()
+                        .fold(Vec3::zero(), |c, &i| c + bodies[i].m * f(&bodies[i]))
+                        / total
+                };
+                let (cm, cv) = (centre(&|b| b.r), centre(&|b| b.v));
+                Group {
+                    r: members.iter().map(|&i| bodies[i].r - cm).collect(),
+                    v: members.iter().map(|&i| bodies[i].v - cv).collect(),
+                    members,
+                    m,
+                }
+            })
+            .collect();
+        for group in self.groups.iter_mut() {
+            let cm = bodies[group.members[0]].r - group.r[0];
+            let members = group.members.clone();
+            let others = bodies
+                .iter()
+                .enumerate()
+                .filter(move |(i, _)| !members.contains(i))
+                .map(|(_, body)| body);
+            group.kick(dt, cm, others, external, close_encounter);
+        }
+        self.kept = (0..n).filter(|i| !grouped[*i]).collect();
+        let mut reduced: Vec<Body> = self.kept.iter().map(|&i| bodies[i].clone()).collect();
+        for group in self.groups.iter() {
+            let first = &bodies[group.members[0]];
+            let (cm, cv) = (first.r - group.r[0], first.v - group.v[0]);
+            reduced.push(Body::new(0, group.mass(), cm, cv, None));
+        }
+        for (i, body) in reduced.iter_mut().enumerate() {
+            body.id = i;
+        }
+        *bodies = reduced;
+        entries
+    }
+    // Puts the members back in place of their group after the step of `dt`,
+    // in the order of the bodies before `split`.
+    pub fn join(
+        &mut self,
+        dt: f64,
+        bodies: &mut Vec<Body>,
+        external: &External,
+        close_encounter: &CloseEncounter,
+    ) -> Result<(), ChainErr> {
+        if self.groups.is_empty() {
+            return Ok(());
+        }
+        let n = self.kept.len() + self.groups.iter().map(|g| g.members.len()).sum::<usize>();
+        let mut full = vec![Body::empty(); n];
+        for (k, &i) in self.kept.iter().enumerate() {
+            full[i] = bodies[k].clone();
+        }
+        for (g, group) in self.groups.iter_mut().enumerate() {
+            let centre = &bodies[self.kept.len() + g];
+            group.advance(dt)?;
+            let others = bodies
+                .iter()
+                .enumerate()
+                .filter(|(k, _)| *k != self.kept.len() + g)
+                .map(|(_, body)| body);
+            group.kick(dt, centre.r, others, external, close_encounter);
+            for (k, &i) in group.members.iter().enumerate() {
+                let a = centre.a + group.acc(k);
+                full[i] = Body::new(
+                    i,
+                    group.m[k],
+                    centre.r + group.r[k],
+                    centre.v + group.v[k],
+                    Some(a),
+                );
+            }
+        }
+        for (i, body) in full.iter_mut().enumerate() {
+            body.id = i;
+        }
+        self.groups.clear();
+        *bodies = full;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::cm::Barycentre;
+
+    #[test]
+    fn logs_the_pairs_it_takes_in_and_lets_go() {
+        // two bodies flying past each other, a third far away
+        let mut bodies = vec![
+            Body::new(
+                0,
+                1.0,
+                Vec3::new(-1.0, 0.05, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                None,
+            ),
+            Body::new(
+                1,
+                1.0,
+                Vec3::new(1.0, -0.05, 0.0),
+                Vec3::new(-1.0, 0.0, 0.0),
+                None,
+            ),
+            Body::new(2, 1e-3, Vec3::new(0.0, 50.0, 0.0), Vec3::zero(), None),
+        ];
+        let ids = [4, 7, 9];
+        let external = External::new(Vec::new(), Barycentre::from_bodies(&bodies).unwrap());
+        let close_encounter = CloseEncounter::Regularized;
+        let mut regular = Regular::new(0.2);
+        let dt = 0.5;
+        let mut entries = Vec::new();
+        for step in 0..8 {
+            let t = step as f64 * dt;
+            entries.extend(regular.split(t, dt, &mut bodies, &ids, &external, &close_encounter));
+            // nothing else moves them
+            regular
+                .join(dt, &mut bodies, &external, &close_encounter)
+                .unwrap();
+            if regular.together.is_empty() {
+                for body in bodies.iter_mut() {
+                    body.r += dt * body.v;
+                }
+            }
+        }
+        assert_eq!(entries.len(), 2, "{:?}", entries);
+        let (start, end) = (&entries[0], &entries[1]);
+        assert!(start.start && !end.start);
+        assert_eq!((start.body, start.other, start.members), (4, 7, 2));
+        assert!(end.separation > RELEASE * 0.2);
+        assert_eq!(bodies.len(), 3);
+    }
+
+    #[test]
+    fn takes_in_a_pair_at_rest() {
+        let mut bodies = vec![
+            Body::new(0, 1.0, Vec3::new(-0.1, 0.0, 0.0), Vec3::zero(), None),
+            Body::new(1, 1.0, Vec3::new(0.1, 0.0, 0.0), Vec3::zero(), None),
+        ];
+        let external = External::new(Vec::new(), Barycentre::from_bodies(&bodies).unwrap());
+        let mut regular = Regular::new(0.5);
+        let entries = regular.split(
+            0.0,
+            0.1,
+            &mut bodies,
+            &[0, 1],
+            &external,
+            &CloseEncounter::Regularized,
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(bodies.len(), 1);
+    }
+}
@@ -0,0 +1,132 @@
+use bima_rs::body::Body;
+use bima_rs::vec3::Vec3;
+use wide::f64x4;
+
+const LANES: usize = 4;
+
+// Masses and positions of the bodies, one array per component, so that the
+// direct sum runs over `LANES` bodies at a time.
+#[derive(Clone, Debug, Default)]
+pub struct Particles {
+    pub m: Vec<f64>,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub z: Vec<f64>,
+}
+
+impl Particles {
+    pub fn from_bodies(bodies: &[Body]) -> Self {
+        let mut particles = Particles {
+            m: Vec::with_capacity(bodies.len()),
+            x: Vec::with_capacity(bodies.len()),
+            y: Vec::with_capacity(bodies.len()),
+            z: Vec::with_capacity(bodies.len()),
+        };
+        for body in bodies.iter() {
+            particles.m.push(body.m);
+            particles.x.push(body.r.x());
+            particles.y.push(body.r.y());
+            particles.z.push(body.r.z());
+        }
+        particles
+    }
+    pub fn len(&self) -> usize {
+        self.m.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.m.is_empty()
+    }
+    // Acceleration at `r` with the softened force of `bima_rs::force::gravity`,
+    // m / (d² + ε²) along d, leaving out the body `skip`. Rounds differently
+    // from the scalar sum.
+    pub fn soften(&self, r: Vec3, eps: f64, skip: Option<usize>) -> Vec3 {
+        let eps2 = eps * eps;
+        let (rx, ry, rz) = (
+            f64x4::splat(r.x()),
+            f64x4::splat(r.y()),
+            f64x4::splat(r.z()),
+        );
+        let eps2_x4 = f64x4::splat(eps2);
+        let (mut ax, mut ay, mut az) = (f64x4::ZERO, f64x4::ZERO, f64x4::ZERO);
+        let n = self.len() / LANES * LANES;
+        for i in (0..n).step_by(LANES) {
+            let lanes =
+                |v: &[f64]| f64x4::from(<[f64; LANES]>::try_from(&v[i..i + LANES]).unwrap());
+            let dx = lanes(&self.x) - rx;
+            let dy = lanes(&self.y) - ry;
+            let dz = lanes(&self.z) - rz;
+            let d2 = dx * dx + dy * dy + dz * dz;
+            let mut s = lanes(&self.m) / ((d2 + eps2_x4) * d2.sqrt());
+            if let Some(j) = skip.filter(|j| (i..i + LANES).contains(j)) {
+                let mut s_lanes = s.to_array();
+                s_lanes[j - i] = 0.0;
+                s = f64x4::from(s_lanes);
+            }
+            ax += s * dx;
+            ay += s * dy;
+            az += s * dz;
+        }
+        let mut total = Vec3::new(ax.reduce_add(), ay.reduce_add(), az.reduce_add());
+        for j in n..self.len() {
+            if skip == Some(j) {
+                continue;
+            }
+            let d = Vec3::new(self.x[j] - r.x(), self.y[j] - r.y(), self.z[j] - r.z());
+            let d2 = d.norm_2();
+            total += (self.m[j] / ((d2 + eps2) * d2.sqrt())) * d;
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bima_rs::close_encounter::CloseEncounter;
+    use bima_rs::force;
+
+    // spread out bodies of unequal masses
+    fn bodies(n: usize) -> Vec<Body> {
+        (0..n)
+            .map(|i| {
+                let f = i as f64;
+                let r = Vec3::new(f.sin() * 3.0, (1.7 * f).cos() * 2.0, 0.1 * f - 0.4);
+                Body::new(i, 1.0 + 0.25 * f, r, Vec3::zero(), None)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn agrees_with_the_scalar_sum() {
+        let eps = 0.05;
+        for n in [1, 3, 4, 7, 16, 33] {
+            let bodies = bodies(n);
+            let particles = Particles::from_bodies(&bodies);
+            for body in bodies.iter() {
+                let expected = force::direct(body, &bodies, &CloseEncounter::Soften(eps), None);
+                let a = particles.soften(body.r, eps, Some(body.id));
+                let error = (a - expected).norm();
+                assert!(
+                    error <= 1e-12 * expected.norm().max(1.0),
+                    "n={} error={}",
+                    n,
+                    error
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn leaves_out_only_the_skipped_body() {
+        let bodies = bodies(6);
+        let particles = Particles::from_bodies(&bodies);
+        let r = Vec3::new(10.0, 0.0, 0.0);
+        let all = particles.soften(r, 0.0, None);
+        for body in bodies.iter() {
+            let d = body.r - r;
+            let one = (body.m / d.norm_2()) * d.hat();
+            let without = particles.soften(r, 0.0, Some(body.id));
+            assert!((all - without - one).norm() < 1e-14);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::simulation::block::Block;
+use crate::simulation::ewald::Ewald;
+use crate::simulation::external::External;
+use crate::simulation::fmm::Tree;
+use crate::simulation::force;
+use crate::simulation::nongravity::NonGravity;
+use crate::simulation::pm::{Mesh, Pm};
+use crate::simulation::soa::Particles;
+use bima_rs::body::Body;
+use bima_rs::close_encounter::CloseEncounter;
+use bima_rs::force::ForceMethod;
+use bima_rs::integrator::{self, Integrator, leap_frog};
+use bima_rs::system::System;
+use bima_rs::timestep::calc_wdot;
+use bima_rs::vec3::Vec3;
+use bima_rs::vec6::Vec6;
+use rayon::ThreadPool;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+// How the gravity of the bodies is computed.
+pub struct Solver {
+    // expansion order of the fast multipole method
+    pub fmm: Option<usize>,
+    // the sum over the images of a periodic box
+    pub ewald: Option<Ewald>,
+    // the particle mesh
+    pub pm: Option<Pm>,
+    // hierarchical block timesteps, run by `integrate` instead of the steps
+    // of the `System`
+    pub block: Option<Block>,
+    // radius of the regularisation of close encounters
+    pub regular: Option<f64>,
+    // threads the bodies are spread over
+    pub pool: Option<ThreadPool>,
+}
+
+// Where the gravity of the bodies comes from during a step.
+enum Gravity<'a> {
+    Fmm(Tree<'a>),
+    Ewald(&'a Ewald),
+    Mesh(Mesh<'a>),
+    Soften(Particles, f64),
+    // `calc_wdot` for the bodies, `force::field` for the test particles
+    Pairs,
+}
+
+fn solve<F: FnMut(Vec6, bool) -> Vec6>(
+    solve: &Integrator,
+    w: Vec6,
+    dt: f64,
+    wdot_func: F,
+) -> integrator::Solution {
+    match solve {
+        Integrator::Euler => integrator::euler(w, dt, wdot_func),
+        Integrator::RK4 => integrator::rk4(w, dt, wdot_func),
+        Integrator::BS => integrator::bs(w, dt, wdot_func),
+        Integrator::LeapFrog(state) => integrator::lf(w, dt, wdot_func, *state),
+    }
+}
+
+// Same as `bima_rs::timestep::constant_step`, with the external field and the
+// non-gravitational forces added to the acceleration of every body. The test
+// particles are moved in the field of the bodies at the start of the step,
+// their `id` is the id of the object they belong to.
+//
+// Every body is stepped on its own against the bodies at the start of the
+// step. With a thread pool the pairs are not cached, so the result does not
+// depend on how the bodies are split over the threads. Run serially the
+// first stage takes the pairs from the cache of the `System`, as `bima_rs`
+// does, the pull of a pair being the same either way round. With the fast
+// multipole method its tree is built from the bodies at the start of the
+// step, and so is the particle mesh, the Ewald sum replaces the pairs in a
+// periodic box, otherwise the direct sum with softening goes through the
+// SIMD kernel of `Particles`.
+pub fn constant_step(
+    system: &mut System,
+    dt: f64,
+    tmp: &mut Vec<Body>,
+    tests: &mut [Body],
+    external: &External,
+    nongravity: Option<&NonGravity>,
+    solver: &Solver,
+) -> bool {
+    let n = system.bodies.len();
+    let bodies = &system.bodies;
+    let integrator = &system.integrator;
+    let force_method = &system.force_method;
+    let close_encounter = &system.close_encounter;
+    let gravity = if let Some(order) = solver.fmm {
+        Gravity::Fmm(Tree::new(bodies, order))
+    } else if let Some(ewald) = &solver.ewald {
+        Gravity::Ewald(ewald)
+    } else if let Some(pm) = &solver.pm {
+        Gravity::Mesh(Mesh::new(bodies, pm))
+    } else if let (ForceMethod::Direct, CloseEncounter::Soften(eps)) =
+        (force_method, close_encounter)
+    {
+        Gravity::Soften(Particles::from_bodies(bodies), *eps)
+    } else {
+        Gravity::Pairs
+    };
+    let gravity = &gravity;
+    let step_test = |test: &mut Body| {
+        let wdot_func = |w: Vec6, _: bool| {
+            let mut a = match gravity {
+                Gravity::Fmm(tree) => tree.acc(w.r, None, close_encounter),
+                Gravity::Ewald(ewald) => ewald.acc(w.r, bodies, None, close_encounter),
+                Gravity::Mesh(mesh) => mesh.acc(w.r, None, close_encounter),
+                Gravity::Soften(particles, eps) => particles.soften(w.r, *eps, None),
+                Gravity::Pairs => force::field(w.r, bodies, close_encounter),
+            };
+            if !external.is_empty() {
+                a += external.acc(w.r);
+            }
+            if let Some(nongravity) = nongravity {
+                a += nongravity.acc(test.id, w, bodies);
+            }
+            Vec6::new(w.v, a)
+        };
+        let (w_new, a_new) = solve(integrator, test.to_vec6(), dt, wdot_func).unzip();
+        test.r = w_new.r;
+        test.v = w_new.v;
+        test.a = a_new.unwrap_or(test.a);
+    };
+    let step_body = |id: usize, mut shared: Option<&mut HashMap<(usize, usize), Vec3>>| {
+        let body = &bodies[id];
+        let m = body.m;
+        let mut own = HashMap::new();
+        let wdot_func = |w: Vec6, first: bool| {
+            let mut wdot = match gravity {
+                Gravity::Fmm(tree) => Vec6::new(w.v, tree.acc(w.r, Some(id), close_encounter)),
+                Gravity::Ewald(ewald) => {
+                    Vec6::new(w.v, ewald.acc(w.r, bodies, Some(id), close_encounter))
+                }
+                Gravity::Mesh(mesh) => Vec6::new(w.v, mesh.acc(w.r, Some(id), close_encounter)),
+                Gravity::Soften(particles, eps) => {
+                    Vec6::new(w.v, particles.soften(w.r, *eps, Some(id)))
+                }
+                Gravity::Pairs => {
+                    let dummy = Body::new(id, m, w.r, w.v, None);
+                    let (cache, use_cache) = match shared.as_deref_mut() {
+                        Some(cache) => (cache, first),
+                        None => (&mut own, false),
+                    };
+                    calc_wdot(
+                        &dummy,
+                        bodies,
+                        force_method,
+                        close_encounter,
+                        cache,
+                        use_cache,
+                    )
+                }
+            };
+            if !external.is_empty() {
+                wdot.v += external.acc(w.r);
+            }
+            if let Some(nongravity) = nongravity {
+                wdot.v += nongravity.acc(nongravity.id(id), w, bodies);
+            }
+            wdot
+        };
+        let (w_new, a_new) = solve(integrator, body.to_vec6(), dt, wdot_func).unzip();
+        Body::new(id, m, w_new.r, w_new.v, a_new.or(Some(body.a)))
+    };
+    match &solver.pool {
+        Some(pool) => pool.install(|| {
+            tests.par_iter_mut().for_each(step_test);
+            (0..n)
+                .into_par_iter()
+                .map(|id| step_body(id, None))
+                .collect_into_vec(tmp);
+        }),
+        None => {
+            let cache = &mut system.cache;
+            tests.iter_mut().for_each(step_test);
+            tmp.extend((0..n).map(|id| step_body(id, Some(&mut *cache))));
+        }
+    }
+    let mut proceed = true;
+    if let Integrator::LeapFrog(state) = &mut system.integrator {
+        state.next();
+        proceed = *state == leap_frog::State::SecondDrift;
+    }
+    system.clear_cache();
+    system.bodies = std::mem::take(tmp);
+    proceed
+}
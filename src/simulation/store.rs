@@ -1,27 +1,42 @@
-use bima_rs::cm::CM;
+use crate::simulation::cm::Barycentre;
+use crate::units::UnitSystem;
 use bima_rs::record::line::Line;
+use hdf5::types::VarLenUnicode;
 use hdf5::{self, File, Group};
+#[cfg(feature = "python")]
 use pyo3::{PyErr, exceptions::PyValueError};
+use std::fmt;
 use std::fs::metadata;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct Store {
-    file: File,
+    // the file, or the group of one run in a file shared by an ensemble
+    root: Group,
     pub path: PathBuf,
     counters: Vec<usize>,
+    // group of every object, test particles are kept apart from the bodies
+    paths: Vec<String>,
 }
 
+#[derive(Debug)]
 pub enum StoreErr {
     Hdf5Err(hdf5::Error),
     AlreadyExists,
 }
 
+impl fmt::Display for StoreErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreErr::AlreadyExists => write!(f, "File already exist"),
+            StoreErr::Hdf5Err(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
 impl From<StoreErr> for PyErr {
     fn from(value: StoreErr) -> Self {
-        match value {
-            StoreErr::AlreadyExists => PyValueError::new_err("File already exist"),
-            StoreErr::Hdf5Err(e) => PyValueError::new_err(e.to_string()),
-        }
+        PyValueError::new_err(value.to_string())
     }
 }
 
@@ -31,27 +46,68 @@ impl From<hdf5::Error> for StoreErr {
     }
 }
 
+// Creates an empty file, unless it exists and `replace` is false.
+pub fn create_file(path: &Path, replace: bool) -> Result<File, StoreErr> {
+    if let Ok(metadata) = metadata(path) {
+        if !replace && metadata.is_file() {
+            return Err(StoreErr::AlreadyExists);
+        }
+    }
+    Ok(File::create(path)?)
+}
+
 impl Store {
     pub fn new(
         path: PathBuf,
         n_objects: usize,
         m: Vec<f64>,
+        units: &UnitSystem,
         replace: bool,
         save_acc: bool,
     ) -> Result<Self, StoreErr> {
-        if let Ok(metadata) = metadata(&path) {
-            if !replace && metadata.is_file() {
-                return Err(StoreErr::AlreadyExists);
-            }
-        }
-        let file = File::create(&path)?;
-        for obj_id in 0..n_objects {
-            let obj_group = file.create_group(&format!("objects/{}", obj_id))?;
+        let file = create_file(&path, replace)?;
+        Store::with_root(Group::clone(&file), path, n_objects, m, units, save_acc)
+    }
+    // One run of an ensemble, written under the new group `group` of `file`
+    // with the same layout as a file of its own.
+    pub fn in_group(
+        file: &File,
+        path: PathBuf,
+        group: &str,
+        n_objects: usize,
+        m: Vec<f64>,
+        units: &UnitSystem,
+        save_acc: bool,
+    ) -> Result<Self, StoreErr> {
+        let root = file.create_group(group)?;
+        Store::with_root(root, path, n_objects, m, units, save_acc)
+    }
+    fn with_root(
+        root: Group,
+        path: PathBuf,
+        n_objects: usize,
+        m: Vec<f64>,
+        units: &UnitSystem,
+        save_acc: bool,
+    ) -> Result<Self, StoreErr> {
+        store_units(&root, units)?;
+        let mut paths = Vec::with_capacity(n_objects);
+        let (mut n_massive, mut n_test) = (0, 0);
+        for m in m.iter().take(n_objects) {
+            let obj_path = if *m != 0.0 {
+                n_massive += 1;
+                format!("objects/{}", n_massive - 1)
+            } else {
+                n_test += 1;
+                format!("test_particles/{}", n_test - 1)
+            };
+            let obj_group = root.create_group(&obj_path)?;
+            paths.push(obj_path);
             obj_group
                 .new_dataset::<f64>()
                 .shape(1)
                 .create("m")?
-                .write(&[m[obj_id]])?;
+                .write(&[*m])?;
             obj_group.create_group("t")?;
             obj_group.create_group("x")?;
             obj_group.create_group("y")?;
@@ -66,16 +122,17 @@ impl Store {
             }
         }
         Ok(Store {
-            file,
+            root,
             path,
             counters: vec![0; n_objects],
+            paths,
         })
     }
-    pub fn append(&mut self, obj_id: usize, lines: Vec<Line>, cm: &CM) -> hdf5::Result<()> {
+    pub fn append(&mut self, obj_id: usize, lines: Vec<Line>, cm: &Barycentre) -> hdf5::Result<()> {
         // for chunk in lines.chunks(65536) {
         let chunk_id = self.counters[obj_id];
         let (t, x, y, z, vx, vy, vz, ax, ay, az) = unpack(&lines, &cm);
-        let obj_g = self.file.group(&format!("objects/{}", obj_id))?;
+        let obj_g = self.root.group(&self.paths[obj_id])?;
         store_dataset(&obj_g, "t", chunk_id, t)?;
         store_dataset(&obj_g, "x", chunk_id, x)?;
         store_dataset(&obj_g, "y", chunk_id, y)?;
@@ -96,6 +153,42 @@ impl Store {
         // }
         Ok(())
     }
+    // one dataset per column
+    pub fn store_table(
+        &mut self,
+        name: &str,
+        columns: &[&str],
+        rows: &[Vec<f64>],
+    ) -> hdf5::Result<()> {
+        let group = self.root.create_group(name)?;
+        for (c, name) in columns.iter().enumerate() {
+            let value: Vec<f64> = rows.iter().map(|row| row[c]).collect();
+            group
+                .new_dataset::<f64>()
+                .shape(value.len())
+                .create(*name)?
+                .write(value.as_slice())?;
+        }
+        Ok(())
+    }
+}
+
+// SI value of every unit, to convert the stored data back
+fn store_units(root: &Group, units: &UnitSystem) -> hdf5::Result<()> {
+    if let Ok(name) = units.name.parse::<VarLenUnicode>() {
+        root.new_attr::<VarLenUnicode>()
+            .create("units")?
+            .write_scalar(&name)?;
+    }
+    for (name, value) in [
+        ("length", units.length),
+        ("mass", units.mass),
+        ("time", units.time),
+        ("G", units.g),
+    ] {
+        root.new_attr::<f64>().create(name)?.write_scalar(&value)?;
+    }
+    Ok(())
 }
 
 fn store_dataset(obj_g: &Group, name: &str, chunk_id: usize, value: Vec<f64>) -> hdf5::Result<()> {
@@ -108,7 +201,7 @@ fn store_dataset(obj_g: &Group, name: &str, chunk_id: usize, value: Vec<f64>) ->
 
 fn unpack(
     lines: &[Line],
-    cm: &CM,
+    cm: &Barycentre,
 ) -> (
     Vec<f64>,
     Vec<f64>,
@@ -144,12 +237,12 @@ fn unpack(
         (None, None, None)
     };
 
-    for line in lines.into_iter() {
+    for line in lines.iter().map(|line| cm.line(line)) {
         t.push(line.t);
 
-        x.push(line.r.x() + cm.x());
-        y.push(line.r.y() + cm.y());
-        z.push(line.r.z() + cm.z());
+        x.push(line.r.x());
+        y.push(line.r.y());
+        z.push(line.r.z());
 
         vx.push(line.v.x());
         vy.push(line.v.y());
@@ -165,3 +258,85 @@ fn unpack(
 
     (t, x, y, z, vx, vy, vz, ax, ay, az)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load;
+    use bima_rs::vec3::Vec3;
+    use std::fs;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bima-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn lines(t: &[f64], x: f64) -> Vec<Line> {
+        t.iter()
+            .map(|t| Line::new(*t, Vec3::new(x, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), None))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_an_existing_file() {
+        let path = temp_path("exists.h5");
+        let units = UnitSystem::nbody();
+        Store::new(path.clone(), 1, vec![1.0], &units, false, false).unwrap();
+        let again = Store::new(path.clone(), 1, vec![1.0], &units, false, false);
+        assert!(matches!(again, Err(StoreErr::AlreadyExists)));
+        assert!(Store::new(path, 1, vec![1.0], &units, true, false).is_ok());
+    }
+
+    #[test]
+    fn reads_back_the_chunks_in_order() {
+        let path = temp_path("chunks.h5");
+        let units = UnitSystem::au_msun_yr().unwrap();
+        let cm = Barycentre {
+            r: Vec3::new(1.0, 0.0, 0.0),
+            v: Vec3::new(0.0, 0.0, 2.0),
+            t: 0.0,
+        };
+        {
+            let mut store =
+                Store::new(path.clone(), 2, vec![3.0, 0.0], &units, false, false).unwrap();
+            store.append(0, lines(&[0.0, 1.0], 0.5), &cm).unwrap();
+            store.append(1, lines(&[0.0, 1.0], -0.5), &cm).unwrap();
+            store.append(0, lines(&[2.0], 0.25), &cm).unwrap();
+            store.append(1, lines(&[2.0], -0.25), &cm).unwrap();
+            assert_eq!(store.paths, vec!["objects/0", "test_particles/0"]);
+        }
+        let (bodies, t) = load::read_result(path.to_str().unwrap(), -1).unwrap();
+        assert_eq!(t, 2.0);
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies[0].m, 3.0);
+        assert_eq!(bodies[1].m, 0.0);
+        // the barycentre has moved along z for 2 time units
+        assert_eq!(bodies[0].r.x(), 1.25);
+        assert_eq!(bodies[0].r.z(), 4.0);
+        assert_eq!(bodies[1].r.x(), 0.75);
+        assert_eq!(bodies[0].v.z(), 2.0);
+        let (_, t) = load::read_result(path.to_str().unwrap(), 1).unwrap();
+        assert_eq!(t, 1.0);
+        let file = File::open(&path).unwrap();
+        let g = file.attr("G").unwrap().read_scalar::<f64>().unwrap();
+        assert_eq!(g, units.g);
+    }
+
+    #[test]
+    fn stores_a_table_by_column() {
+        let path = temp_path("table.h5");
+        let mut store = Store::new(path, 1, vec![1.0], &UnitSystem::nbody(), false, false).unwrap();
+        let rows = vec![vec![0.0, 1.0], vec![2.0, 3.0]];
+        store.store_table("escapes", &["id", "t"], &rows).unwrap();
+        let t = store
+            .root
+            .dataset("escapes/t")
+            .unwrap()
+            .read_raw::<f64>()
+            .unwrap();
+        assert_eq!(t, vec![1.0, 3.0]);
+    }
+}
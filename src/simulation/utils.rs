@@ -1,27 +1,153 @@
+use crate::simulation::block::{Block, Scheme};
+use crate::simulation::boundary::Boundary;
+use crate::simulation::event::{Condition, Event};
+use crate::simulation::external::Potential;
+use crate::simulation::nongravity::NonGravity;
+use crate::simulation::pm::Grid;
 use bima_rs::close_encounter::CloseEncounter;
 use bima_rs::force::ForceMethod;
 use bima_rs::integrator::Integrator;
 use bima_rs::timestep::TimestepMethod;
-use pyo3::{exceptions::PyValueError, prelude::*};
+use bima_rs::vec3::Vec3;
+#[cfg(feature = "python")]
+use pyo3::{PyErr, exceptions::PyValueError};
+use std::fmt;
 
-pub struct ForceMethodErr;
+pub enum ForceMethodErr {
+    Invalid,
+    InvalidOrder,
+    // Ewald without a box
+    NoBox,
+    // a box for a force method that is not periodic
+    NotPeriodic,
+    InvalidBox,
+    InvalidCells,
+    // an isolated mesh over MAX_ISOLATED_CELLS
+    IsolatedCells,
+}
 
+impl fmt::Display for ForceMethodErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForceMethodErr::Invalid => write!(f, "Invalid input"),
+            ForceMethodErr::InvalidOrder => {
+                write!(
+                    f,
+                    "The expansion order must be an integer from 1 to {}",
+                    MAX_ORDER
+                )
+            }
+            ForceMethodErr::NoBox => write!(f, "The Ewald force method needs a box size"),
+            ForceMethodErr::NotPeriodic => {
+                write!(
+                    f,
+                    "Only the Ewald and particle mesh force methods take a box size"
+                )
+            }
+            ForceMethodErr::InvalidBox => write!(f, "The box size must be positive"),
+            ForceMethodErr::InvalidCells => {
+                write!(
+                    f,
+                    "The cells per side of the mesh must be a power of two from {} to {}",
+                    MIN_CELLS, MAX_CELLS
+                )
+            }
+            ForceMethodErr::IsolatedCells => {
+                write!(
+                    f,
+                    "Without a box the mesh can have at most {} cells per side",
+                    MAX_ISOLATED_CELLS
+                )
+            }
+        }
+    }
+}
+#[cfg(feature = "python")]
 impl From<ForceMethodErr> for PyErr {
-    fn from(_: ForceMethodErr) -> Self {
-        PyValueError::new_err("Invalid input")
+    fn from(v: ForceMethodErr) -> Self {
+        PyValueError::new_err(v.to_string())
     }
 }
-pub fn get_force(force_method: u8) -> Result<ForceMethod, ForceMethodErr> {
+
+// highest expansion order of the fast multipole method
+pub const MAX_ORDER: usize = 12;
+// Cells per side of the particle mesh. Every node takes 16 bytes for the
+// transform, 8 for the potential and 24 for the acceleration, allocated on
+// every step: about 800 MB at 256 cells.
+pub const MIN_CELLS: usize = 16;
+pub const MAX_CELLS: usize = 256;
+// An isolated mesh is transformed on twice the cells per side, with the
+// transform of the Green function kept at 8 bytes a node: about 470 MB at
+// 128 cells.
+pub const MAX_ISOLATED_CELLS: usize = 128;
+
+// A force method of `bima_rs`, kept by the `System`, or the fast multipole
+// method with its expansion order, the Ewald sum in a periodic box of the
+// given size or the particle mesh, which `step` runs instead.
+#[derive(Clone, Debug)]
+pub enum Force {
+    Method(ForceMethod),
+    Fmm(usize),
+    Ewald(f64),
+    Pm(Grid),
+}
+
+// `par` is the expansion order of the fast multipole method, 4 by default,
+// or the cells per side of the particle mesh, 64 by default. `box_size`
+// makes the boundaries periodic, for the Ewald sum and the particle mesh.
+pub fn get_force(
+    force_method: u8,
+    par: Option<f64>,
+    box_size: Option<f64>,
+) -> Result<Force, ForceMethodErr> {
+    if box_size.is_some() && !(3..=5).contains(&force_method) {
+        return Err(ForceMethodErr::NotPeriodic);
+    }
+    if box_size.is_some_and(|size| size <= 0.0) {
+        return Err(ForceMethodErr::InvalidBox);
+    }
     match force_method {
-        0 => Ok(ForceMethod::Direct),
-        1 => Ok(ForceMethod::new_octree()),
-        _ => Err(ForceMethodErr),
+        0 => Ok(Force::Method(ForceMethod::Direct)),
+        1 => Ok(Force::Method(ForceMethod::new_octree())),
+        2 => {
+            let order = par.unwrap_or(4.0);
+            if order.fract() != 0.0 || !(1.0..=MAX_ORDER as f64).contains(&order) {
+                return Err(ForceMethodErr::InvalidOrder);
+            }
+            Ok(Force::Fmm(order as usize))
+        }
+        3 => box_size.map(Force::Ewald).ok_or(ForceMethodErr::NoBox),
+        // 5 adds the pairs of the short range
+        4 | 5 => {
+            let cells = par.unwrap_or(64.0);
+            if cells.fract() != 0.0
+                || !(MIN_CELLS as f64..=MAX_CELLS as f64).contains(&cells)
+                || !(cells as usize).is_power_of_two()
+            {
+                return Err(ForceMethodErr::InvalidCells);
+            }
+            if box_size.is_none() && cells as usize > MAX_ISOLATED_CELLS {
+                return Err(ForceMethodErr::IsolatedCells);
+            }
+            Ok(Force::Pm(Grid {
+                cells: cells as usize,
+                short_range: force_method == 5,
+                box_size,
+            }))
+        }
+        _ => Err(ForceMethodErr::Invalid),
     }
 }
 pub struct IntegratorErr;
+impl fmt::Display for IntegratorErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid input")
+    }
+}
+#[cfg(feature = "python")]
 impl From<IntegratorErr> for PyErr {
-    fn from(_: IntegratorErr) -> Self {
-        PyValueError::new_err("Invalid input")
+    fn from(v: IntegratorErr) -> Self {
+        PyValueError::new_err(v.to_string())
     }
 }
 pub fn get_integrator(integrator: u8) -> Result<Integrator, IntegratorErr> {
@@ -33,26 +159,104 @@ pub fn get_integrator(integrator: u8) -> Result<Integrator, IntegratorErr> {
         _ => Err(IntegratorErr),
     }
 }
+// a stop that is not after the time the bodies are at
+pub struct StopErr;
+impl fmt::Display for StopErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "t_stop must be after the time the bodies are at")
+    }
+}
+#[cfg(feature = "python")]
+impl From<StopErr> for PyErr {
+    fn from(v: StopErr) -> Self {
+        PyValueError::new_err(v.to_string())
+    }
+}
+pub fn check_stop(t_start: f64, t_stop: f64) -> Result<(), StopErr> {
+    match t_stop > t_start {
+        true => Ok(()),
+        false => Err(StopErr),
+    }
+}
 pub enum TimestepMethodErr {
     NoDelta,
     Invalid,
+    InvalidEta,
+    // block timesteps with an integrator other than Hermite or leapfrog
+    NoScheme,
+    // Hermite without block timesteps
+    NoBlock,
+    // block timesteps with a force method other than the direct sum
+    NotDirect,
 }
+impl fmt::Display for TimestepMethodErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestepMethodErr::Invalid => write!(f, "Invalid input"),
+            TimestepMethodErr::NoDelta => write!(f, "No delta value"),
+            TimestepMethodErr::InvalidEta => {
+                write!(f, "The accuracy parameter must be positive")
+            }
+            TimestepMethodErr::NoScheme => write!(
+                f,
+                "Block timesteps need the Hermite or the leapfrog integrator"
+            ),
+            TimestepMethodErr::NoBlock => {
+                write!(f, "The Hermite integrator needs block timesteps")
+            }
+            TimestepMethodErr::NotDirect => {
+                write!(f, "Block timesteps need the direct force method")
+            }
+        }
+    }
+}
+#[cfg(feature = "python")]
 impl From<TimestepMethodErr> for PyErr {
     fn from(v: TimestepMethodErr) -> Self {
-        match v {
-            TimestepMethodErr::Invalid => PyValueError::new_err("Invalid input"),
-            TimestepMethodErr::NoDelta => PyValueError::new_err("No delta value"),
-        }
+        PyValueError::new_err(v.to_string())
     }
 }
+// A timestep method of `bima_rs`, kept by the `System`, or block timesteps,
+// which `integrate` runs instead.
+#[derive(Clone, Debug)]
+pub enum Timestep {
+    Method(TimestepMethod),
+    Block(Block),
+}
+
+// `par` is the accuracy parameter of block timesteps, 0.02 by default, and
+// `integrator` picks their scheme, 3 for leapfrog and 4 for Hermite, which
+// only runs with them.
 pub fn get_timestep(
     timestep_method: u8,
     delta_t: Option<f64>,
-) -> Result<TimestepMethod, TimestepMethodErr> {
+    par: Option<f64>,
+    integrator: u8,
+) -> Result<Timestep, TimestepMethodErr> {
     match timestep_method {
         0 => {
             let delta_t = delta_t.ok_or(TimestepMethodErr::NoDelta)?;
-            Ok(TimestepMethod::Constant(delta_t))
+            if integrator == 4 {
+                return Err(TimestepMethodErr::NoBlock);
+            }
+            Ok(Timestep::Method(TimestepMethod::Constant(delta_t)))
+        }
+        2 => {
+            let delta_t = delta_t.ok_or(TimestepMethodErr::NoDelta)?;
+            let eta = par.unwrap_or(0.02);
+            if eta.is_nan() || eta <= 0.0 {
+                return Err(TimestepMethodErr::InvalidEta);
+            }
+            let scheme = match integrator {
+                3 => Scheme::LeapFrog,
+                4 => Scheme::Hermite,
+                _ => return Err(TimestepMethodErr::NoScheme),
+            };
+            Ok(Timestep::Block(Block {
+                delta_t,
+                eta,
+                scheme,
+            }))
         }
         _ => Err(TimestepMethodErr::Invalid),
     }
@@ -61,13 +265,39 @@ pub fn get_timestep(
 pub enum CloseEncounterErr {
     NoPar,
     Invalid,
+    InvalidRadius,
+    // regularisation with block timesteps
+    Block,
+    // regularisation with non-gravitational forces
+    NonGravity,
+    // regularisation with a force method other than the direct sum
+    NotDirect,
 }
+impl fmt::Display for CloseEncounterErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloseEncounterErr::Invalid => write!(f, "Invalid input"),
+            CloseEncounterErr::NoPar => write!(f, "No delta value"),
+            CloseEncounterErr::InvalidRadius => {
+                write!(f, "The regularization radius must be positive")
+            }
+            CloseEncounterErr::Block => {
+                write!(f, "Regularization does not work with block timesteps")
+            }
+            CloseEncounterErr::NonGravity => write!(
+                f,
+                "Regularization does not work with non-gravitational forces"
+            ),
+            CloseEncounterErr::NotDirect => {
+                write!(f, "Regularization only works with the direct force method")
+            }
+        }
+    }
+}
+#[cfg(feature = "python")]
 impl From<CloseEncounterErr> for PyErr {
     fn from(v: CloseEncounterErr) -> Self {
-        match v {
-            CloseEncounterErr::Invalid => PyValueError::new_err("Invalid input"),
-            CloseEncounterErr::NoPar => PyValueError::new_err("No delta value"),
-        }
+        PyValueError::new_err(v.to_string())
     }
 }
 pub fn get_close(
@@ -87,3 +317,234 @@ pub fn get_close(
         _ => Err(CloseEncounterErr::Invalid),
     }
 }
+
+// The radius the regularized method takes close encounters in from, when
+// `par` is given with it.
+pub fn get_regular(
+    close_encounter: u8,
+    par: Option<f64>,
+) -> Result<Option<f64>, CloseEncounterErr> {
+    match (close_encounter, par) {
+        (2, Some(radius)) if radius.is_nan() || radius <= 0.0 => {
+            Err(CloseEncounterErr::InvalidRadius)
+        }
+        (2, radius) => Ok(radius),
+        _ => Ok(None),
+    }
+}
+
+pub enum BoundaryErr {
+    NoPar,
+    Invalid,
+}
+impl fmt::Display for BoundaryErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoundaryErr::Invalid => write!(f, "Invalid input"),
+            BoundaryErr::NoPar => write!(f, "No boundary radius"),
+        }
+    }
+}
+#[cfg(feature = "python")]
+impl From<BoundaryErr> for PyErr {
+    fn from(v: BoundaryErr) -> Self {
+        PyValueError::new_err(v.to_string())
+    }
+}
+pub fn get_boundary(
+    boundary: Option<u8>,
+    par: Option<f64>,
+) -> Result<Option<Boundary>, BoundaryErr> {
+    let Some(boundary) = boundary else {
+        return Ok(None);
+    };
+    let par = par.ok_or(BoundaryErr::NoPar)?;
+    match boundary {
+        0 => Ok(Some(Boundary::Radius(par))),
+        1 => Ok(Some(Boundary::Unbound(par))),
+        _ => Err(BoundaryErr::Invalid),
+    }
+}
+
+pub enum EventErr {
+    Invalid,
+    NoBody,
+    NoAxis,
+}
+impl fmt::Display for EventErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventErr::Invalid => write!(f, "Invalid input"),
+            EventErr::NoBody => write!(f, "Event refers to a body that does not exist"),
+            EventErr::NoAxis => write!(f, "Axis must be 0, 1, or 2"),
+        }
+    }
+}
+#[cfg(feature = "python")]
+impl From<EventErr> for PyErr {
+    fn from(v: EventErr) -> Self {
+        PyValueError::new_err(v.to_string())
+    }
+}
+// (condition, body, other, par, stop), for a plane `other` is the axis
+pub type EventArg = (u8, usize, usize, f64, bool);
+
+pub fn get_events(events: Option<Vec<EventArg>>, n_objects: usize) -> Result<Vec<Event>, EventErr> {
+    events
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(condition, body, other, par, stop)| {
+            if body >= n_objects {
+                return Err(EventErr::NoBody);
+            }
+            let condition = match condition {
+                0 | 2 if other >= n_objects => return Err(EventErr::NoBody),
+                0 => Condition::Approach(body, other),
+                1 if other > 2 => return Err(EventErr::NoAxis),
+                1 => Condition::Plane(body, other, par),
+                2 => Condition::Distance(body, other, par),
+                _ => return Err(EventErr::Invalid),
+            };
+            Ok(Event { condition, stop })
+        })
+        .collect()
+}
+
+pub enum PotentialErr {
+    Invalid,
+    WrongPar,
+    // a parameter that is NaN or infinite
+    NotFinite,
+    InvalidMass,
+    // a scale length that is not positive, or negative for Miyamoto-Nagai
+    InvalidScale,
+}
+impl fmt::Display for PotentialErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PotentialErr::Invalid => write!(f, "Invalid input"),
+            PotentialErr::WrongPar => write!(f, "Wrong number of parameters for the potential"),
+            PotentialErr::NotFinite => write!(f, "The parameters of the potential must be finite"),
+            PotentialErr::InvalidMass => write!(f, "The mass of the potential must be positive"),
+            PotentialErr::InvalidScale => write!(
+                f,
+                "The scale lengths of the potential must be positive, or zero for Miyamoto-Nagai"
+            ),
+        }
+    }
+}
+#[cfg(feature = "python")]
+impl From<PotentialErr> for PyErr {
+    fn from(v: PotentialErr) -> Self {
+        PyValueError::new_err(v.to_string())
+    }
+}
+// (potential, parameters), the centre (x, y, z) is the last three parameters
+pub type PotentialArg = (u8, Vec<f64>);
+
+// The masses are scaled by `g` like the masses of the bodies.
+pub fn get_potentials(
+    potentials: Option<Vec<PotentialArg>>,
+    g: f64,
+) -> Result<Vec<Potential>, PotentialErr> {
+    potentials
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(potential, par)| {
+            let expected = match potential {
+                0 => 4,
+                1..=3 => 5,
+                4 => 6,
+                5 => 3,
+                _ => return Err(PotentialErr::Invalid),
+            };
+            if par.len() != expected {
+                return Err(PotentialErr::WrongPar);
+            }
+            if par.iter().any(|par| !par.is_finite()) {
+                return Err(PotentialErr::NotFinite);
+            }
+            if potential != 5 && par[0] <= 0.0 {
+                return Err(PotentialErr::InvalidMass);
+            }
+            // a = 0 or b = 0 of Miyamoto-Nagai are the Plummer sphere and the
+            // Kuzmin disk
+            let scaled = match potential {
+                1..=3 => par[1] > 0.0,
+                4 => par[1] >= 0.0 && par[2] >= 0.0,
+                _ => true,
+            };
+            if !scaled {
+                return Err(PotentialErr::InvalidScale);
+            }
+            let n = par.len();
+            let r = Vec3::new(par[n - 3], par[n - 2], par[n - 1]);
+            let m = g * par[0];
+            Ok(match potential {
+                0 => Potential::PointMass { m, r },
+                1 => Potential::Plummer { m, a: par[1], r },
+                2 => Potential::Hernquist { m, a: par[1], r },
+                3 => Potential::Nfw { m, rs: par[1], r },
+                4 => Potential::MiyamotoNagai {
+                    m,
+                    a: par[1],
+                    b: par[2],
+                    r,
+                },
+                _ => Potential::Uniform(r),
+            })
+        })
+        .collect()
+}
+
+pub enum NonGravityErr {
+    NoBody,
+    NoSpeedOfLight,
+    // drag with a timescale that is not positive
+    InvalidTau,
+}
+impl fmt::Display for NonGravityErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NonGravityErr::NoBody => write!(f, "The central body does not exist"),
+            NonGravityErr::NoSpeedOfLight => write!(f, "The speed of light must be positive"),
+            NonGravityErr::InvalidTau => write!(f, "The drag timescale must be positive"),
+        }
+    }
+}
+#[cfg(feature = "python")]
+impl From<NonGravityErr> for PyErr {
+    fn from(v: NonGravityErr) -> Self {
+        PyValueError::new_err(v.to_string())
+    }
+}
+// (central, c, gr, radiation, drag)
+pub type NonGravityArg = (usize, f64, bool, bool, bool);
+
+pub fn get_nongravity(
+    nongravity: Option<NonGravityArg>,
+    beta: &[f64],
+    tau: &[Option<f64>],
+) -> Result<Option<NonGravity>, NonGravityErr> {
+    let Some((central, c, gr, radiation, drag)) = nongravity else {
+        return Ok(None);
+    };
+    if central >= beta.len() {
+        return Err(NonGravityErr::NoBody);
+    }
+    if (gr || radiation) && c <= 0.0 {
+        return Err(NonGravityErr::NoSpeedOfLight);
+    }
+    if drag && tau.iter().flatten().any(|tau| tau.is_nan() || *tau <= 0.0) {
+        return Err(NonGravityErr::InvalidTau);
+    }
+    Ok(Some(NonGravity::new(
+        central,
+        c,
+        gr,
+        radiation,
+        drag,
+        beta.to_vec(),
+        tau.to_vec(),
+    )))
+}
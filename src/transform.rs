@@ -0,0 +1,211 @@
+use crate::generate::rng::Rng;
+use crate::initial::Initial;
+use bima_rs::vec3::Vec3;
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new(
+        a.y() * b.z() - a.z() * b.y(),
+        a.z() * b.x() - a.x() * b.z(),
+        a.x() * b.y() - a.y() * b.x(),
+    )
+}
+
+// rotation about the z axis, then the x axis
+fn rotate_z(r: Vec3, angle: f64) -> Vec3 {
+    let (s, c) = angle.sin_cos();
+    Vec3::new(c * r.x() - s * r.y(), s * r.x() + c * r.y(), r.z())
+}
+
+fn rotate_x(r: Vec3, angle: f64) -> Vec3 {
+    let (s, c) = angle.sin_cos();
+    Vec3::new(r.x(), c * r.y() - s * r.z(), s * r.y() + c * r.z())
+}
+
+// Position and velocity of the centre of mass, test particles have no weight.
+pub fn centre_of_mass(bodies: &[Initial]) -> Option<(Vec3, Vec3)> {
+    let total: f64 = bodies.iter().map(|b| b.m).sum();
+    if total == 0.0 {
+        return None;
+    }
+    let (r, v) = bodies
+        .iter()
+        .fold((Vec3::zero(), Vec3::zero()), |(r, v), b| {
+            (r + b.m * b.r, v + b.m * b.v)
+        });
+    Some((r / total, v / total))
+}
+
+pub fn barycentre(bodies: &mut [Initial], velocity: bool) {
+    let Some((r_cm, v_cm)) = centre_of_mass(bodies) else {
+        return;
+    };
+    for body in bodies.iter_mut() {
+        body.r -= r_cm;
+        if velocity {
+            body.v -= v_cm;
+        }
+    }
+}
+
+// kinetic and potential energy
+pub fn energies(bodies: &[Initial], g: f64) -> (f64, f64) {
+    let kinetic = bodies.iter().map(|b| 0.5 * b.m * b.v.norm_2()).sum();
+    let mut potential = 0.0;
+    for (i, a) in bodies.iter().enumerate() {
+        for b in bodies[i + 1..].iter() {
+            potential -= g * a.m * b.m / (a.r - b.r).norm();
+        }
+    }
+    (kinetic, potential)
+}
+
+pub fn scale(bodies: &mut [Initial], r_scale: f64, v_scale: f64) {
+    for body in bodies.iter_mut() {
+        body.r = r_scale * body.r;
+        body.v = v_scale * body.v;
+    }
+}
+
+// z-x-z Euler angles, the same rotation that takes the orbital plane of
+// elements (i, Ω, ω) = (beta, alpha, gamma) to the reference frame
+pub fn rotate(bodies: &mut [Initial], alpha: f64, beta: f64, gamma: f64) {
+    let rotation = |r: Vec3| rotate_z(rotate_x(rotate_z(r, gamma), beta), alpha);
+    for body in bodies.iter_mut() {
+        body.r = rotation(body.r);
+        body.v = rotation(body.v);
+    }
+}
+
+// Moves the bodies to their centre of mass, both position and velocity,
+// or position only.
+#[pyfunction]
+#[pyo3(signature = (initial, velocity=true))]
+pub fn to_barycentric(mut initial: Vec<Initial>, velocity: bool) -> Vec<Initial> {
+    barycentre(&mut initial, velocity);
+    initial
+}
+
+#[pyfunction]
+#[pyo3(signature = (initial, alpha, beta, gamma))]
+pub fn rotate_euler(mut initial: Vec<Initial>, alpha: f64, beta: f64, gamma: f64) -> Vec<Initial> {
+    rotate(&mut initial, alpha, beta, gamma);
+    initial
+}
+
+// Rotates the bodies so that the total angular momentum about the centre of
+// mass points along z, the line of nodes of the old x-y plane becoming the x
+// axis.
+#[pyfunction]
+#[pyo3(signature = (initial))]
+pub fn to_invariable_plane(mut initial: Vec<Initial>) -> PyResult<Vec<Initial>> {
+    let (r_cm, v_cm) =
+        centre_of_mass(&initial).ok_or_else(|| PyValueError::new_err("Total mass is zero"))?;
+    let l = initial
+        .iter()
+        .fold(Vec3::zero(), |l, b| l + b.m * cross(b.r - r_cm, b.v - v_cm));
+    if l.norm() == 0.0 {
+        return Err(PyValueError::new_err("Total angular momentum is zero"));
+    }
+    let node = l.x().atan2(-l.y());
+    let i = (l.z() / l.norm()).clamp(-1., 1.).acos();
+    for body in initial.iter_mut() {
+        body.r = rotate_x(rotate_z(body.r, -node), -i);
+        body.v = rotate_x(rotate_z(body.v, -node), -i);
+    }
+    Ok(initial)
+}
+
+// Galilean boost by `v`, and a shift by `r`.
+#[pyfunction]
+#[pyo3(signature = (initial, v, r=None))]
+pub fn boost(
+    mut initial: Vec<Initial>,
+    v: (f64, f64, f64),
+    r: Option<(f64, f64, f64)>,
+) -> Vec<Initial> {
+    let v = Vec3::new(v.0, v.1, v.2);
+    let r = r.map_or(Vec3::zero(), |r| Vec3::new(r.0, r.1, r.2));
+    for body in initial.iter_mut() {
+        body.r += r;
+        body.v += v;
+    }
+    initial
+}
+
+// Scales the velocities about the centre of mass to the ratio of kinetic to
+// potential energy, 0.5 being virial equilibrium.
+#[pyfunction]
+#[pyo3(signature = (initial, virial_ratio=0.5, g=1.0))]
+pub fn scale_virial(
+    mut initial: Vec<Initial>,
+    virial_ratio: f64,
+    g: f64,
+) -> PyResult<Vec<Initial>> {
+    if virial_ratio < 0.0 {
+        return Err(PyValueError::new_err("virial_ratio cannot be negative"));
+    }
+    let (r_cm, v_cm) =
+        centre_of_mass(&initial).ok_or_else(|| PyValueError::new_err("Total mass is zero"))?;
+    barycentre(&mut initial, true);
+    let (kinetic, potential) = energies(&initial, g);
+    if kinetic == 0.0 {
+        return Err(PyValueError::new_err("Kinetic energy is zero"));
+    }
+    scale(
+        &mut initial,
+        1.0,
+        (-virial_ratio * potential / kinetic).sqrt(),
+    );
+    for body in initial.iter_mut() {
+        body.r += r_cm;
+        body.v += v_cm;
+    }
+    Ok(initial)
+}
+
+// Scales the positions by s and the velocities by 1/√s about the centre of
+// mass, which keeps the virial ratio, until the energy is `energy`.
+#[pyfunction]
+#[pyo3(signature = (initial, energy, g=1.0))]
+pub fn scale_energy(mut initial: Vec<Initial>, energy: f64, g: f64) -> PyResult<Vec<Initial>> {
+    let (r_cm, v_cm) =
+        centre_of_mass(&initial).ok_or_else(|| PyValueError::new_err("Total mass is zero"))?;
+    barycentre(&mut initial, true);
+    let (kinetic, potential) = energies(&initial, g);
+    let s = (kinetic + potential) / energy;
+    if !(s > 0.0 && s.is_finite()) {
+        return Err(PyValueError::new_err(
+            "The energy cannot be reached by scaling",
+        ));
+    }
+    scale(&mut initial, s, 1. / s.sqrt());
+    for body in initial.iter_mut() {
+        body.r += r_cm;
+        body.v += v_cm;
+    }
+    Ok(initial)
+}
+
+// A copy of the bodies with gaussian noise of standard deviation `sigma_r`
+// on every component of the positions and `sigma_v` on the velocities, for
+// the members of an ensemble. The same seed gives the same copy.
+#[pyfunction]
+#[pyo3(signature = (initial, sigma_r, sigma_v, seed=0))]
+pub fn perturb(
+    mut initial: Vec<Initial>,
+    sigma_r: f64,
+    sigma_v: f64,
+    seed: u64,
+) -> PyResult<Vec<Initial>> {
+    if sigma_r < 0.0 || sigma_v < 0.0 {
+        return Err(PyValueError::new_err("sigma cannot be negative"));
+    }
+    let mut rng = Rng::new(seed);
+    let mut noise = |sigma: f64| sigma * Vec3::new(rng.normal(), rng.normal(), rng.normal());
+    for body in initial.iter_mut() {
+        body.r += noise(sigma_r);
+        body.v += noise(sigma_v);
+    }
+    Ok(initial)
+}
@@ -0,0 +1,160 @@
+#[cfg(feature = "python")]
+use pyo3::{PyErr, exceptions::PyValueError, prelude::*};
+use std::fmt;
+
+// CODATA 2018 and IAU 2015 nominal values, in SI
+pub const G: f64 = 6.67430e-11;
+pub const AU: f64 = 1.495978707e11;
+pub const PARSEC: f64 = 3.085677581491367e16;
+pub const M_SUN: f64 = 1.98840987e30;
+pub const YEAR: f64 = 365.25 * 86400.;
+
+// Length, mass and time units in SI together with G expressed in them. The
+// bodies are integrated with G = 1 by scaling every mass by `g`.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct UnitSystem {
+    pub name: String,
+    pub length: f64,
+    pub mass: f64,
+    pub time: f64,
+    pub g: f64,
+}
+
+#[derive(Debug)]
+pub enum UnitsErr {
+    NotPositive,
+    Invalid(String),
+}
+
+impl fmt::Display for UnitsErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnitsErr::NotPositive => write!(f, "Units must be positive"),
+            UnitsErr::Invalid(name) => write!(f, "Unknown unit system {}", name),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<UnitsErr> for PyErr {
+    fn from(value: UnitsErr) -> Self {
+        PyValueError::new_err(value.to_string())
+    }
+}
+
+impl Default for UnitSystem {
+    fn default() -> Self {
+        UnitSystem::nbody()
+    }
+}
+
+impl UnitSystem {
+    pub fn new(length: f64, mass: f64, time: f64, name: Option<String>) -> Result<Self, UnitsErr> {
+        if length <= 0.0 || mass <= 0.0 || time <= 0.0 {
+            return Err(UnitsErr::NotPositive);
+        }
+        Ok(UnitSystem {
+            name: name.unwrap_or_else(|| "custom".to_string()),
+            length,
+            mass,
+            time,
+            g: G * mass * time * time / (length * length * length),
+        })
+    }
+    // one of the systems below by name
+    pub fn from_name(name: &str) -> Result<Self, UnitsErr> {
+        match name {
+            "nbody" => Ok(UnitSystem::nbody()),
+            "si" => Ok(UnitSystem::si()),
+            "au_msun_yr" => UnitSystem::au_msun_yr(),
+            "pc_msun_myr" => UnitSystem::pc_msun_myr(),
+            _ => Err(UnitsErr::Invalid(name.to_string())),
+        }
+    }
+    // dimensionless, G = 1 without a physical scale
+    pub fn nbody() -> Self {
+        UnitSystem {
+            name: "nbody".to_string(),
+            length: 1.0,
+            mass: 1.0,
+            time: 1.0,
+            g: 1.0,
+        }
+    }
+    pub fn si() -> Self {
+        UnitSystem {
+            name: "si".to_string(),
+            length: 1.0,
+            mass: 1.0,
+            time: 1.0,
+            g: G,
+        }
+    }
+    pub fn au_msun_yr() -> Result<Self, UnitsErr> {
+        UnitSystem::new(AU, M_SUN, YEAR, Some("au_msun_yr".to_string()))
+    }
+    pub fn pc_msun_myr() -> Result<Self, UnitsErr> {
+        UnitSystem::new(PARSEC, M_SUN, 1e6 * YEAR, Some("pc_msun_myr".to_string()))
+    }
+    pub fn velocity(&self) -> f64 {
+        self.length / self.time
+    }
+    pub fn acceleration(&self) -> f64 {
+        self.length / (self.time * self.time)
+    }
+    pub fn energy(&self) -> f64 {
+        self.mass * self.velocity() * self.velocity()
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl UnitSystem {
+    #[new]
+    #[pyo3(signature = (length, mass, time, name=None))]
+    fn py_new(length: f64, mass: f64, time: f64, name: Option<String>) -> PyResult<Self> {
+        Ok(UnitSystem::new(length, mass, time, name)?)
+    }
+    #[staticmethod]
+    #[pyo3(name = "nbody")]
+    fn py_nbody() -> Self {
+        UnitSystem::nbody()
+    }
+    #[staticmethod]
+    #[pyo3(name = "si")]
+    fn py_si() -> Self {
+        UnitSystem::si()
+    }
+    #[staticmethod]
+    #[pyo3(name = "au_msun_yr")]
+    fn py_au_msun_yr() -> PyResult<Self> {
+        Ok(UnitSystem::au_msun_yr()?)
+    }
+    #[staticmethod]
+    #[pyo3(name = "pc_msun_myr")]
+    fn py_pc_msun_myr() -> PyResult<Self> {
+        Ok(UnitSystem::pc_msun_myr()?)
+    }
+    #[getter(velocity)]
+    fn get_velocity(&self) -> f64 {
+        self.velocity()
+    }
+    #[getter(acceleration)]
+    fn get_acceleration(&self) -> f64 {
+        self.acceleration()
+    }
+    #[getter(energy)]
+    fn get_energy(&self) -> f64 {
+        self.energy()
+    }
+    fn __repr__(&self) -> String {
+        format!(
+            "UnitSystem(name={}, length={:e} m, mass={:e} kg, time={:e} s, G={:.9e})",
+            self.name, self.length, self.mass, self.time, self.g
+        )
+    }
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
@@ -0,0 +1,597 @@
+use _bima::initial::Initial;
+use _bima::simulation::Simulation;
+use _bima::simulation::driver::{Options, run_memory};
+use _bima::units::UnitSystem;
+use bima_rs::vec3::Vec3;
+use std::f64::consts::PI;
+
+// Known problems run through `run_memory` with the integrators and the close
+// encounter methods, each bound about twice the error of its run. The stages
+// of RK4 and Bulirsch-Stoer see the other bodies where they were at the start
+// of the step, so on these problems they converge at first order, and the
+// rows of leapfrog are taken before its last drift.
+
+// The methods of one run, with the bound of its error.
+struct Case {
+    name: &'static str,
+    integrator: u8,
+    timestep_method: u8,
+    delta_t: f64,
+    close_encounter: u8,
+    ce_par: Option<f64>,
+    bound: f64,
+}
+
+impl Case {
+    fn options(&self, t_stop: f64) -> Options {
+        Options {
+            integrator: self.integrator,
+            timestep_method: self.timestep_method,
+            delta_t: Some(self.delta_t),
+            close_encounter: self.close_encounter,
+            ce_par: self.ce_par,
+            t_stop,
+            threads: 1,
+            ..Default::default()
+        }
+    }
+    // The rows of every object up to `t`. A row is the start of a step, the
+    // run goes half a step further to keep the row at `t`.
+    fn run(&self, initial: &[Initial], t: f64) -> Vec<Vec<Vec<f64>>> {
+        let simulation = Simulation::from_initial(initial, UnitSystem::nbody()).unwrap();
+        let options = self.options(t + 0.5 * self.delta_t);
+        let (objects, _, _, _) = run_memory(&simulation, options, &mut ()).unwrap();
+        objects
+    }
+    // potential of unit masses at `r`, that of the force of the close
+    // encounter method
+    fn potential(&self, r: f64) -> f64 {
+        match (self.close_encounter, self.ce_par) {
+            (0, Some(s)) if r < s => (r - 2.0 * s) / (s * s),
+            (1, Some(eps)) => -(eps / r).atan() / eps,
+            _ => -1.0 / r,
+        }
+    }
+}
+
+fn initial(m: f64, r: Vec3, v: Vec3) -> Initial {
+    Initial {
+        m,
+        r,
+        v,
+        beta: 0.0,
+        tau: None,
+    }
+}
+
+fn position(row: &[f64]) -> Vec3 {
+    Vec3::new(row[1], row[2], row[3])
+}
+
+fn velocity(row: &[f64]) -> Vec3 {
+    Vec3::new(row[4], row[5], row[6])
+}
+
+// of the row `k` of every object
+fn energy(m: &[f64], objects: &[Vec<Vec<f64>>], k: usize, case: &Case) -> f64 {
+    let mut e = 0.0;
+    for i in 0..m.len() {
+        e += 0.5 * m[i] * velocity(&objects[i][k]).norm_2();
+        for j in i + 1..m.len() {
+            let r = position(&objects[j][k]) - position(&objects[i][k]);
+            e += m[i] * m[j] * case.potential(r.norm());
+        }
+    }
+    e
+}
+
+// largest relative change of the energy over the rows
+fn energy_error(m: &[f64], objects: &[Vec<Vec<f64>>], case: &Case) -> f64 {
+    let first = energy(m, objects, 0, case);
+    (0..objects[0].len())
+        .map(|k| ((energy(m, objects, k, case) - first) / first).abs())
+        .fold(0.0, f64::max)
+}
+
+// the row of the time closest to `t`
+fn at(rows: &[Vec<f64>], t: f64) -> &[f64] {
+    let row = rows
+        .iter()
+        .min_by(|a, b| (a[0] - t).abs().total_cmp(&(b[0] - t).abs()))
+        .unwrap();
+    assert!((row[0] - t).abs() < 1e-9, "no row at {}", t);
+    row
+}
+
+fn check(case: &Case, error: f64) {
+    assert!(
+        error < case.bound,
+        "{}: error={:e} bound={:e}",
+        case.name,
+        error,
+        case.bound
+    );
+}
+
+// Masses 0.8 and 0.2 on an orbit of a = 1 and e = 0.5 from the pericentre.
+const KEPLER: (f64, f64, f64, f64) = (0.8, 0.2, 1.0, 0.5);
+
+// the separation at `t`, from the equation of Kepler
+fn kepler(t: f64) -> Vec3 {
+    let (m1, m2, a, e) = KEPLER;
+    let mean = (m1 + m2) / (a * a * a);
+    let anomaly = mean.sqrt() * t;
+    let mut u = anomaly;
+    for _ in 0..50 {
+        u -= (u - e * u.sin() - anomaly) / (1.0 - e * u.cos());
+    }
+    Vec3::new(a * (u.cos() - e), a * (1.0 - e * e).sqrt() * u.sin(), 0.0)
+}
+
+#[test]
+fn kepler_follows_the_analytic_orbit() {
+    let (m1, m2, a, e) = KEPLER;
+    let m = m1 + m2;
+    let (r, v) = (a * (1.0 - e), (m / a * (1.0 + e) / (1.0 - e)).sqrt());
+    let initial = [
+        initial(
+            m1,
+            Vec3::new(-m2 / m * r, 0.0, 0.0),
+            Vec3::new(0.0, -m2 / m * v, 0.0),
+        ),
+        initial(
+            m2,
+            Vec3::new(m1 / m * r, 0.0, 0.0),
+            Vec3::new(0.0, m1 / m * v, 0.0),
+        ),
+    ];
+    // two orbits, with every integrator and close encounter method but the
+    // regularisation with block timesteps, which the driver turns down
+    let t_stop = 4.0 * PI;
+    let cases = [
+        Case {
+            name: "euler",
+            integrator: 0,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 0.19,
+        },
+        Case {
+            name: "euler softened",
+            integrator: 0,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 1,
+            ce_par: Some(1e-3),
+            bound: 0.19,
+        },
+        Case {
+            name: "euler truncated",
+            integrator: 0,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 0,
+            ce_par: Some(1e-3),
+            bound: 0.19,
+        },
+        Case {
+            name: "euler regularised",
+            integrator: 0,
+            timestep_method: 0,
+            delta_t: 0.25,
+            close_encounter: 2,
+            ce_par: Some(2.0),
+            bound: 2e-13,
+        },
+        Case {
+            name: "rk4",
+            integrator: 1,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 3e-2,
+        },
+        Case {
+            name: "rk4 softened",
+            integrator: 1,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 1,
+            ce_par: Some(1e-3),
+            bound: 3.1e-2,
+        },
+        Case {
+            name: "rk4 truncated",
+            integrator: 1,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 0,
+            ce_par: Some(1e-3),
+            bound: 3e-2,
+        },
+        Case {
+            name: "rk4 regularised",
+            integrator: 1,
+            timestep_method: 0,
+            delta_t: 0.25,
+            close_encounter: 2,
+            ce_par: Some(2.0),
+            bound: 2e-13,
+        },
+        Case {
+            name: "bs",
+            integrator: 2,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 3e-2,
+        },
+        Case {
+            name: "bs softened",
+            integrator: 2,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 1,
+            ce_par: Some(1e-3),
+            bound: 3.1e-2,
+        },
+        Case {
+            name: "bs truncated",
+            integrator: 2,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 0,
+            ce_par: Some(1e-3),
+            bound: 3e-2,
+        },
+        Case {
+            name: "bs regularised",
+            integrator: 2,
+            timestep_method: 0,
+            delta_t: 0.25,
+            close_encounter: 2,
+            ce_par: Some(2.0),
+            bound: 2e-13,
+        },
+        Case {
+            name: "leap_frog",
+            integrator: 3,
+            timestep_method: 0,
+            delta_t: 1e-3,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 1.8e-3,
+        },
+        Case {
+            name: "leap_frog softened",
+            integrator: 3,
+            timestep_method: 0,
+            delta_t: 1e-3,
+            close_encounter: 1,
+            ce_par: Some(1e-3),
+            bound: 2.2e-3,
+        },
+        Case {
+            name: "leap_frog truncated",
+            integrator: 3,
+            timestep_method: 0,
+            delta_t: 1e-3,
+            close_encounter: 0,
+            ce_par: Some(1e-3),
+            bound: 1.8e-3,
+        },
+        Case {
+            name: "leap_frog regularised",
+            integrator: 3,
+            timestep_method: 0,
+            delta_t: 0.25,
+            close_encounter: 2,
+            ce_par: Some(2.0),
+            bound: 2e-13,
+        },
+        Case {
+            name: "leap_frog block",
+            integrator: 3,
+            timestep_method: 2,
+            delta_t: 0.25,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 3.4e-3,
+        },
+        Case {
+            name: "leap_frog block softened",
+            integrator: 3,
+            timestep_method: 2,
+            delta_t: 0.25,
+            close_encounter: 1,
+            ce_par: Some(1e-3),
+            bound: 3.8e-3,
+        },
+        Case {
+            name: "leap_frog block truncated",
+            integrator: 3,
+            timestep_method: 2,
+            delta_t: 0.25,
+            close_encounter: 0,
+            ce_par: Some(1e-3),
+            bound: 3.4e-3,
+        },
+        Case {
+            name: "hermite block",
+            integrator: 4,
+            timestep_method: 2,
+            delta_t: 0.25,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 2.3e-4,
+        },
+        Case {
+            name: "hermite block softened",
+            integrator: 4,
+            timestep_method: 2,
+            delta_t: 0.25,
+            close_encounter: 1,
+            ce_par: Some(1e-3),
+            bound: 6e-4,
+        },
+        Case {
+            name: "hermite block truncated",
+            integrator: 4,
+            timestep_method: 2,
+            delta_t: 0.25,
+            close_encounter: 0,
+            ce_par: Some(1e-3),
+            bound: 2.3e-4,
+        },
+    ];
+    for case in cases.iter() {
+        let objects = case.run(&initial, t_stop);
+        let error = objects[0]
+            .iter()
+            .zip(objects[1].iter())
+            .map(|(a, b)| (position(b) - position(a) - kepler(a[0])).norm())
+            .fold(0.0, f64::max);
+        check(case, error);
+    }
+}
+
+#[test]
+fn figure_eight_comes_back_after_a_period() {
+    // Chenciner and Montgomery, with the period of Simó
+    let period = 6.32591398;
+    let r = Vec3::new(0.97000436, -0.24308753, 0.0);
+    let v = Vec3::new(-0.93240737, -0.86473146, 0.0);
+    let initial = [
+        initial(1.0, r, -0.5 * v),
+        initial(1.0, -1.0 * r, -0.5 * v),
+        initial(1.0, Vec3::zero(), v),
+    ];
+    let cases = [
+        Case {
+            name: "rk4",
+            integrator: 1,
+            timestep_method: 0,
+            delta_t: period / 20000.0,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 3e-2,
+        },
+        Case {
+            name: "bs",
+            integrator: 2,
+            timestep_method: 0,
+            delta_t: period / 20000.0,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 3e-2,
+        },
+        Case {
+            name: "leap_frog",
+            integrator: 3,
+            timestep_method: 0,
+            delta_t: period / 10000.0,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 8e-4,
+        },
+        Case {
+            name: "hermite block",
+            integrator: 4,
+            timestep_method: 2,
+            delta_t: period / 8.0,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 3.3e-5,
+        },
+        Case {
+            name: "euler chain",
+            integrator: 0,
+            timestep_method: 0,
+            delta_t: period / 8.0,
+            close_encounter: 2,
+            ce_par: Some(3.0),
+            bound: 8e-8,
+        },
+    ];
+    for case in cases.iter() {
+        let objects = case.run(&initial, period);
+        let error = objects
+            .iter()
+            .map(|rows| {
+                let (first, last) = (&rows[0], at(rows, period));
+                (position(last) - position(first)).norm()
+            })
+            .fold(0.0, f64::max);
+        check(case, error);
+    }
+}
+
+#[test]
+fn pythagorean_problem_keeps_its_energy() {
+    // masses 3, 4 and 5 at rest on the corners of a 3-4-5 triangle, through
+    // the first close encounters
+    let m = [3.0, 4.0, 5.0];
+    let initial = [
+        initial(m[0], Vec3::new(1.0, 3.0, 0.0), Vec3::zero()),
+        initial(m[1], Vec3::new(-2.0, -1.0, 0.0), Vec3::zero()),
+        initial(m[2], Vec3::new(1.0, -1.0, 0.0), Vec3::zero()),
+    ];
+    let cases = [
+        Case {
+            name: "hermite block",
+            integrator: 4,
+            timestep_method: 2,
+            delta_t: 0.1,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 1.6e-4,
+        },
+        Case {
+            name: "rk4 softened",
+            integrator: 1,
+            timestep_method: 0,
+            delta_t: 1e-4,
+            close_encounter: 1,
+            ce_par: Some(0.1),
+            bound: 0.16,
+        },
+        Case {
+            name: "leap_frog block",
+            integrator: 3,
+            timestep_method: 2,
+            delta_t: 0.1,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 1e-3,
+        },
+        Case {
+            name: "rk4 regularised",
+            integrator: 1,
+            timestep_method: 0,
+            delta_t: 1e-2,
+            close_encounter: 2,
+            ce_par: Some(1.0),
+            bound: 0.13,
+        },
+        Case {
+            name: "euler chain",
+            integrator: 0,
+            timestep_method: 0,
+            delta_t: 0.5,
+            close_encounter: 2,
+            ce_par: Some(6.0),
+            bound: 5e-12,
+        },
+    ];
+    for case in cases.iter() {
+        let objects = case.run(&initial, 10.0);
+        let error = energy_error(&m, &objects, case);
+        check(case, error);
+    }
+}
+
+// Draws from 0 to 1 with the generator of Marsaglia.
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn uniform(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+    fn direction(&mut self) -> Vec3 {
+        let z = 2.0 * self.uniform() - 1.0;
+        let phi = 2.0 * PI * self.uniform();
+        let s = (1.0 - z * z).sqrt();
+        Vec3::new(s * phi.cos(), s * phi.sin(), z)
+    }
+}
+
+// `n` equal masses of a Plummer sphere in N-body units, sampled as Aarseth,
+// Hénon and Wielen.
+fn plummer(n: usize, seed: u64) -> Vec<Initial> {
+    let mut rng = Xorshift(seed);
+    let scale = 3.0 * PI / 16.0;
+    let mut bodies: Vec<Initial> = (0..n)
+        .map(|_| {
+            // from the mass inside r, the last percent left out
+            let r = 1.0 / ((0.99 * rng.uniform()).powf(-2.0 / 3.0) - 1.0).sqrt();
+            // von Neumann on g(q) = q² (1 - q²)^3.5
+            let q = loop {
+                let (q, g) = (rng.uniform(), 0.1 * rng.uniform());
+                if g < q * q * (1.0 - q * q).powf(3.5) {
+                    break q;
+                }
+            };
+            let v = q * 2f64.sqrt() * (1.0 + r * r).powf(-0.25);
+            initial(
+                1.0 / n as f64,
+                scale * r * rng.direction(),
+                (1.0 / scale.sqrt()) * v * rng.direction(),
+            )
+        })
+        .collect();
+    let n = n as f64;
+    let (r, v) = bodies
+        .iter()
+        .fold((Vec3::zero(), Vec3::zero()), |(r, v), b| (r + b.r, v + b.v));
+    for body in bodies.iter_mut() {
+        body.r -= r / n;
+        body.v -= v / n;
+    }
+    bodies
+}
+
+#[test]
+fn plummer_sphere_keeps_its_energy() {
+    let initial = plummer(64, 7);
+    let m: Vec<f64> = initial.iter().map(|b| b.m).collect();
+    let cases = [
+        Case {
+            name: "leap_frog softened",
+            integrator: 3,
+            timestep_method: 0,
+            delta_t: 1.0 / 256.0,
+            close_encounter: 1,
+            ce_par: Some(0.05),
+            bound: 4.8e-3,
+        },
+        Case {
+            name: "rk4 softened",
+            integrator: 1,
+            timestep_method: 0,
+            delta_t: 1.0 / 128.0,
+            close_encounter: 1,
+            ce_par: Some(0.05),
+            bound: 1.4e-2,
+        },
+        Case {
+            name: "hermite block",
+            integrator: 4,
+            timestep_method: 2,
+            delta_t: 0.125,
+            close_encounter: 2,
+            ce_par: None,
+            bound: 1.6e-6,
+        },
+        Case {
+            name: "leap_frog block softened",
+            integrator: 3,
+            timestep_method: 2,
+            delta_t: 0.125,
+            close_encounter: 1,
+            ce_par: Some(0.05),
+            bound: 6.7e-5,
+        },
+    ];
+    for case in cases.iter() {
+        let objects = case.run(&initial, 2.0);
+        let error = energy_error(&m, &objects, case);
+        check(case, error);
+    }
+}